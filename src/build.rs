@@ -1,92 +1,356 @@
 use crate::builder;
 use crate::config;
-use crate::config::{Arch, LibType, Library, Platform};
+use crate::config::{Arch, Config, LibType, Library, Platform};
+use crate::logging;
 use crate::post_build;
 use crate::repo;
-use anyhow::Result;
-use std::collections::HashMap;
+use crate::sizes;
+use crate::timings::BuildTimings;
+use crate::ui;
+use crate::utils::ExecOptions;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 #[derive(Debug, Clone, Copy)]
 pub struct BuildOptions {
-    pub verbose: bool,
+    pub verbose: u8,
+    pub dry_run: bool,
+    pub offline: bool,
+    /// Overrides `build.make_concurrent_jobs` when set.
+    pub jobs: Option<u32>,
     pub force: bool,
+    pub log_file: bool,
+    /// Continue with the remaining targets after one fails, like `make -k`.
+    pub keep_going: bool,
+    /// Delete and clone every repo again before building.
+    pub reclone: bool,
+    /// Let `reclone` delete checkouts found via `paths.repo_path`.
+    pub reclone_user_repos: bool,
+    /// Build the checkouts as they are, without resetting and cleaning them first.
+    pub resume: bool,
+    /// With `resume`, still reuse cached targets; for callers that invalidate
+    /// the edited libraries themselves with [`invalidate_build_cache`], like `watch`.
+    pub trust_cache: bool,
+    /// Fail instead of warning when a library to package or merge is missing.
+    pub strict: bool,
+    /// Run every library's `make check` on host-runnable targets.
+    pub run_tests: bool,
+    /// Overrides `build.smoke_test` when set.
+    pub smoke_test: bool,
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
 }
 
-pub async fn run(options: BuildOptions) -> Result<()> {
+impl BuildOptions {
+    pub fn exec(&self) -> ExecOptions {
+        ExecOptions {
+            verbose: self.verbose,
+            dry_run: self.dry_run,
+            offline: self.offline,
+            ..ExecOptions::default()
+        }
+    }
+
+    /// Whether up-to-date targets may be reused; not when resuming, as the
+    /// cache can't see local edits, unless the caller vouches for it.
+    fn reuses_cache(&self) -> bool {
+        !self.resume || self.trust_cache
+    }
+
+    /// Whether missing libraries are errors; never in a dry run, where nothing is built.
+    pub fn strict_artifacts(&self) -> bool {
+        self.strict && !self.dry_run
+    }
+}
+
+/// One per-arch library produced (or reused) by [`run`].
+#[derive(Debug, Clone)]
+pub struct ArtifactInfo {
+    pub library: Library,
+    pub platform: Platform,
+    pub arch: Arch,
+    pub lib_type: LibType,
+    /// Packaged location under `build/lib` for Android/Harmony; the per-arch
+    /// library under `build/<platform>` (merged into the xcframework) on Apple platforms.
+    pub path: PathBuf,
+    /// Reused from a previous build instead of rebuilt.
+    pub cached: bool,
+    /// Output of the library's `make check` when its tests ran for this target.
+    pub test_log: Option<PathBuf>,
+    /// Flags the library was compiled with; `None` when reused from the cache.
+    pub flags: Option<builder::BuildFlags>,
+}
+
+/// Targets that failed under `--keep-going`, so libraries depending on them
+/// are skipped instead of built against a missing or stale prefix.
+#[derive(Debug, Default)]
+pub struct FailedTargets(BTreeSet<(Platform, Arch, Library)>);
+
+impl FailedTargets {
+    pub fn insert(&mut self, platform: Platform, arch: Arch, library: Library) {
+        self.0.insert((platform, arch, library));
+    }
+
+    /// A dependency of `library` that failed (or was skipped) for the same target.
+    pub fn failed_dependency(
+        &self,
+        platform: Platform,
+        arch: Arch,
+        library: &Library,
+    ) -> Result<Option<Library>> {
+        Ok(library
+            .with_dependencies()?
+            .into_iter()
+            .find(|dep| dep != library && self.0.contains(&(platform, arch, *dep))))
+    }
+}
+
+/// What [`run`] built, for callers driving the crate programmatically.
+#[derive(Debug, Clone, Default)]
+pub struct BuildReport {
+    pub artifacts: Vec<ArtifactInfo>,
+}
+
+impl BuildReport {
+    pub fn cached_count(&self) -> usize {
+        self.artifacts.iter().filter(|a| a.cached).count()
+    }
+
+    pub fn built_count(&self) -> usize {
+        self.artifacts.len() - self.cached_count()
+    }
+
+    /// Artifacts whose file does not exist, e.g. because `make install` succeeded
+    /// without installing the library.
+    pub fn missing_artifacts(&self) -> Vec<&ArtifactInfo> {
+        self.artifacts.iter().filter(|a| !a.path.exists()).collect()
+    }
+
+    /// Fail with every missing artifact listed, instead of letting later steps
+    /// skip them with a warning.
+    pub fn verify(&self) -> Result<()> {
+        let missing = self.missing_artifacts();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let list = missing
+            .iter()
+            .map(|a| {
+                format!(
+                    "  - {} / {} / {}: {}",
+                    a.library,
+                    a.platform,
+                    a.arch,
+                    a.path.display()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        anyhow::bail!("{} expected artifact(s) missing:\n{list}", missing.len());
+    }
+}
+
+/// Clone missing repos and reset the checkouts to their configured versions;
+/// with `resume`, local edits are kept and only missing repos are cloned.
+/// The build cache doesn't see such edits, so `resume` also rebuilds every target.
+/// Up to `general.max_parallel_clones` repos are prepared at once.
+async fn prepare_repos(config: &Config, repos: &[repo::Repo], options: BuildOptions) -> Result<()> {
+    if options.resume {
+        log::warn!(
+            "--resume: building the checkouts as they are, artifacts may not match the configured versions"
+        );
+    }
+    let results = repo::prepare_parallel(
+        repos,
+        config.general.max_parallel_clones,
+        |repo| async move {
+            if options.reclone {
+                repo.remove_for_reclone(options.reclone_user_repos, options.exec())?;
+            }
+            repo.ensure(options.exec()).await?;
+            if !options.resume {
+                repo.clean(options.exec()).await?;
+            }
+            repo.ensure_model(options.exec()).await
+        },
+    )
+    .await;
+    results.into_iter().collect()
+}
+
+/// Build with `build_config.toml` from the current directory.
+pub async fn run(options: BuildOptions) -> Result<BuildReport> {
     let config_path = PathBuf::from("build_config.toml");
-    let mut config = config::load_or_create_config(&config_path)?;
+    let config = config::load_or_create_config(&config_path, options.strict_config)?;
+    run_with_config(config, options).await
+}
+
+/// Build every configured library/platform/arch from an already loaded `config`.
+pub async fn run_with_config(mut config: Config, options: BuildOptions) -> Result<BuildReport> {
+    if let Some(jobs) = options.jobs {
+        config.build.make_concurrent_jobs = jobs;
+    }
+    config.general.libraries = config::sort_by_dependencies(&config.general.libraries)?;
 
-    config.general.libraries.sort();
+    if options.log_file || config.general.log_file {
+        let log_path = logging::start_log_file(&config.paths.build_dir.join("logs"))?;
+        log::info!("Writing build log to {}", log_path.display());
+    }
 
     log::info!("Configuration: {:#?}", config);
 
-    let repos = repo::get_repos(&config)?;
-    for repo in &repos {
-        repo.ensure(options.verbose).await?;
-        repo.clean(options.verbose).await?;
+    if config.general.platforms.contains(&Platform::Android) {
+        match crate::platforms::android::build::validate_ndk(&config) {
+            Err(e) if options.dry_run => log::warn!("{e:#}"),
+            result => result?,
+        }
+    }
+    if config.general.platforms.contains(&Platform::Harmony) {
+        match crate::platforms::harmony::build::validate_archs(&config) {
+            Err(e) if options.dry_run => log::warn!("{e:#}"),
+            result => result?,
+        }
     }
 
+    let repos = repo::get_repos(&config)?;
+    prepare_repos(&config, &repos, options).await?;
+
     let repo_map: HashMap<_, _> = repos.iter().map(|r| (r.name.as_str(), r)).collect();
+    let commits = source_commits(&repos).await;
+    let mut timings = BuildTimings::default();
+    let mut report = BuildReport::default();
+    let mut failures = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failed_targets = FailedTargets::default();
 
     for platform in &config.general.platforms {
+        if crate::platforms::darwin::build::sdk_is_optional(*platform)
+            && !crate::platforms::darwin::build::sdk_available(*platform).await
+        {
+            log::warn!("Skipping {platform}: its SDK is not installed (requires a newer Xcode)");
+            continue;
+        }
+
         let archs_for_platform = config.platforms.get_archs_for_platform(platform);
-        let lib_type_for_platform = config.platforms.get_lib_type_for_platform(platform);
+        let lib_types_for_platform = config.platforms.get_lib_types_for_platform(platform);
 
         for library in &config.general.libraries {
             let version = config.get_library_version(library)?;
             for arch in archs_for_platform {
                 let can_reuse_cached_build = !options.force
-                    && build_artifact_ready(
-                        &config.paths.build_dir,
-                        *platform,
-                        *arch,
-                        library,
-                        lib_type_for_platform,
-                    )?;
+                    && options.reuses_cache()
+                    && build_artifact_ready(&config, *platform, *arch, library, &commits)?;
+                let mut built = BuiltTarget::default();
+                if let Some(dep) = failed_targets.failed_dependency(*platform, *arch, library)? {
+                    log::warn!(
+                        "Skipping {library} for {platform} ({arch}): its dependency {dep} failed"
+                    );
+                    failed_targets.insert(*platform, *arch, *library);
+                    skipped.push((*library, *platform, *arch, dep));
+                    continue;
+                }
                 if can_reuse_cached_build {
-                    log::info!("Reusing cached {library} for {platform} ({arch})");
+                    ui::section(format!(
+                        "Reusing cached {}",
+                        target_label(library, *platform, *arch)
+                    ));
+                    logging::emit_event("build_cached", target_event(library, platform, arch));
                 } else if let Some(repo) = repo_map.get(library.repo_name()) {
-                    log::info!("Building {library} for {platform} ({arch})");
-                    let b = builder::Builder::new(
+                    let result = build_target(
+                        &config,
+                        repo,
                         *platform,
                         *arch,
                         *library,
-                        repo,
-                        &config,
-                        options.verbose,
-                    );
-                    b.build().await?;
-                    log::info!("Built {library} for {platform} ({arch}) succeeded!");
+                        options,
+                        &mut timings,
+                    )
+                    .await;
+                    match result {
+                        Err(e) if options.keep_going => {
+                            log::error!("Failed to build {library} for {platform} ({arch}): {e:#}");
+                            failures.push((*library, *platform, *arch, e));
+                            failed_targets.insert(*platform, *arch, *library);
+                            continue;
+                        }
+                        result => built = result?,
+                    }
+                    if !options.dry_run {
+                        write_build_cache_key(&config, *platform, *arch, library, &commits)?;
+                    }
                 }
 
-                package_artifact_if_needed(
-                    &config.paths.build_dir,
-                    *platform,
-                    library,
-                    version,
-                    *arch,
-                    lib_type_for_platform,
-                )?;
+                for lib_type in lib_types_for_platform {
+                    let Some(path) = package_artifact_if_needed(
+                        &config,
+                        *platform,
+                        library,
+                        &version,
+                        *arch,
+                        *lib_type,
+                        options.strict_artifacts(),
+                    )?
+                    else {
+                        continue;
+                    };
+                    report.artifacts.push(ArtifactInfo {
+                        library: *library,
+                        platform: *platform,
+                        arch: *arch,
+                        lib_type: *lib_type,
+                        path,
+                        cached: can_reuse_cached_build,
+                        test_log: built.test_log.clone(),
+                        flags: built.flags.clone(),
+                    });
+                }
             }
 
             if platform.is_darwin() {
-                log::info!("Creating universal binary for {library} for {platform}");
-                crate::platforms::darwin::build::create_universal_binary(
-                    &config.paths.build_dir,
-                    *platform,
-                    library,
-                    lib_type_for_platform,
-                    archs_for_platform,
-                )
-                .await?;
+                create_universal_binaries(&config, *platform, library, options, &mut timings)
+                    .await?;
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        eprintln!("\n{} target(s) failed:", failures.len());
+        for (library, platform, arch, e) in &failures {
+            eprintln!("  - {library} / {platform} / {arch}: {e:#}");
+        }
+        if !skipped.is_empty() {
+            eprintln!("\n{} target(s) skipped:", skipped.len());
+            for (library, platform, arch, dep) in &skipped {
+                eprintln!("  - {library} / {platform} / {arch}: dependency {dep} failed");
             }
         }
+        anyhow::bail!("{} target(s) failed to build", failures.len());
+    }
+    if !options.dry_run {
+        report.verify()?;
+    }
+
+    post_build::merge_static_libraries_if_needed(&config, options.force, options.exec()).await?;
+    assemble_outputs(&config, options, &mut timings).await?;
+
+    timings.print_summary();
+
+    if options.dry_run {
+        println!("\nDry run finished, no commands were executed.\n");
+        return Ok(report);
     }
 
-    post_build::create_xcframework_if_needed(&config).await?;
-    post_build::copy_headers_from_build_artifacts(&config)?;
+    timings.write_json(&config.paths.build_dir.join("build_timings.json"))?;
+    post_build::write_manifest(&config, &repos).await?;
+    if config.general.platforms.contains(&Platform::Harmony) {
+        crate::platforms::harmony::build::write_package_index(&config)?;
+    }
+    post_build::write_checksums(&config.paths.build_dir)?;
+    report_sizes(&config.paths.build_dir)?;
 
     if !config.general.keep_intermediate {
         log::info!("Cleaning up intermediate build artifacts");
@@ -101,64 +365,506 @@ pub async fn run(options: BuildOptions) -> Result<()> {
 
     println!("\n🎉 Build completed successfully!\n");
 
+    Ok(report)
+}
+
+/// Combine the per-arch builds of `library` for an Apple `platform` into one
+/// universal binary per lib type, from whichever configured archs exist.
+async fn create_universal_binaries(
+    config: &Config,
+    platform: Platform,
+    library: &Library,
+    options: BuildOptions,
+    timings: &mut BuildTimings,
+) -> Result<()> {
+    ui::section(format!(
+        "Creating universal binary for {} for {platform}",
+        library.name_with_lib_prefix()
+    ));
+    let started = Instant::now();
+    for lib_type in config.platforms.get_lib_types_for_platform(&platform) {
+        crate::platforms::darwin::build::create_universal_binary(
+            &config.paths.build_dir,
+            platform,
+            library,
+            *lib_type,
+            config.platforms.get_archs_for_platform(&platform),
+            options.strict_artifacts(),
+            options.exec(),
+        )
+        .await?;
+    }
+    timings.record("universal", library, platform, "-", started.elapsed());
+    Ok(())
+}
+
+/// Assemble the xcframeworks in `build/lib/darwin` and the headers in
+/// `build/include` from the install prefixes of `config`'s libraries.
+async fn assemble_outputs(
+    config: &Config,
+    options: BuildOptions,
+    timings: &mut BuildTimings,
+) -> Result<()> {
+    post_build::create_xcframework_if_needed(config, options.force, options.exec(), timings)
+        .await?;
+    post_build::copy_headers_from_build_artifacts(config)?;
     Ok(())
 }
 
-fn build_artifact_ready(
+/// Print the sizes of the shipped files, flagging large changes since the
+/// previous build, and keep them for the next comparison.
+fn report_sizes(build_dir: &Path) -> Result<()> {
+    let last_sizes = build_dir.join(sizes::LAST_SIZES_FILE);
+    let previous = sizes::SizeSummary::load(&last_sizes).unwrap_or_else(|e| {
+        log::warn!("Ignoring the previous build's sizes: {e:#}");
+        None
+    });
+    let summary = sizes::SizeSummary::collect(build_dir)?;
+    summary.print_summary(previous.as_ref());
+    summary.write_json(&last_sizes)
+}
+
+/// Build exactly one library for one platform/arch and package it.
+///
+/// Dependencies (e.g. opus for opusenc) are built for the same target first
+/// unless an up-to-date build of them already exists. Like [`run`], it then
+/// refreshes the packages, xcframeworks and headers of the libraries involved.
+pub async fn run_single(
+    mut config: Config,
+    library: Library,
+    platform: Platform,
+    arch: Arch,
+    options: BuildOptions,
+) -> Result<BuildReport> {
+    if let Some(jobs) = options.jobs {
+        config.build.make_concurrent_jobs = jobs;
+    }
+    config.general.libraries = library.with_dependencies()?;
+    config.general.platforms = vec![platform];
+
+    let repos = repo::get_repos(&config)?;
+    prepare_repos(&config, &repos, options).await?;
+    let commits = source_commits(&repos).await;
+
+    let mut timings = BuildTimings::default();
+    let mut report = BuildReport::default();
+    for lib in &config.general.libraries {
+        let is_dependency = *lib != library;
+        let cached = (is_dependency || !options.force)
+            && options.reuses_cache()
+            && build_artifact_ready(&config, platform, arch, lib, &commits)?;
+        let mut built = BuiltTarget::default();
+        if cached {
+            ui::section(format!(
+                "Reusing cached {}",
+                target_label(lib, platform, arch)
+            ));
+            logging::emit_event("build_cached", target_event(lib, &platform, &arch));
+        } else {
+            let repo = repos
+                .iter()
+                .find(|r| r.name == lib.repo_name())
+                .with_context(|| format!("No repo for {lib}"))?;
+            built = build_target(&config, repo, platform, arch, *lib, options, &mut timings)
+                .await
+                .with_context(|| {
+                    if is_dependency {
+                        format!("Failed to build dependency {lib} of {library}")
+                    } else {
+                        format!("Failed to build {lib}")
+                    }
+                })?;
+            if !options.dry_run {
+                write_build_cache_key(&config, platform, arch, lib, &commits)?;
+            }
+        }
+
+        // dependencies are packaged too, `build/lib` would be stale otherwise
+        let version = config.get_library_version(lib)?;
+        for lib_type in config.platforms.get_lib_types_for_platform(&platform) {
+            let path = package_artifact_if_needed(
+                &config,
+                platform,
+                lib,
+                &version,
+                arch,
+                *lib_type,
+                options.strict_artifacts(),
+            )?;
+            let Some(path) = path.filter(|_| !is_dependency) else {
+                continue;
+            };
+            report.artifacts.push(ArtifactInfo {
+                library: *lib,
+                platform,
+                arch,
+                lib_type: *lib_type,
+                path,
+                cached,
+                test_log: built.test_log.clone(),
+                flags: built.flags.clone(),
+            });
+        }
+        if platform.is_darwin() {
+            create_universal_binaries(&config, platform, lib, options, &mut timings).await?;
+        }
+    }
+
+    if !options.dry_run {
+        report.verify()?;
+    }
+    assemble_outputs(&config, options, &mut timings).await?;
+
+    timings.print_summary();
+    if !options.dry_run {
+        timings.write_json(&config.paths.build_dir.join("build_timings.json"))?;
+    }
+    Ok(report)
+}
+
+fn target_event(library: &Library, platform: &Platform, arch: &Arch) -> serde_json::Value {
+    serde_json::json!({
+        "library": library.to_string(),
+        "platform": platform.to_string(),
+        "arch": arch.to_string(),
+    })
+}
+
+/// Whether binaries for `platform`/`arch` run on this machine, so a library's
+/// tests can: only the native macOS arch. Cross-compiled targets would need an
+/// emulator or device.
+pub fn runs_on_host(platform: Platform, arch: Arch) -> bool {
+    let host_arch = match std::env::consts::ARCH {
+        "aarch64" => Arch::Arm64,
+        "x86_64" => Arch::X86_64,
+        _ => return false,
+    };
+    cfg!(target_os = "macos") && platform == Platform::Macos && arch == host_arch
+}
+
+/// Whether `library`'s tests were requested (`--run-tests` or its `run_tests`)
+/// and can run for this target.
+fn should_run_tests(
+    config: &Config,
+    options: BuildOptions,
+    platform: Platform,
+    arch: Arch,
+    library: &Library,
+) -> bool {
+    let requested = options.run_tests
+        || config
+            .libraries
+            .get(library)
+            .is_some_and(|opts| opts.run_tests);
+    if requested && !runs_on_host(platform, arch) {
+        log::warn!(
+            "Not running the {library} tests for {platform} ({arch}): it can't run on this host"
+        );
+        return false;
+    }
+    requested
+}
+
+/// Where the `make check` output of one target is kept.
+pub fn test_log_path(
     build_dir: &Path,
     platform: Platform,
     arch: Arch,
     library: &Library,
-    lib_type: LibType,
+) -> Result<PathBuf> {
+    Ok(build_dir.join("logs").join("tests").join(format!(
+        "{}-{platform}-{}.log",
+        library.name_with_lib_prefix(),
+        arch_dir_name(platform, arch)?
+    )))
+}
+
+/// What [`build_target`] leaves behind besides the installed library.
+#[derive(Debug, Default)]
+struct BuiltTarget {
+    /// The library's `make check` output, when its tests ran.
+    test_log: Option<PathBuf>,
+    flags: Option<builder::BuildFlags>,
+}
+
+/// Configure/make/install one target, then record its cache key and timing.
+async fn build_target(
+    config: &Config,
+    repo: &repo::Repo,
+    platform: Platform,
+    arch: Arch,
+    library: Library,
+    options: BuildOptions,
+    timings: &mut BuildTimings,
+) -> Result<BuiltTarget> {
+    let target = target_event(&library, &platform, &arch);
+    ui::section(format!(
+        "Building {}",
+        target_label(&library, platform, arch)
+    ));
+    logging::emit_event("build_start", target.clone());
+    let started = Instant::now();
+    let run_tests = should_run_tests(config, options, platform, arch, &library);
+    let flags = builder::Builder::new(platform, arch, library, repo, config, options.exec())
+        .run_tests(run_tests)
+        .smoke_test(options.smoke_test || config.build.smoke_test)
+        .build()
+        .await?;
+    ui::success(format!("Built {}", target_label(&library, platform, arch)));
+    let elapsed = started.elapsed();
+    timings.record("build", library, platform, arch, elapsed);
+    let mut done = target;
+    done["duration_secs"] = elapsed.as_secs_f64().into();
+    logging::emit_event("build_done", done);
+    let test_log = (run_tests && !options.dry_run)
+        .then(|| test_log_path(&config.paths.build_dir, platform, arch, &library))
+        .transpose()?;
+    Ok(BuiltTarget {
+        test_log,
+        flags: Some(flags),
+    })
+}
+
+const CACHE_KEY_PREFIX: &str = ".opusbuild-";
+
+/// HEAD commit of each git checkout by repo name, part of the build cache key.
+/// Tarball sources and checkouts that don't exist yet (dry run) have none.
+pub type SourceCommits = HashMap<String, String>;
+
+/// Resolves the [`SourceCommits`] of the prepared `repos`.
+pub async fn source_commits(repos: &[repo::Repo]) -> SourceCommits {
+    let mut commits = SourceCommits::new();
+    for repo in repos {
+        if let Ok(commit) = repo.head_commit().await {
+            commits.insert(repo.name.clone(), commit);
+        }
+    }
+    commits
+}
+
+/// Returns true when the artifact exists and was built from the same inputs.
+pub fn build_artifact_ready(
+    config: &Config,
+    platform: Platform,
+    arch: Arch,
+    library: &Library,
+    commits: &SourceCommits,
 ) -> Result<bool> {
-    Ok(expected_library_path(build_dir, platform, arch, library, lib_type)?.exists())
+    // the branch tip may have moved since the last build
+    if config.tracks_branch(library) {
+        return Ok(false);
+    }
+    for lib_type in config.platforms.get_lib_types_for_platform(&platform) {
+        let artifact =
+            expected_library_path(&config.paths.build_dir, platform, arch, library, *lib_type)?;
+        if !artifact.exists() {
+            return Ok(false);
+        }
+    }
+    let key = build_cache_key(config, platform, arch, library, commits)?;
+    Ok(
+        target_prefix(&config.paths.build_dir, platform, arch, library)?
+            .join(format!("{CACHE_KEY_PREFIX}{key}"))
+            .exists(),
+    )
+}
+
+/// Stores the cache key of a successful build next to its artifact.
+pub fn write_build_cache_key(
+    config: &Config,
+    platform: Platform,
+    arch: Arch,
+    library: &Library,
+    commits: &SourceCommits,
+) -> Result<()> {
+    let prefix = target_prefix(&config.paths.build_dir, platform, arch, library)?;
+    fs::create_dir_all(&prefix)?;
+    remove_build_cache_keys(&prefix)?;
+    let key = build_cache_key(config, platform, arch, library, commits)?;
+    fs::write(prefix.join(format!("{CACHE_KEY_PREFIX}{key}")), "")?;
+    Ok(())
+}
+
+/// Forget the cache keys of `library` for every configured target, so the
+/// next build rebuilds it even though its config inputs didn't change.
+pub fn invalidate_build_cache(config: &Config, library: &Library) -> Result<()> {
+    for platform in &config.general.platforms {
+        for arch in config.platforms.get_archs_for_platform(platform) {
+            let prefix = target_prefix(&config.paths.build_dir, *platform, *arch, library)?;
+            if prefix.exists() {
+                remove_build_cache_keys(&prefix)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn remove_build_cache_keys(prefix: &Path) -> Result<()> {
+    for entry in fs::read_dir(prefix)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(CACHE_KEY_PREFIX))
+        {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Hashes every input that affects the artifact of one target: its config,
+/// its checked out commit and, recursively, the keys of its dependencies.
+pub fn build_cache_key(
+    config: &Config,
+    platform: Platform,
+    arch: Arch,
+    library: &Library,
+    commits: &SourceCommits,
+) -> Result<String> {
+    let platform_settings = match platform {
+        Platform::Macos => serde_json::to_value(&config.platforms.macos)?,
+        Platform::Ios => serde_json::to_value(&config.platforms.ios)?,
+        Platform::IosSim => serde_json::to_value(&config.platforms.ios_sim)?,
+        Platform::MacCatalyst => serde_json::to_value(&config.platforms.maccatalyst)?,
+        Platform::Tvos => serde_json::to_value(&config.platforms.tvos)?,
+        Platform::TvosSim => serde_json::to_value(&config.platforms.tvos_sim)?,
+        Platform::Visionos => serde_json::to_value(&config.platforms.visionos)?,
+        Platform::VisionosSim => serde_json::to_value(&config.platforms.visionos_sim)?,
+        Platform::Android => serde_json::to_value(&config.platforms.android)?,
+        Platform::Harmony => serde_json::to_value(&config.platforms.harmony)?,
+    };
+    let inputs = serde_json::json!({
+        "library": library,
+        "platform": platform,
+        "arch": arch,
+        "version": config.get_library_version(library)?,
+        "cflags": &config.build.cflags,
+        "ldflags": &config.build.ldflags,
+        "configure_flags": &config.build.configure_flags,
+        "fast_math": config.build.fast_math,
+        "la_files": config.build.la_files,
+        "lto": config.build.lto,
+        "reproducible": config.build.reproducible,
+        "sanitizers": &config.build.sanitizers,
+        "library_options": config.libraries.get(library),
+        "platform_settings": platform_settings,
+        "commit": commits.get(library.repo_name()),
+        "dependencies": library
+            .deps()
+            .iter()
+            .map(|dep| build_cache_key(config, platform, arch, dep, commits))
+            .collect::<Result<Vec<_>>>()?,
+    });
+
+    let digest = Sha256::digest(inputs.to_string().as_bytes());
+    Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// "libopus for ios (arm64)", for progress output.
+fn target_label(library: &Library, platform: Platform, arch: Arch) -> String {
+    let arch = arch_dir_name(platform, arch).map_or_else(|_| arch.to_string(), str::to_string);
+    format!("{} for {platform} ({arch})", library.name_with_lib_prefix())
+}
+
+/// `arch`'s directory name in `platform`'s naming, e.g. `arm64-v8a` on Android.
+fn arch_dir_name(platform: Platform, arch: Arch) -> Result<&'static str> {
+    match platform {
+        Platform::Macos
+        | Platform::Ios
+        | Platform::IosSim
+        | Platform::MacCatalyst
+        | Platform::Tvos
+        | Platform::TvosSim
+        | Platform::Visionos
+        | Platform::VisionosSim => crate::platforms::darwin::build::arch_dir_name(arch),
+        Platform::Android => crate::platforms::android::build::arch_dir_name(arch),
+        Platform::Harmony => crate::platforms::harmony::build::arch_dir_name(arch),
+    }
 }
 
-fn expected_library_path(
+fn target_prefix(
     build_dir: &Path,
     platform: Platform,
     arch: Arch,
     library: &Library,
-    lib_type: LibType,
 ) -> Result<PathBuf> {
     let platform_dir = platform.to_string().to_lowercase();
-    let arch_dir = match platform {
-        Platform::Macos | Platform::Ios | Platform::IosSim => {
-            crate::platforms::darwin::build::arch_dir_name(arch)?
-        }
-        Platform::Android => crate::platforms::android::build::arch_dir_name(arch)?,
-        Platform::Harmony => crate::platforms::harmony::build::arch_dir_name(arch)?,
-    };
+    let arch_dir = arch_dir_name(platform, arch)?;
+    Ok(build_dir
+        .join(platform_dir)
+        .join(arch_dir)
+        .join(library.repo_name()))
+}
 
+pub fn expected_library_path(
+    build_dir: &Path,
+    platform: Platform,
+    arch: Arch,
+    library: &Library,
+    lib_type: LibType,
+) -> Result<PathBuf> {
     let ext = match platform {
-        Platform::Macos | Platform::Ios | Platform::IosSim => lib_type.darwin_ext(),
+        Platform::Macos
+        | Platform::Ios
+        | Platform::IosSim
+        | Platform::MacCatalyst
+        | Platform::Tvos
+        | Platform::TvosSim
+        | Platform::Visionos
+        | Platform::VisionosSim => lib_type.darwin_ext(),
         Platform::Android | Platform::Harmony => lib_type.linux_ext(),
     };
     let file_name = format!("{}.{}", library.name_with_lib_prefix(), ext);
 
-    Ok(build_dir
-        .join(platform_dir)
-        .join(arch_dir)
-        .join(library.repo_name())
+    Ok(target_prefix(build_dir, platform, arch, library)?
         .join("lib")
         .join(file_name))
 }
 
+/// The artifact of `library` for the target, packaged into `build/lib` on
+/// Android and HarmonyOS. `None` when the built library is missing, so there is
+/// nothing to report.
 fn package_artifact_if_needed(
-    build_dir: &Path,
+    config: &Config,
     platform: Platform,
     library: &Library,
     version: &str,
     arch: Arch,
     lib_type: LibType,
-) -> Result<()> {
+    strict: bool,
+) -> Result<Option<PathBuf>> {
+    let build_dir = &config.paths.build_dir;
     match platform {
-        Platform::Android => crate::platforms::android::build::move_android_package(
-            build_dir, library, version, arch, lib_type,
-        ),
+        Platform::Android => {
+            let Some(path) = crate::platforms::android::build::move_android_package(
+                build_dir, library, version, arch, lib_type, strict,
+            )?
+            else {
+                return Ok(None);
+            };
+            if let Some(dest_dir) = path.parent() {
+                crate::platforms::android::build::copy_sanitizer_runtimes(config, arch, dest_dir)?;
+                if lib_type == LibType::Shared {
+                    crate::platforms::android::build::copy_stl_runtime(config, arch, dest_dir)?;
+                }
+            }
+            if config.platforms.android.symbols && lib_type == LibType::Shared {
+                crate::platforms::android::build::copy_unstripped_symbols(build_dir, arch, &path)?;
+            }
+            Ok(Some(path))
+        }
         Platform::Harmony => crate::platforms::harmony::build::move_harmony_package(
-            build_dir, library, version, arch, lib_type,
+            build_dir, library, version, arch, lib_type, strict,
         ),
-        Platform::Macos | Platform::Ios | Platform::IosSim => Ok(()),
+        Platform::Macos
+        | Platform::Ios
+        | Platform::IosSim
+        | Platform::MacCatalyst
+        | Platform::Tvos
+        | Platform::TvosSim
+        | Platform::Visionos
+        | Platform::VisionosSim => {
+            expected_library_path(build_dir, platform, arch, library, lib_type).map(Some)
+        }
     }
 }
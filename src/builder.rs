@@ -1,12 +1,103 @@
-use crate::config::{Arch, Config, LibType, Library, Platform};
+use crate::config::{
+    Arch, BuildSystem, Config, LaFiles, LibType, Library, LibraryBuildOptions, Lto, Platform,
+};
 use crate::platforms::{android, darwin, harmony};
 use crate::repo::Repo;
-use crate::utils::CommandVerboseExt;
+use crate::utils::{self, CommandVerboseExt, ExecOptions};
 use anyhow::{Context, Result};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
+/// Compiler and linker flags for one target, shared by all build systems.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildFlags {
+    pub cflags: String,
+    pub cppflags: String,
+    pub ldflags: String,
+    pub pkg_config_path: String,
+    /// The library's `env` from the config, set on top of the toolchain's.
+    pub env: Vec<(String, String)>,
+}
+
+/// The flags `library` is built with for the target of `toolchain`: the
+/// toolchain's base flags (SDK/sysroot, deployment target), the library's
+/// own, fast-math, PIC, sanitizer/LTO flags and the dependencies' `-I`/`-L`
+/// paths, plus the library's extra environment. With `allow_missing_deps`,
+/// dependencies that aren't built yet get their would-be paths instead of
+/// failing. `build.reproducible`'s prefix maps are added later, as they depend
+/// on the checkout.
+pub fn assemble_flags(
+    config: &Config,
+    library: &Library,
+    toolchain: &AutotoolsToolchain,
+    lib_types: &[LibType],
+    allow_missing_deps: bool,
+) -> Result<BuildFlags> {
+    let mut cflags = toolchain.base_cflags.clone();
+    let mut ldflags = toolchain.base_ldflags.clone();
+    let mut cppflags = String::new();
+    let mut pkg_config_path = String::new();
+    append_library_build_options(config, library, &mut cflags, &mut ldflags);
+    let fast_math = config.build.fast_math_for(config.libraries.get(library));
+    set_fast_math(&mut cflags, fast_math);
+    set_position_independence(&mut cflags, &mut ldflags, lib_types);
+    if let Some(sanitize) = config.build.sanitize_flags() {
+        apply_sanitizers(&sanitize, config, lib_types, &mut cflags, &mut ldflags);
+    } else if let Some(flag) = config.build.lto.flag() {
+        cflags.push_str(&format!(" {flag}"));
+        ldflags.push_str(&format!(" {flag}"));
+    }
+    append_dependency_search_paths(
+        &config.paths.build_dir,
+        toolchain,
+        library,
+        &mut cppflags,
+        &mut ldflags,
+        &mut pkg_config_path,
+        allow_missing_deps,
+    )?;
+    let env = config
+        .libraries
+        .get(library)
+        .map(|opts| {
+            opts.env
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(BuildFlags {
+        cflags,
+        cppflags,
+        ldflags,
+        pkg_config_path,
+        env,
+    })
+}
+
+/// Compiler, SDK/sysroot and base flags for one platform/arch.
+pub async fn prepare_toolchain(
+    platform: Platform,
+    arch: Arch,
+    config: &Config,
+) -> Result<AutotoolsToolchain> {
+    match platform {
+        Platform::Android => android::build::prepare_toolchain(arch, config),
+        Platform::Harmony => harmony::build::prepare_toolchain(arch, config),
+        Platform::Macos
+        | Platform::Ios
+        | Platform::IosSim
+        | Platform::MacCatalyst
+        | Platform::Tvos
+        | Platform::TvosSim
+        | Platform::Visionos
+        | Platform::VisionosSim => darwin::build::prepare_toolchain(platform, arch, config).await,
+    }
+    .with_context(|| format!("prepare toolchain failed for {platform} ({arch})"))
+}
+
+#[derive(Clone)]
 pub struct AutotoolsToolchain {
     pub platform_dir: String,
     pub arch_dir: String,
@@ -18,13 +109,25 @@ pub struct AutotoolsToolchain {
     pub base_ldflags: String,
 }
 
+impl AutotoolsToolchain {
+    /// `extra_env` in a form `Command::envs` accepts.
+    fn env_pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.extra_env.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Configures, builds and installs one library for one platform/arch into
+/// `build/<platform>/<arch>/<repo>`. The repo must already be checked out, and
+/// the library's dependencies built for the same target.
 pub struct Builder<'a> {
     platform: Platform,
     arch: Arch,
     library: Library,
     repo: &'a Repo,
     config: &'a Config,
-    verbose: bool,
+    exec: ExecOptions,
+    run_tests: bool,
+    smoke_test: bool,
 }
 
 impl<'a> Builder<'a> {
@@ -34,19 +137,45 @@ impl<'a> Builder<'a> {
         library: Library,
         repo: &'a Repo,
         config: &'a Config,
-        verbose: bool,
+        exec: ExecOptions,
     ) -> Self {
+        let prefix_target = config
+            .general
+            .prefix_output
+            .then_some((library, platform, arch));
         Self {
             platform,
             arch,
             library,
             repo,
             config,
-            verbose,
+            exec: ExecOptions {
+                prefix_target,
+                timeout: config.build.command_timeout(),
+                failure_tail_lines: Some(config.build.failure_tail_lines),
+                ..exec
+            },
+            run_tests: false,
+            smoke_test: false,
         }
     }
 
-    pub async fn build(&self) -> Result<()> {
+    /// Link and run a tiny program against the installed library (see [`crate::smoke`]).
+    pub fn smoke_test(mut self, enabled: bool) -> Self {
+        self.smoke_test = enabled;
+        self
+    }
+
+    /// Run the library's `make check` after installing it; the target must be
+    /// runnable on this machine (see [`crate::build::runs_on_host`]).
+    pub fn run_tests(mut self, enabled: bool) -> Self {
+        self.run_tests = enabled;
+        self
+    }
+
+    /// Build and install the library for this target, returning the flags it
+    /// was compiled with.
+    pub async fn build(&self) -> Result<BuildFlags> {
         log::info!(
             "Building {} for {} ({}) from {}",
             self.library,
@@ -55,29 +184,104 @@ impl<'a> Builder<'a> {
             self.repo.local_path.display()
         );
 
-        let toolchain = match self.platform {
-            Platform::Android => android::build::prepare_toolchain(self.arch, self.config),
-            Platform::Harmony => harmony::build::prepare_toolchain(self.arch, self.config),
-            Platform::Macos | Platform::Ios | Platform::IosSim => {
-                darwin::build::prepare_toolchain(self.platform, self.arch, self.config).await
+        let toolchain = prepare_toolchain(self.platform, self.arch, self.config).await?;
+        let lib_types = self
+            .config
+            .platforms
+            .get_lib_types_for_platform(&self.platform);
+        let flags = self.compile(&toolchain, lib_types).await?;
+        if self.platform == Platform::Harmony && !self.exec.dry_run {
+            // packaging only warns about a missing library, so catch a broken
+            // install here where the failing target is known
+            for lib_type in lib_types {
+                let lib = crate::build::expected_library_path(
+                    &self.config.paths.build_dir,
+                    self.platform,
+                    self.arch,
+                    &self.library,
+                    *lib_type,
+                )?;
+                if !lib.exists() {
+                    anyhow::bail!(
+                        "{} was not installed by the {} build",
+                        lib.display(),
+                        self.library
+                    );
+                }
             }
         }
-        .with_context(|| {
-            format!(
-                "prepare toolchain failed for {} ({})",
-                self.platform, self.arch
+        if lib_types.contains(&LibType::Static) && !self.exec.dry_run {
+            self.normalize_static_archive(&toolchain).await?;
+        }
+
+        if self.platform.is_darwin()
+            && darwin::build::embeds_bitcode(&toolchain)
+            && !self.exec.dry_run
+        {
+            for lib_type in lib_types {
+                let lib = crate::build::expected_library_path(
+                    &self.config.paths.build_dir,
+                    self.platform,
+                    self.arch,
+                    &self.library,
+                    *lib_type,
+                )?;
+                darwin::build::verify_bitcode(&lib).await?;
+            }
+        }
+
+        if self.platform == Platform::Android
+            && self.config.platforms.android.page_size_16k
+            && lib_types.contains(&LibType::Shared)
+            && !self.exec.dry_run
+        {
+            let lib = crate::build::expected_library_path(
+                &self.config.paths.build_dir,
+                self.platform,
+                self.arch,
+                &self.library,
+                LibType::Shared,
+            )?;
+            android::build::verify_page_alignment(self.config, &lib).await?;
+        }
+
+        if self.config.build.lto != Lto::Off
+            && self.config.build.sanitizers.is_empty()
+            && lib_types.contains(&LibType::Static)
+            && !self.exec.dry_run
+        {
+            let lib = crate::build::expected_library_path(
+                &self.config.paths.build_dir,
+                self.platform,
+                self.arch,
+                &self.library,
+                LibType::Static,
+            )?;
+            check_lto_archive(&toolchain, &lib).await;
+        }
+
+        if self.smoke_test {
+            crate::smoke::run(
+                self.config,
+                self.platform,
+                self.arch,
+                &self.library,
+                &toolchain,
+                self.exec,
             )
-        })?;
-        let lib_type = self
-            .config
-            .platforms
-            .get_lib_type_for_platform(&self.platform);
-        self.run_autotools(&toolchain, lib_type).await?;
+            .await?;
+        }
 
-        Ok(())
+        Ok(flags)
     }
 
-    async fn run_autotools(&self, toolchain: &AutotoolsToolchain, lib_type: LibType) -> Result<()> {
+    /// Compute the flags for this target, then configure, build and install it
+    /// with the library's build system.
+    async fn compile(
+        &self,
+        toolchain: &AutotoolsToolchain,
+        lib_types: &[LibType],
+    ) -> Result<BuildFlags> {
         let prefix = self
             .config
             .paths
@@ -86,97 +290,638 @@ impl<'a> Builder<'a> {
             .join(&toolchain.arch_dir)
             .join(self.library.repo_name());
 
-        fs::create_dir_all(&prefix)?;
-        let prefix = fs::canonicalize(&prefix)?;
+        let prefix = if self.exec.dry_run {
+            std::path::absolute(&prefix)?
+        } else {
+            fs::create_dir_all(&prefix)?;
+            fs::canonicalize(&prefix)?
+        };
 
-        let mut cflags = toolchain.base_cflags.clone();
-        let mut ldflags = toolchain.base_ldflags.clone();
-        let mut pkg_config_path = String::new();
-        append_library_build_options(self.config, &self.library, &mut cflags, &mut ldflags);
-        append_dependency_search_paths(
-            &self.config.paths.build_dir,
-            &toolchain.platform_dir,
-            &toolchain.arch_dir,
+        let mut flags = assemble_flags(
+            self.config,
             &self.library,
-            &mut cflags,
-            &mut ldflags,
-            &mut pkg_config_path,
+            toolchain,
+            lib_types,
+            self.exec.dry_run,
         )?;
 
-        run_autogen(
-            &self.repo.local_path,
-            self.verbose,
-            toolchain,
-            &cflags,
-            &ldflags,
-        )
-        .await
-        .with_context(|| format!("autogen failed for {}", self.library))?;
+        let reproducible_toolchain;
+        let toolchain = if self.config.build.reproducible {
+            reproducible_toolchain = self
+                .make_reproducible(toolchain, &prefix, &mut flags.cflags)
+                .await?;
+            &reproducible_toolchain
+        } else {
+            toolchain
+        };
+        let library_env_toolchain;
+        let toolchain = if flags.env.is_empty() {
+            toolchain
+        } else {
+            let mut with_env = toolchain.clone();
+            with_env.extra_env.extend(flags.env.iter().cloned());
+            library_env_toolchain = with_env;
+            &library_env_toolchain
+        };
 
-        try_make_clean(&self.repo.local_path).await;
+        if self.run_tests && self.build_system() != BuildSystem::Autotools {
+            log::warn!(
+                "Not running the {} tests: only autotools builds support `make check`",
+                self.library
+            );
+        }
+        match self.build_system() {
+            BuildSystem::Autotools => {
+                self.run_autotools(toolchain, lib_types, &prefix, &flags)
+                    .await?
+            }
+            BuildSystem::Cmake => {
+                self.run_cmake(toolchain, lib_types, &prefix, &flags)
+                    .await?
+            }
+            BuildSystem::Meson => {
+                self.run_meson(toolchain, lib_types, &prefix, &flags)
+                    .await?
+            }
+        }
+
+        if !self.exec.dry_run {
+            process_la_files(&prefix, self.config.build.la_files)?;
+        }
+        Ok(flags)
+    }
+
+    fn build_system(&self) -> BuildSystem {
+        self.config
+            .libraries
+            .get(&self.library)
+            .map(|opts| opts.build_system)
+            .unwrap_or_default()
+    }
 
-        let mut configure_cmd = Command::new("./configure");
+    async fn run_autotools(
+        &self,
+        toolchain: &AutotoolsToolchain,
+        lib_types: &[LibType],
+        prefix: &Path,
+        flags: &BuildFlags,
+    ) -> Result<()> {
+        let BuildFlags {
+            cflags,
+            cppflags,
+            ldflags,
+            pkg_config_path,
+            ..
+        } = flags;
+        // release tarballs ship a generated `configure` instead of `autogen.sh`
+        let has_autogen = self.repo.local_path.join("autogen.sh").exists()
+            || !self.repo.local_path.join("configure").exists();
+        if has_autogen {
+            run_autogen(
+                &self.repo.local_path,
+                self.exec,
+                toolchain,
+                cflags,
+                cppflags,
+                ldflags,
+            )
+            .await
+            .with_context(|| format!("autogen failed for {}", self.library))?;
+        } else {
+            log::info!("{} has no autogen.sh, using its configure", self.library);
+        }
+
+        let in_tree = self
+            .config
+            .libraries
+            .get(&self.library)
+            .is_some_and(|opts| opts.in_tree);
+        let (configure, obj_dir) = if in_tree {
+            try_make_clean(&self.repo.local_path, self.exec).await;
+            (PathBuf::from("./configure"), self.repo.local_path.clone())
+        } else {
+            // VPATH build: the source tree stays pristine, so no `make clean` is needed
+            let source_dir = dependency_dir(&self.repo.local_path, self.exec.dry_run)?;
+            let obj_dir = self.object_dir(toolchain);
+            if !self.exec.dry_run {
+                if obj_dir.exists() {
+                    fs::remove_dir_all(&obj_dir)?;
+                }
+                fs::create_dir_all(&obj_dir)?;
+            }
+            (source_dir.join("configure"), obj_dir)
+        };
+
+        let mut configure_cmd = Command::new(&configure);
         configure_cmd
-            .current_dir(&self.repo.local_path)
+            .current_dir(&obj_dir)
             .arg(format!("--host={}", toolchain.host))
             .arg(format!("--prefix={}", prefix.display()))
-            .env("PKG_CONFIG_PATH", &pkg_config_path);
+            .env("PKG_CONFIG_PATH", pkg_config_path);
 
-        match lib_type {
-            LibType::Static => {
-                configure_cmd.arg("--enable-static").arg("--disable-shared");
-            }
-            LibType::Shared => {
-                configure_cmd.arg("--enable-shared").arg("--disable-static");
+        // a single configure run produces every requested type when both are enabled
+        for (lib_type, name) in [(LibType::Static, "static"), (LibType::Shared, "shared")] {
+            if lib_types.contains(&lib_type) {
+                configure_cmd.arg(format!("--enable-{name}"));
+            } else {
+                configure_cmd.arg(format!("--disable-{name}"));
             }
         }
 
+        if http_enabled(self.config, &self.library) {
+            ensure_openssl_available(pkg_config_path, self.exec)
+                .await
+                .with_context(|| format!("enable_http is set for {}", self.library))?;
+        }
+
         append_configure_flags(self.config, &self.library, &mut configure_cmd);
-        apply_common_env(&mut configure_cmd, toolchain, &cflags, &ldflags);
+        apply_common_env(&mut configure_cmd, toolchain, cflags, cppflags, ldflags);
 
-        configure_cmd
-            .run_with_verbose(self.verbose)
-            .await
-            .with_context(|| {
+        configure_cmd.run_with(self.exec).await.with_context(|| {
+            format!(
+                "configure failed for {} on {}/{}",
+                self.library, toolchain.platform_dir, toolchain.arch_dir
+            )
+        })?;
+
+        // `make` and `make install` get exactly the env `configure` saw, so a
+        // relink during install can't fall back to the host compiler
+        let make_jobs = format!("-j{}", self.config.build.resolved_make_jobs());
+        let lib_options = self.config.libraries.get(&self.library);
+        let make_args = lib_options.map_or(&[][..], |opts| &opts.make_args);
+        let make = |target: Option<&str>| {
+            let mut cmd = Command::new("make");
+            cmd.current_dir(&obj_dir)
+                .arg(&make_jobs)
+                .args(make_args)
+                .args(target)
+                .env("PKG_CONFIG_PATH", pkg_config_path);
+            apply_common_env(&mut cmd, toolchain, cflags, cppflags, ldflags);
+            cmd
+        };
+        make(None).run_with(self.exec).await.with_context(|| {
+            format!(
+                "make failed for {} on {}/{}",
+                self.library, toolchain.platform_dir, toolchain.arch_dir
+            )
+        })?;
+
+        let make_targets = lib_options
+            .map(LibraryBuildOptions::make_targets)
+            .unwrap_or_else(|| LibraryBuildOptions::default().make_targets());
+        for target in &make_targets {
+            make(Some(target))
+                .run_with(self.exec)
+                .await
+                .with_context(|| {
+                    format!(
+                        "make {target} failed for {} on {}/{}",
+                        self.library, toolchain.platform_dir, toolchain.arch_dir
+                    )
+                })?;
+        }
+
+        if self.run_tests {
+            self.run_make_check(make(Some("check"))).await?;
+        }
+
+        if in_tree {
+            try_make_clean(&self.repo.local_path, self.exec).await;
+        }
+        Ok(())
+    }
+
+    /// Configure, build and install with CMake, once per lib type since CMake
+    /// builds either static or shared libraries (`BUILD_SHARED_LIBS`).
+    async fn run_cmake(
+        &self,
+        toolchain: &AutotoolsToolchain,
+        lib_types: &[LibType],
+        prefix: &Path,
+        flags: &BuildFlags,
+    ) -> Result<()> {
+        let source_dir = dependency_dir(&self.repo.local_path, self.exec.dry_run)?;
+        let dep_prefixes = dependency_prefixes(
+            &self.config.paths.build_dir,
+            toolchain,
+            &self.library,
+            self.exec.dry_run,
+        )?
+        .join(";");
+
+        for lib_type in lib_types {
+            let obj_dir = self.object_dir(toolchain).join(lib_type.as_str());
+            if !self.exec.dry_run {
+                if obj_dir.exists() {
+                    fs::remove_dir_all(&obj_dir)?;
+                }
+                fs::create_dir_all(&obj_dir)?;
+            }
+            let toolchain_args = match self.platform {
+                Platform::Android => android::build::cmake_args(self.arch, self.config)?,
+                Platform::Harmony => harmony::build::cmake_args(self.arch, self.config)?,
+                Platform::Macos
+                | Platform::Ios
+                | Platform::IosSim
+                | Platform::MacCatalyst
+                | Platform::Tvos
+                | Platform::TvosSim
+                | Platform::Visionos
+                | Platform::VisionosSim => darwin::build::cmake_args(
+                    self.platform,
+                    self.arch,
+                    self.config,
+                    toolchain,
+                    &obj_dir,
+                    self.exec.dry_run,
+                )?,
+            };
+
+            let c_flags = format!("{} {}", flags.cflags, flags.cppflags.trim_start());
+            let shared = if *lib_type == LibType::Shared {
+                "ON"
+            } else {
+                "OFF"
+            };
+            let mut configure_cmd = Command::new("cmake");
+            configure_cmd
+                .arg("-S")
+                .arg(&source_dir)
+                .arg("-B")
+                .arg(&obj_dir)
+                .args(toolchain_args)
+                .arg(format!("-DCMAKE_INSTALL_PREFIX={}", prefix.display()))
+                .arg("-DCMAKE_INSTALL_LIBDIR=lib")
+                .arg("-DCMAKE_BUILD_TYPE=Release")
+                .arg("-DCMAKE_POSITION_INDEPENDENT_CODE=ON")
+                .arg(format!("-DBUILD_SHARED_LIBS={shared}"))
+                .arg(format!("-DCMAKE_C_FLAGS={c_flags}"))
+                .arg(format!("-DCMAKE_SHARED_LINKER_FLAGS={}", flags.ldflags))
+                .arg(format!("-DCMAKE_PREFIX_PATH={dep_prefixes}"))
+                .arg(format!("-DCMAKE_FIND_ROOT_PATH={dep_prefixes}"))
+                .env("PKG_CONFIG_PATH", &flags.pkg_config_path);
+            // global `configure_flags` are autotools options; only per-library ones apply
+            if let Some(opts) = self.config.libraries.get(&self.library)
+                && let Some(extra) = &opts.configure_flags
+            {
+                configure_cmd.args(extra);
+            }
+            for (k, v) in &toolchain.extra_env {
+                configure_cmd.env(k, v);
+            }
+            configure_cmd.run_with(self.exec).await.with_context(|| {
                 format!(
-                    "configure failed for {} on {}/{}",
+                    "cmake configure failed for {} on {}/{}",
                     self.library, toolchain.platform_dir, toolchain.arch_dir
                 )
             })?;
 
-        let mut make_cmd = Command::new("make");
-        make_cmd
-            .current_dir(&self.repo.local_path)
-            .arg(format!("-j{}", self.config.build.make_concurrent_jobs));
-        apply_common_env(&mut make_cmd, toolchain, &cflags, &ldflags);
-        make_cmd
-            .run_with_verbose(self.verbose)
+            Command::new("cmake")
+                .envs(toolchain.env_pairs())
+                .arg("--build")
+                .arg(&obj_dir)
+                .arg("--parallel")
+                .arg(self.config.build.resolved_make_jobs().to_string())
+                .run_with(self.exec)
+                .await
+                .with_context(|| {
+                    format!(
+                        "cmake build failed for {} on {}/{}",
+                        self.library, toolchain.platform_dir, toolchain.arch_dir
+                    )
+                })?;
+
+            Command::new("cmake")
+                .envs(toolchain.env_pairs())
+                .arg("--install")
+                .arg(&obj_dir)
+                .run_with(self.exec)
+                .await
+                .with_context(|| {
+                    format!(
+                        "cmake install failed for {} on {}/{}",
+                        self.library, toolchain.platform_dir, toolchain.arch_dir
+                    )
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Configure with `meson setup` and a cross file generated from `toolchain`,
+    /// then build and install with `ninja`. One setup covers both lib types.
+    async fn run_meson(
+        &self,
+        toolchain: &AutotoolsToolchain,
+        lib_types: &[LibType],
+        prefix: &Path,
+        flags: &BuildFlags,
+    ) -> Result<()> {
+        let source_dir = dependency_dir(&self.repo.local_path, self.exec.dry_run)?;
+        let obj_dir = self.object_dir(toolchain);
+        let cross_file = obj_dir.with_extension("cross.ini");
+        if !self.exec.dry_run {
+            if obj_dir.exists() {
+                fs::remove_dir_all(&obj_dir)?;
+            }
+            fs::create_dir_all(&obj_dir)?;
+            fs::write(
+                &cross_file,
+                meson_cross_file(self.platform, self.arch, toolchain, flags),
+            )?;
+        }
+
+        let default_library = match lib_types {
+            [LibType::Static] => "static",
+            [LibType::Shared] => "shared",
+            _ => "both",
+        };
+        let mut setup_cmd = Command::new("meson");
+        setup_cmd
+            .envs(toolchain.env_pairs())
+            .arg("setup")
+            .arg(&obj_dir)
+            .arg(&source_dir)
+            .arg("--cross-file")
+            .arg(&cross_file)
+            .arg(format!("--prefix={}", prefix.display()))
+            .arg("--libdir=lib")
+            .arg("--buildtype=release")
+            .arg(format!("--default-library={default_library}"))
+            .arg(format!("--pkg-config-path={}", flags.pkg_config_path));
+        if let Some(opts) = self.config.libraries.get(&self.library)
+            && let Some(extra) = &opts.configure_flags
+        {
+            setup_cmd.args(extra);
+        }
+        setup_cmd.run_with(self.exec).await.with_context(|| {
+            format!(
+                "meson setup failed for {} on {}/{}",
+                self.library, toolchain.platform_dir, toolchain.arch_dir
+            )
+        })?;
+
+        Command::new("ninja")
+            .envs(toolchain.env_pairs())
+            .arg("-C")
+            .arg(&obj_dir)
+            .arg(format!("-j{}", self.config.build.resolved_make_jobs()))
+            .run_with(self.exec)
             .await
             .with_context(|| {
                 format!(
-                    "make failed for {} on {}/{}",
+                    "ninja failed for {} on {}/{}",
                     self.library, toolchain.platform_dir, toolchain.arch_dir
                 )
             })?;
 
-        let mut install_cmd = Command::new("make");
-        install_cmd
-            .current_dir(&self.repo.local_path)
-            .arg("install");
-        apply_common_env(&mut install_cmd, toolchain, &cflags, &ldflags);
-        install_cmd
-            .run_with_verbose(self.verbose)
+        Command::new("ninja")
+            .envs(toolchain.env_pairs())
+            .arg("-C")
+            .arg(&obj_dir)
+            .arg("install")
+            .run_with(self.exec)
             .await
             .with_context(|| {
                 format!(
-                    "make install failed for {} on {}/{}",
+                    "ninja install failed for {} on {}/{}",
                     self.library, toolchain.platform_dir, toolchain.arch_dir
                 )
+            })
+    }
+
+    /// Run `check_cmd`, keeping its whole output in [`crate::build::test_log_path`]
+    /// whether it passes or not.
+    async fn run_make_check(&self, mut check_cmd: Command) -> Result<()> {
+        if self.exec.dry_run {
+            return check_cmd.run_with(self.exec).await;
+        }
+        log::info!(
+            "Running the {} test suite for {} ({})",
+            self.library,
+            self.platform,
+            self.arch
+        );
+        let log_path = crate::build::test_log_path(
+            &self.config.paths.build_dir,
+            self.platform,
+            self.arch,
+            &self.library,
+        )?;
+        check_cmd
+            .run_with_log(self.exec, &log_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "make check failed for {} on {} ({}), see {}",
+                    self.library,
+                    self.platform,
+                    self.arch,
+                    log_path.display()
+                )
             })?;
+        log::info!(
+            "{} tests passed on {} ({}), log: {}",
+            self.library,
+            self.platform,
+            self.arch,
+            log_path.display()
+        );
+        Ok(())
+    }
+
+    /// Per-target object directory for VPATH builds, next to the install prefix.
+    fn object_dir(&self, toolchain: &AutotoolsToolchain) -> PathBuf {
+        self.config
+            .paths
+            .build_dir
+            .join(&toolchain.platform_dir)
+            .join(&toolchain.arch_dir)
+            .join("obj")
+            .join(self.library.repo_name())
+    }
+
+    /// Re-create the installed `.a` deterministically (zeroed timestamps/uids,
+    /// stable member order) so identical sources give identical archives.
+    async fn normalize_static_archive(&self, toolchain: &AutotoolsToolchain) -> Result<()> {
+        let lib = crate::build::expected_library_path(
+            &self.config.paths.build_dir,
+            self.platform,
+            self.arch,
+            &self.library,
+            LibType::Static,
+        )?;
+        if !lib.exists() {
+            return Ok(());
+        }
+        let normalized = lib.with_extension("normalized.a");
+        log::info!("Normalizing {}", lib.display());
+
+        if self.platform.is_darwin() {
+            Command::new("libtool")
+                .args(["-static", "-D", "-o"])
+                .arg(&normalized)
+                .arg(&lib)
+                .env("ZERO_AR_DATE", "1")
+                .run_with(self.exec)
+                .await
+                .with_context(|| format!("libtool failed to normalize {}", lib.display()))?;
+        } else {
+            // llvm-ar writes deterministic archives by default
+            let ar = toolchain
+                .extra_env
+                .iter()
+                .find(|(k, _)| k == "AR")
+                .map_or("llvm-ar", |(_, v)| v.as_str());
+            crate::post_build::merge_with_ar_script(
+                Path::new(ar),
+                std::slice::from_ref(&lib),
+                &normalized,
+                self.exec,
+            )
+            .await?;
+        }
 
-        try_make_clean(&self.repo.local_path).await;
+        fs::rename(&normalized, &lib)?;
         Ok(())
     }
+
+    /// Map the checkout and build paths out of debug info and `__FILE__`, and make
+    /// timestamps and `ar` output deterministic.
+    async fn make_reproducible(
+        &self,
+        toolchain: &AutotoolsToolchain,
+        prefix: &Path,
+        cflags: &mut String,
+    ) -> Result<AutotoolsToolchain> {
+        let repo_name = self.library.repo_name();
+        let build_dir = fs::canonicalize(&self.config.paths.build_dir)?;
+        let mut path_maps = vec![
+            (prefix.to_path_buf(), format!("/prefix/{repo_name}")),
+            (build_dir, "/build".to_string()),
+        ];
+        if let Ok(repo_path) = fs::canonicalize(&self.repo.local_path) {
+            path_maps.push((repo_path, format!("/src/{repo_name}")));
+        }
+        for (from, to) in path_maps {
+            let from = from.display();
+            cflags.push_str(&format!(
+                " -ffile-prefix-map={from}={to} -fdebug-prefix-map={from}={to}"
+            ));
+        }
+
+        let source_date_epoch = match self.repo.head_commit_time().await {
+            Ok(time) => time,
+            Err(_) if self.exec.dry_run => 0,
+            Err(e) => return Err(e),
+        };
+        let mut toolchain = toolchain.clone();
+        toolchain.extra_env.extend([
+            (
+                "SOURCE_DATE_EPOCH".to_string(),
+                source_date_epoch.to_string(),
+            ),
+            // `D`: zero timestamps/uids in archives (libtool reads AR_FLAGS, make ARFLAGS)
+            ("AR_FLAGS".to_string(), "crD".to_string()),
+            ("ARFLAGS".to_string(), "crD".to_string()),
+            // Apple's ar/libtool/ld equivalent of `D`
+            ("ZERO_AR_DATE".to_string(), "1".to_string()),
+        ]);
+        if !toolchain.extra_env.iter().any(|(k, _)| k == "AR") && !self.platform.is_darwin() {
+            toolchain
+                .extra_env
+                .push(("AR".to_string(), "llvm-ar".to_string()));
+        }
+        Ok(toolchain)
+    }
+}
+
+/// Delete or relocate the libtool archives installed under `prefix`; their
+/// absolute paths break linking once the artifacts are moved. Relocating also
+/// points the pkg-config files' `prefix` at their own location.
+pub fn process_la_files(prefix: &Path, mode: LaFiles) -> Result<()> {
+    let lib_dir = prefix.join("lib");
+    if mode == LaFiles::Keep || !lib_dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(&lib_dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "la") {
+            continue;
+        }
+        if mode == LaFiles::Delete {
+            log::debug!("Removing {}", path.display());
+            fs::remove_file(&path)?;
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        let relocated: Vec<String> = content
+            .lines()
+            .map(|line| {
+                if let Some(libs) = line.strip_prefix("dependency_libs='") {
+                    format!(
+                        "dependency_libs='{}'",
+                        relocatable_dependency_libs(libs.trim_end_matches('\''))
+                    )
+                } else if let Some(libdir) = line.strip_prefix("libdir='") {
+                    // relative to the install prefix; libtool falls back to the
+                    // `.la`'s own directory when `libdir` doesn't exist
+                    let libdir = Path::new(libdir.trim_end_matches('\''));
+                    let relative = libdir.strip_prefix(prefix).unwrap_or(libdir);
+                    format!("libdir='{}'", relative.display())
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        fs::write(&path, relocated.join("\n") + "\n")?;
+    }
+
+    let pkgconfig_dir = lib_dir.join("pkgconfig");
+    if mode == LaFiles::Relocate && pkgconfig_dir.exists() {
+        for entry in fs::read_dir(&pkgconfig_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "pc") {
+                let content = fs::read_to_string(&path)?;
+                fs::write(&path, relocatable_pc_file(&content, prefix))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sets `prefix` to `${pcfiledir}/../..` (`<prefix>/lib/pkgconfig`) and makes
+/// `libdir`, `includedir` and the other variables refer to it.
+fn relocatable_pc_file(content: &str, prefix: &Path) -> String {
+    let prefix = prefix.to_string_lossy();
+    content
+        .lines()
+        .map(|line| {
+            if line.starts_with("prefix=") {
+                "prefix=${pcfiledir}/../..".to_string()
+            } else {
+                line.replace(prefix.as_ref(), "${prefix}")
+            }
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// `/abs/path/libogg.la` becomes `-logg`; absolute `-L` dirs are dropped.
+fn relocatable_dependency_libs(libs: &str) -> String {
+    libs.split_whitespace()
+        .filter(|lib| !lib.starts_with("-L/"))
+        .map(|lib| {
+            let name = Path::new(lib)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.strip_prefix("lib"));
+            match name {
+                Some(name) if lib.starts_with('/') && lib.ends_with(".la") => format!("-l{name}"),
+                _ => lib.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 fn append_library_build_options(
@@ -201,20 +946,119 @@ fn append_library_build_options(
     }
 }
 
+/// Add or remove `-ffast-math`, also when it is still spelled out in older `cflags`.
+fn set_fast_math(cflags: &mut String, enabled: bool) {
+    let mut flags: Vec<&str> = cflags
+        .split_whitespace()
+        .filter(|flag| *flag != "-ffast-math")
+        .collect();
+    if enabled {
+        flags.push("-ffast-math");
+    }
+    *cflags = flags.join(" ");
+}
+
+/// Compile position independent code whatever the configured flags say:
+/// shared libraries can't link `-fPIE` objects, so `-fPIE`/`-pie` (the old
+/// default `ldflags`) are replaced with `-fPIC` when building them. Static-only
+/// builds get `-fPIC` too, unless the flags explicitly ask for `-fPIE`.
+pub fn set_position_independence(cflags: &mut String, ldflags: &mut String, lib_types: &[LibType]) {
+    const PIE_FLAGS: [&str; 3] = ["-fPIE", "-fpie", "-pie"];
+    let is_pie = |flag: &&str| PIE_FLAGS.contains(flag);
+    let shared = lib_types.contains(&LibType::Shared);
+    if !shared
+        && cflags
+            .split_whitespace()
+            .chain(ldflags.split_whitespace())
+            .any(|f| is_pie(&f))
+    {
+        return;
+    }
+    if shared
+        && ldflags
+            .split_whitespace()
+            .chain(cflags.split_whitespace())
+            .any(|f| is_pie(&f))
+    {
+        log::debug!("Building shared libraries, replacing -fPIE/-pie with -fPIC");
+    }
+    for flags in [&mut *cflags, &mut *ldflags] {
+        *flags = flags
+            .split_whitespace()
+            .filter(|flag| !is_pie(flag))
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+    if !cflags.split_whitespace().any(|flag| flag == "-fPIC") {
+        cflags.push_str(" -fPIC");
+        *cflags = cflags.trim_start().to_string();
+    }
+}
+
+/// Instrument the build with `sanitize` flags. `-ffast-math` and LTO are dropped
+/// since they hide or break sanitizer reports; only shared libraries link the
+/// sanitizer runtime, static ones leave that to the final app.
+fn apply_sanitizers(
+    sanitize: &str,
+    config: &Config,
+    lib_types: &[LibType],
+    cflags: &mut String,
+    ldflags: &mut String,
+) {
+    if config.build.lto != Lto::Off {
+        log::warn!("build.sanitizers is set, ignoring build.lto");
+    }
+    set_fast_math(cflags, false);
+    cflags.push_str(&format!(" {sanitize}"));
+    if lib_types.contains(&LibType::Shared) {
+        ldflags.push_str(&format!(" {sanitize}"));
+    }
+    if lib_types.contains(&LibType::Static) {
+        log::info!("Static libraries are sanitized; link the app with `{sanitize}` as well");
+    }
+}
+
+/// Warn if an LTO-built static archive is unreadable by `nm` or holds bitcode
+/// objects, which break consumers not linking with a compatible clang.
+async fn check_lto_archive(toolchain: &AutotoolsToolchain, lib: &Path) {
+    let nm = toolchain
+        .extra_env
+        .iter()
+        .find(|(k, _)| k == "NM")
+        .map_or("nm", |(_, v)| v.as_str());
+    let output = Command::new(nm).arg(lib).output().await;
+    match output {
+        Ok(output)
+            if output.status.success()
+                && String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .any(|line| line.contains(" T ")) => {}
+        _ => log::warn!(
+            "`{nm}` lists no defined symbols in {}; the LTO archive may be unusable (try build.lto = \"off\")",
+            lib.display()
+        ),
+    }
+
+    match utils::archive_bitcode_members(lib) {
+        Ok((0, _)) => {}
+        Ok((bitcode, total)) => log::warn!(
+            "{} contains {bitcode}/{total} LLVM bitcode objects due to build.lto; consumers must link it with an LTO-capable clang of the same or newer version",
+            lib.display()
+        ),
+        Err(e) => log::warn!("Could not inspect {}: {e:#}", lib.display()),
+    }
+}
+
 fn append_dependency_search_paths(
     build_dir: &Path,
-    platform_dir: &str,
-    arch_dir: &str,
+    toolchain: &AutotoolsToolchain,
     library: &Library,
-    cflags: &mut String,
+    cppflags: &mut String,
     ldflags: &mut String,
     pkg_config_path: &mut String,
+    dry_run: bool,
 ) -> Result<()> {
-    let deps: &[Library] = match library {
-        Library::Libopusenc => &[Library::Libopus],
-        Library::Libopusfile => &[Library::Libopus, Library::Libogg],
-        _ => &[],
-    };
+    let deps = library.deps();
     if deps.is_empty() {
         return Ok(());
     }
@@ -222,19 +1066,15 @@ fn append_dependency_search_paths(
     let mut pkg_config_paths = Vec::new();
     for dep in deps {
         let dep_prefix = build_dir
-            .join(platform_dir)
-            .join(arch_dir)
+            .join(&toolchain.platform_dir)
+            .join(&toolchain.arch_dir)
             .join(dep.repo_name());
 
-        let include_dir = dep_prefix.join("include");
-        cflags.push_str(&format!(" -I{}", include_dir.display()));
+        // configure and libtool need absolute paths
+        let include_dir = dependency_dir(&dep_prefix.join("include"), dry_run)?;
+        cppflags.push_str(&format!(" -I{}", include_dir.display()));
 
-        let lib_dir = fs::canonicalize(dep_prefix.join("lib")).with_context(|| {
-            format!(
-                "Dependency lib dir not found: {}",
-                dep_prefix.join("lib").display()
-            )
-        })?;
+        let lib_dir = dependency_dir(&dep_prefix.join("lib"), dry_run)?;
         ldflags.push_str(&format!(" -L{}", lib_dir.display()));
         pkg_config_paths.push(lib_dir.join("pkgconfig"));
     }
@@ -247,27 +1087,149 @@ fn append_dependency_search_paths(
     Ok(())
 }
 
-fn append_configure_flags(config: &Config, library: &Library, cmd: &mut Command) {
-    for flag in &config.build.configure_flags {
-        cmd.arg(flag);
+/// Meson cross file describing the target of `toolchain`: compiler and tools,
+/// flags (SDK/sysroot, deployment target, ...) and the host machine.
+fn meson_cross_file(
+    platform: Platform,
+    arch: Arch,
+    toolchain: &AutotoolsToolchain,
+    flags: &BuildFlags,
+) -> String {
+    fn array<'s>(items: impl IntoIterator<Item = &'s str>) -> String {
+        let quoted: Vec<_> = items
+            .into_iter()
+            .map(|item| format!("'{}'", item.replace('\\', "\\\\").replace('\'', "\\'")))
+            .collect();
+        format!("[{}]", quoted.join(", "))
     }
+
+    let mut binaries = format!("c = {}\n", array(toolchain.cc.split_whitespace()));
+    for (key, env) in [("ar", "AR"), ("strip", "STRIP"), ("nm", "NM")] {
+        if let Some((_, tool)) = toolchain.extra_env.iter().find(|(k, _)| k == env) {
+            binaries.push_str(&format!("{key} = {}\n", array([tool.as_str()])));
+        }
+    }
+    binaries.push_str("pkg-config = 'pkg-config'\n");
+
+    let system = match platform {
+        Platform::Android => "android",
+        Platform::Harmony => "linux",
+        _ => "darwin",
+    };
+    let cpu_family = match arch {
+        Arch::Arm64 | Arch::Arm64e | Arch::Arm64V8a => "aarch64",
+        Arch::ArmeabiV7a => "arm",
+        Arch::X86 => "x86",
+        Arch::X86_64 => "x86_64",
+    };
+    let c_args = array(
+        flags
+            .cflags
+            .split_whitespace()
+            .chain(flags.cppflags.split_whitespace()),
+    );
+    let c_link_args = array(flags.ldflags.split_whitespace());
+
+    format!(
+        "[binaries]\n{binaries}\n\
+         [built-in options]\nc_args = {c_args}\nc_link_args = {c_link_args}\n\n\
+         [host_machine]\nsystem = '{system}'\ncpu_family = '{cpu_family}'\ncpu = '{}'\nendian = 'little'\n",
+        toolchain.arch_dir
+    )
+}
+
+/// Absolute install prefixes of `library`'s dependencies for the toolchain's target.
+fn dependency_prefixes(
+    build_dir: &Path,
+    toolchain: &AutotoolsToolchain,
+    library: &Library,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    library
+        .deps()
+        .iter()
+        .map(|dep| {
+            let dep_prefix = build_dir
+                .join(&toolchain.platform_dir)
+                .join(&toolchain.arch_dir)
+                .join(dep.repo_name());
+            Ok(dependency_dir(&dep_prefix, dry_run)?.display().to_string())
+        })
+        .collect()
+}
+
+fn dependency_dir(dir: &Path, dry_run: bool) -> Result<PathBuf> {
+    match fs::canonicalize(dir) {
+        Ok(dir) => Ok(dir),
+        // dependencies are never built in dry-run mode
+        Err(_) if dry_run => Ok(dir.to_path_buf()),
+        Err(e) => Err(e).with_context(|| format!("Dependency dir not found: {}", dir.display())),
+    }
+}
+
+/// The flags passed to `configure` for `library`: the global
+/// `build.configure_flags` first, then the library's own, with
+/// `--disable-http` swapped for `--enable-http` when `enable_http` is set.
+pub fn configure_flags(config: &Config, library: &Library) -> Vec<String> {
+    let enable_http = http_enabled(config, library);
+    let mut flags = config.build.configure_flags.clone();
     if let Some(lib_opts) = config.libraries.get(library)
-        && let Some(flags) = &lib_opts.configure_flags
+        && let Some(lib_flags) = &lib_opts.configure_flags
     {
-        for flag in flags {
-            cmd.arg(flag);
-        }
+        flags.extend(
+            lib_flags
+                .iter()
+                .filter(|flag| !(enable_http && *flag == "--disable-http"))
+                .cloned(),
+        );
     }
+    if enable_http {
+        flags.push("--enable-http".to_string());
+    }
+    flags
+}
+
+fn append_configure_flags(config: &Config, library: &Library, cmd: &mut Command) {
+    cmd.args(configure_flags(config, library));
+}
+
+fn http_enabled(config: &Config, library: &Library) -> bool {
+    *library == Library::Libopusfile
+        && config
+            .libraries
+            .get(library)
+            .is_some_and(|opts| opts.enable_http)
+}
+
+/// opusfile's `--enable-http` needs OpenSSL visible to pkg-config; fail before configure does.
+async fn ensure_openssl_available(pkg_config_path: &str, exec: ExecOptions) -> Result<()> {
+    if exec.dry_run {
+        return Ok(());
+    }
+    let status = Command::new("pkg-config")
+        .args(["--exists", "openssl"])
+        .env("PKG_CONFIG_PATH", pkg_config_path)
+        .status()
+        .await
+        .context("Failed to run pkg-config")?;
+    if !status.success() {
+        anyhow::bail!(
+            "OpenSSL not found by pkg-config; install it for the target and make openssl.pc visible via PKG_CONFIG_PATH"
+        );
+    }
+    Ok(())
 }
 
 fn apply_common_env(
     cmd: &mut Command,
     toolchain: &AutotoolsToolchain,
     cflags: &str,
+    cppflags: &str,
     ldflags: &str,
 ) {
     cmd.env("CC", &toolchain.cc)
         .env("CFLAGS", cflags)
+        .env("CPPFLAGS", cppflags.trim_start())
         .env("LDFLAGS", ldflags);
 
     if let Some(cxx) = &toolchain.cxx {
@@ -279,20 +1241,57 @@ fn apply_common_env(
     }
 }
 
+/// Tools `autogen.sh` needs; alternatives are tried in order (Homebrew installs
+/// GNU libtoolize as `glibtoolize`).
+const AUTOGEN_TOOLS: &[&[&str]] = &[
+    &["autoconf"],
+    &["automake"],
+    &["libtoolize", "glibtoolize"],
+    &["pkg-config"],
+];
+
+/// Fail with an install hint instead of `autogen.sh`'s opaque errors.
+fn ensure_autotools_available() -> Result<()> {
+    let missing: Vec<_> = AUTOGEN_TOOLS
+        .iter()
+        .filter(|names| !names.iter().any(|name| utils::find_in_path(name).is_some()))
+        .map(|names| names[0])
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let hint = if cfg!(target_os = "macos") {
+        "brew install autoconf automake libtool pkg-config"
+    } else {
+        "sudo apt-get install autoconf automake libtool pkg-config (or your distro's equivalent)"
+    };
+    anyhow::bail!(
+        "Missing build tools needed by autogen.sh: {}. Install them with: {hint}",
+        missing.join(", ")
+    )
+}
+
 async fn run_autogen(
     repo_path: &Path,
-    verbose: bool,
+    exec: ExecOptions,
     toolchain: &AutotoolsToolchain,
     cflags: &str,
+    cppflags: &str,
     ldflags: &str,
 ) -> Result<()> {
+    if !exec.dry_run {
+        ensure_autotools_available()?;
+    }
     let mut cmd = Command::new("sh");
     cmd.arg("./autogen.sh").current_dir(repo_path);
-    apply_common_env(&mut cmd, toolchain, cflags, ldflags);
-    cmd.run_with_verbose(verbose).await
+    apply_common_env(&mut cmd, toolchain, cflags, cppflags, ldflags);
+    cmd.run_with(exec).await
 }
 
-async fn try_make_clean(repo_path: &Path) {
+async fn try_make_clean(repo_path: &Path, exec: ExecOptions) {
+    if exec.dry_run {
+        return;
+    }
     let _ = Command::new("make")
         .current_dir(repo_path)
         .arg("clean")
@@ -1,33 +1,99 @@
-use crate::config;
+use crate::config::{self, Config, Platform};
 use crate::repo;
+use crate::utils::ExecOptions;
 use anyhow::Result;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Copy)]
 pub struct CleanOptions {
-    pub verbose: bool,
+    pub verbose: u8,
+    pub dry_run: bool,
     pub clean_build_dir: bool,
     pub clean_repos: bool,
+    /// Delete the repo checkouts instead of resetting them, so the next build
+    /// or `fetch` clones them again.
+    pub reclone: bool,
+    /// Let `reclone` delete checkouts found via `paths.repo_path`.
+    pub reclone_user_repos: bool,
+    /// Remove the download caches (opus model, `cache_globs` files).
+    pub clean_cache: bool,
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
 }
 
+/// Clean using `build_config.toml` from the current directory.
 pub async fn run(options: CleanOptions) -> Result<()> {
     let config_path = PathBuf::from("build_config.toml");
-    let config = config::load_or_create_config(&config_path)?;
+    let config = config::load_or_create_config(&config_path, options.strict_config)?;
+    run_with_config(&config, options).await
+}
+
+fn remove_dir(dir: &Path, dry_run: bool) -> Result<()> {
+    if dir.exists() {
+        if dry_run {
+            log::info!("[dry-run] Would remove {}", dir.display());
+        } else {
+            fs::remove_dir_all(dir)?;
+            log::info!("Removed {}", dir.display());
+        }
+    }
+    Ok(())
+}
 
+/// What `build --clean <platform>` removes: the platform's intermediates under
+/// `build/<platform>` and, for Android/HarmonyOS, its packaged libraries and
+/// symbols. Apple platforms share `build/lib/darwin` and every platform shares
+/// `build/include`, so those are left to be overwritten by the rebuild.
+pub fn platform_dirs(config: &Config, platform: Platform) -> Vec<PathBuf> {
+    let build_dir = &config.paths.build_dir;
+    let name = platform.to_string().to_lowercase();
+    let mut dirs = vec![build_dir.join(&name)];
+    if matches!(platform, Platform::Android | Platform::Harmony) {
+        dirs.push(build_dir.join("lib").join(&name));
+        dirs.push(build_dir.join("symbols").join(&name));
+    }
+    dirs
+}
+
+/// Remove the build outputs of one platform, keeping every other platform's.
+pub fn clean_platform(config: &Config, platform: Platform, dry_run: bool) -> Result<()> {
+    for dir in platform_dirs(config, platform) {
+        remove_dir(&dir, dry_run)?;
+    }
+    Ok(())
+}
+
+/// Remove the build directory, reset (or delete) repos and/or remove the download caches.
+pub async fn run_with_config(config: &Config, options: CleanOptions) -> Result<()> {
     if options.clean_build_dir {
-        let build_dir = &config.paths.build_dir;
-        if build_dir.exists() {
-            fs::remove_dir_all(build_dir)?;
-            log::info!("Removed {}", build_dir.display());
+        remove_dir(&config.paths.build_dir, options.dry_run)?;
+    }
+
+    if options.clean_cache {
+        for dir in [repo::MODEL_CACHE_DIR, repo::REPO_CACHE_DIR] {
+            remove_dir(Path::new(dir), options.dry_run)?;
         }
     }
 
     if options.clean_repos {
-        let repos = repo::get_repos(&config)?;
+        let repos = repo::get_repos(config)?;
         for repo in &repos {
-            if repo.local_path.exists() {
-                repo.clean(options.verbose).await?;
+            if options.reclone {
+                repo.remove_for_reclone(
+                    options.reclone_user_repos,
+                    ExecOptions {
+                        dry_run: options.dry_run,
+                        ..ExecOptions::default()
+                    },
+                )?;
+            } else if repo.local_path.exists() {
+                repo.clean(ExecOptions {
+                    verbose: options.verbose,
+                    dry_run: options.dry_run,
+                    ..ExecOptions::default()
+                })
+                .await?;
             }
         }
     }
@@ -1,11 +1,69 @@
-use clap::{Parser, Subcommand};
+use crate::config::{Arch, Library, Platform};
+use crate::logging::LogFormat;
+use crate::package::ArchiveFormat;
+use crate::ui::ColorChoice;
+use clap::{ArgAction, Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[command(name = "opus-builder")]
 #[command(about = "Build opus-family libraries for multiple platforms")]
 pub struct Cli {
-    #[arg(short = 'v', long = "verbose", global = true)]
-    pub verbose: bool,
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        global = true,
+        action = ArgAction::Count,
+        help = "Stream command output; -vv also logs each command's working directory and environment, -vvv traces config and repo resolution"
+    )]
+    pub verbose: u8,
+
+    #[arg(
+        long = "dry-run",
+        global = true,
+        help = "Print commands instead of executing them"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long = "offline",
+        global = true,
+        help = "Forbid network access; repos and the opus model must already be present"
+    )]
+    pub offline: bool,
+
+    #[arg(
+        short = 'j',
+        long = "jobs",
+        global = true,
+        help = "Parallel make jobs, overrides build.make_concurrent_jobs (0 = number of CPUs)"
+    )]
+    pub jobs: Option<u32>,
+
+    #[arg(
+        long = "strict-config",
+        global = true,
+        help = "Fail on unknown keys in build_config.toml instead of warning"
+    )]
+    pub strict_config: bool,
+
+    #[arg(
+        long = "log-format",
+        global = true,
+        value_enum,
+        default_value_t = LogFormat::Text,
+        help = "Log output format"
+    )]
+    pub log_format: LogFormat,
+
+    #[arg(
+        long = "color",
+        global = true,
+        value_enum,
+        default_value_t = ColorChoice::Auto,
+        help = "Color progress and log output (auto respects NO_COLOR)"
+    )]
+    pub color: ColorChoice,
 
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -15,6 +73,28 @@ pub struct Cli {
 pub enum Commands {
     Build(BuildArgs),
     Clean(CleanArgs),
+    /// Copy headers from existing build artifacts into build/include without rebuilding
+    Headers,
+    /// Verify build outputs against build/SHA256SUMS
+    Verify,
+    /// Archive build/include and build/lib into a release package
+    Package(PackageArgs),
+    /// Show the architectures contained in a library and check them against its path
+    Inspect(InspectArgs),
+    /// Print a library's version, repo, dependencies, flags and output paths
+    Info(InfoArgs),
+    /// Print the CFLAGS/CPPFLAGS/LDFLAGS each library would be built with per platform and arch
+    Flags(FlagsArgs),
+    /// Clone and check out every configured repo without building, e.g. as a separate CI stage
+    Fetch,
+    /// Bump library versions in build_config.toml to the latest upstream release tags
+    Update,
+    /// Build, then rebuild the affected libraries whenever build_config.toml or a checkout changes
+    #[cfg(feature = "watch")]
+    Watch(WatchArgs),
+    /// Manage build_config.toml
+    #[command(subcommand)]
+    Config(ConfigCommands),
 }
 
 #[derive(Debug, Parser)]
@@ -25,6 +105,187 @@ pub struct BuildArgs {
         help = "Force rebuild, ignoring build/{platform} cache"
     )]
     pub force: bool,
+
+    #[arg(
+        long = "log-file",
+        help = "Write logs and command output to build/logs/build-<timestamp>.log"
+    )]
+    pub log_file: bool,
+
+    #[arg(
+        short = 'k',
+        long = "keep-going",
+        help = "Keep building other targets after a failure and report all failures at the end"
+    )]
+    pub keep_going: bool,
+
+    #[arg(
+        long = "reclone",
+        help = "Delete the repo checkouts and clone them again, e.g. after upstream history was rewritten"
+    )]
+    pub reclone: bool,
+
+    #[arg(
+        long = "reclone-user-repos",
+        requires = "reclone",
+        help = "Allow --reclone to delete checkouts found via paths.repo_path outside repos/"
+    )]
+    pub reclone_user_repos: bool,
+
+    #[arg(
+        long = "resume",
+        visible_alias = "no-clean",
+        conflicts_with = "reclone",
+        help = "Build the repo checkouts as they are, keeping local edits (skips reset and clean)"
+    )]
+    pub resume: bool,
+
+    #[arg(
+        long = "strict",
+        help = "Fail instead of warning when a library to package or merge into a universal binary is missing (on by default when CI is set)"
+    )]
+    pub strict: bool,
+
+    #[arg(
+        long = "run-tests",
+        help = "Run each library's `make check` after building it for the native macOS arch (other targets can't run on the host)"
+    )]
+    pub run_tests: bool,
+
+    #[arg(
+        long = "smoke-test",
+        help = "Link and run a tiny program against each built library on the host or an attached Android device (adb)"
+    )]
+    pub smoke_test: bool,
+
+    #[arg(
+        long = "only",
+        requires_all = ["platform", "arch"],
+        help = "Build a single library (with its missing dependencies), e.g. libopus"
+    )]
+    pub only: Option<Library>,
+
+    #[arg(
+        long = "platform",
+        requires = "only",
+        help = "Platform for --only, e.g. ios"
+    )]
+    pub platform: Option<Platform>,
+
+    #[arg(long = "arch", requires = "only", help = "Arch for --only, e.g. arm64")]
+    pub arch: Option<Arch>,
+
+    #[arg(
+        long = "clean",
+        value_name = "PLATFORM",
+        help = "Remove this platform's build outputs before building, keeping the other platforms' (repeatable), e.g. after switching NDKs"
+    )]
+    pub clean: Vec<Platform>,
+
+    #[arg(
+        long = "ref",
+        value_name = "REF",
+        help = "Build --library (or the --only library) at this tag, commit or branch:<name> instead of its configured version, e.g. to bisect"
+    )]
+    pub git_ref: Option<String>,
+
+    #[arg(
+        long = "library",
+        requires = "git_ref",
+        help = "Library --ref applies to, e.g. libopus; defaults to the --only library"
+    )]
+    pub library: Option<Library>,
+}
+
+#[derive(Debug, Parser)]
+pub struct PackageArgs {
+    #[arg(
+        short = 'o',
+        long = "output",
+        default_value = ".",
+        help = "Directory to write the archive to"
+    )]
+    pub output: PathBuf,
+
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t = ArchiveFormat::TarGz,
+        help = "Archive format"
+    )]
+    pub format: ArchiveFormat,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommands {
+    /// Write the default build_config.toml
+    Init(ConfigInitArgs),
+    /// Upgrade build_config.toml to the current schema, keeping a .bak of the original
+    Migrate,
+}
+
+#[derive(Debug, Parser)]
+pub struct ConfigInitArgs {
+    #[arg(
+        short = 'f',
+        long = "force",
+        help = "Overwrite an existing build_config.toml"
+    )]
+    pub force: bool,
+
+    #[arg(
+        long = "stdout",
+        help = "Print the default config instead of writing it"
+    )]
+    pub stdout: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct InspectArgs {
+    #[arg(help = "Library or object file, e.g. build/ios/arm64/libogg/lib/libogg.a")]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Parser)]
+pub struct InfoArgs {
+    #[arg(help = "Library to describe, e.g. libopus")]
+    pub library: Library,
+}
+
+#[derive(Debug, Parser)]
+pub struct FlagsArgs {
+    #[arg(
+        long = "library",
+        help = "Only print flags for this library, e.g. libopus"
+    )]
+    pub library: Option<Library>,
+
+    #[arg(
+        long = "platform",
+        help = "Only print flags for this platform, e.g. ios"
+    )]
+    pub platform: Option<Platform>,
+
+    #[arg(long = "arch", help = "Only print flags for this arch, e.g. arm64")]
+    pub arch: Option<Arch>,
+}
+
+#[cfg(feature = "watch")]
+#[derive(Debug, Parser)]
+pub struct WatchArgs {
+    #[arg(
+        long = "debounce-ms",
+        default_value_t = 500,
+        help = "Wait this long after the last change before rebuilding"
+    )]
+    pub debounce_ms: u64,
+
+    #[arg(
+        short = 'k',
+        long = "keep-going",
+        help = "Keep building other targets after a failure"
+    )]
+    pub keep_going: bool,
 }
 
 #[derive(Debug, Parser)]
@@ -34,14 +295,35 @@ pub struct CleanArgs {
 
     #[arg(short = 'r', long = "repo", help = "Git reset repos")]
     pub repo: bool,
+
+    #[arg(
+        long = "cache",
+        help = "Remove downloaded caches (opus-model/, repo-cache/)"
+    )]
+    pub cache: bool,
+
+    #[arg(
+        long = "reclone",
+        help = "Delete the repo checkouts instead of resetting them; the next build clones them again"
+    )]
+    pub reclone: bool,
+
+    #[arg(
+        long = "reclone-user-repos",
+        requires = "reclone",
+        help = "Allow --reclone to delete checkouts found via paths.repo_path outside repos/"
+    )]
+    pub reclone_user_repos: bool,
 }
 
 impl CleanArgs {
-    pub fn normalized(&self) -> (bool, bool) {
-        if !self.build && !self.repo {
-            (true, true)
+    /// `(build, repo, cache)`; without flags, build and repos are cleaned but
+    /// the cache is kept to avoid re-downloading. `--reclone` implies `--repo`.
+    pub fn normalized(&self) -> (bool, bool, bool) {
+        if !self.build && !self.repo && !self.cache && !self.reclone {
+            (true, true, false)
         } else {
-            (self.build, self.repo)
+            (self.build, self.repo || self.reclone, self.cache)
         }
     }
 }
@@ -1,8 +1,11 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(default)]
@@ -11,6 +14,7 @@ pub struct Config {
     pub paths: PathConfig,
     pub build: Build,
     pub platforms: PlatformConfig,
+    pub darwin: DarwinOptions,
     pub libraries: HashMap<Library, LibraryBuildOptions>,
 }
 
@@ -18,20 +22,25 @@ impl Default for Config {
     fn default() -> Self {
         let platforms = PlatformConfig {
             macos: DarwinConfig {
-                min_version: "10.13".to_string(),
+                min_version: "10.13".into(),
                 archs: vec![Arch::Arm64, Arch::X86_64],
-                lib_type: LibType::Static,
+                lib_types: vec![LibType::Static],
             },
             ios: DarwinConfig {
-                min_version: "11.0".to_string(),
+                min_version: "11.0".into(),
                 archs: vec![Arch::Arm64],
-                lib_type: LibType::Static,
+                lib_types: vec![LibType::Static],
             },
             ios_sim: DarwinConfig {
-                min_version: "11.0".to_string(),
+                min_version: "11.0".into(),
                 archs: vec![Arch::Arm64, Arch::X86_64],
-                lib_type: LibType::Static,
+                lib_types: vec![LibType::Static],
             },
+            maccatalyst: default_maccatalyst(),
+            tvos: default_tvos(),
+            tvos_sim: default_tvos_sim(),
+            visionos: default_visionos(),
+            visionos_sim: default_visionos(),
             android: AndroidConfig::default(),
             harmony: HarmonyConfig::default(),
         };
@@ -41,44 +50,38 @@ impl Default for Config {
             Library::Libogg,
             LibraryBuildOptions {
                 version: Some("v1.3.5".to_string()),
-                cflags: None,
-                ldflags: None,
-                configure_flags: None,
+                ..Default::default()
             },
         );
         libraries.insert(
             Library::Libopus,
             LibraryBuildOptions {
                 version: Some("v1.5.2".to_string()),
-                cflags: None,
-                ldflags: None,
                 configure_flags: Some(vec![
                     "--enable-float-approx".to_string(),
                     "--disable-extra-programs".to_string(),
                     "--disable-doc".to_string(),
                 ]),
+                ..Default::default()
             },
         );
         libraries.insert(
             Library::Libopusenc,
             LibraryBuildOptions {
                 version: Some("v0.2.1".to_string()),
-                cflags: None,
-                ldflags: None,
-                configure_flags: None,
+                ..Default::default()
             },
         );
         libraries.insert(
             Library::Libopusfile,
             LibraryBuildOptions {
                 version: Some("v0.12".to_string()),
-                cflags: None,
-                ldflags: None,
                 configure_flags: Some(vec![
                     "--disable-http".to_string(),
                     "--disable-examples".to_string(),
                     "--disable-doc".to_string(),
                 ]),
+                ..Default::default()
             },
         );
 
@@ -87,21 +90,62 @@ impl Default for Config {
             paths: PathConfig::default(),
             build: Build::default(),
             platforms,
+            darwin: DarwinOptions::default(),
             libraries,
         }
     }
 }
 
+/// Prefix marking a library `version` as a branch to track, e.g. `branch:main`.
+pub const BRANCH_PREFIX: &str = "branch:";
+
+/// Returns the branch name if `version` uses the `branch:` prefix.
+pub fn tracked_branch(version: &str) -> Option<&str> {
+    version.strip_prefix(BRANCH_PREFIX)
+}
+
 impl Config {
-    pub fn get_library_version(&self, library: &Library) -> Result<&str> {
+    /// Version used in output names; `branch:main` yields `main`, and the `/`
+    /// of branches like `release/1.5` becomes `-` so it stays one path component.
+    pub fn get_library_version(&self, library: &Library) -> Result<Cow<'_, str>> {
         let lib_config = self
             .libraries
             .get(library)
             .with_context(|| format!("Library configuration not found for: {library:?}"))?;
-        lib_config
+        let version = lib_config
             .version
             .as_deref()
-            .with_context(|| format!("Version not specified for library: {library:?}"))
+            .with_context(|| format!("Version not specified for library: {library:?}"))?;
+        Ok(match tracked_branch(version) {
+            Some(branch) if branch.contains('/') => Cow::Owned(branch.replace('/', "-")),
+            Some(branch) => Cow::Borrowed(branch),
+            None => Cow::Borrowed(version),
+        })
+    }
+
+    /// Build `library` from `git_ref` (tag, commit or `branch:<name>`) instead of
+    /// its configured version, e.g. from `build --ref`. Not written back to the file.
+    pub fn override_version(&mut self, library: &Library, git_ref: &str) -> Result<()> {
+        let opts = self.libraries.entry(*library).or_default();
+        if opts.source.as_ref().is_some_and(|s| s.tarball.is_some()) {
+            anyhow::bail!("{library} is built from a tarball, a git ref can't be checked out");
+        }
+        if opts.repo_subpath.is_some() {
+            anyhow::bail!(
+                "{library} lives in a monorepo (repo_subpath), which is never checked out"
+            );
+        }
+        log::info!("Building {library} at {git_ref} instead of its configured version");
+        opts.version = Some(git_ref.to_string());
+        Ok(())
+    }
+
+    /// Whether `library` follows a moving branch tip rather than a fixed tag/commit.
+    pub fn tracks_branch(&self, library: &Library) -> bool {
+        self.libraries
+            .get(library)
+            .and_then(|opts| opts.version.as_deref())
+            .is_some_and(|v| tracked_branch(v).is_some())
     }
 }
 
@@ -120,7 +164,44 @@ impl std::fmt::Display for Library {
     }
 }
 
+impl FromStr for Library {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_enum_value(s).with_context(|| format!("Unknown library: {s}"))
+    }
+}
+
 impl Library {
+    /// This library preceded by all of its transitive [`Library::deps`].
+    pub fn with_dependencies(&self) -> Result<Vec<Library>> {
+        let mut libraries = vec![*self];
+        let mut i = 0;
+        while let Some(library) = libraries.get(i).copied() {
+            for dep in library.deps() {
+                if !libraries.contains(dep) {
+                    libraries.push(*dep);
+                }
+            }
+            i += 1;
+        }
+        sort_by_dependencies(&libraries)
+    }
+    /// Libraries that must be built (for the same platform/arch) before this one.
+    pub fn deps(&self) -> &'static [Library] {
+        match self {
+            Library::Libogg | Library::Libopus => &[],
+            Library::Libopusenc => &[Library::Libopus],
+            Library::Libopusfile => &[Library::Libogg, Library::Libopus],
+        }
+    }
+    /// Files kept across `git clean -fdx` when `cache_globs` is not configured.
+    pub fn default_cache_globs(&self) -> &'static [&'static str] {
+        match self {
+            Library::Libopus => &["opus_data-*.tar.gz"],
+            Library::Libogg | Library::Libopusenc | Library::Libopusfile => &[],
+        }
+    }
     pub fn repo_name(&self) -> &'static str {
         match self {
             Library::Libopus => "opus",
@@ -161,6 +242,12 @@ pub enum Platform {
     Android,
     Harmony,
     Macos,
+    #[serde(rename = "maccatalyst")]
+    MacCatalyst,
+    Tvos,
+    TvosSim,
+    Visionos,
+    VisionosSim,
 }
 impl std::fmt::Display for Platform {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -170,13 +257,36 @@ impl std::fmt::Display for Platform {
             Platform::Android => write!(f, "android"),
             Platform::Harmony => write!(f, "harmony"),
             Platform::Macos => write!(f, "macos"),
+            Platform::MacCatalyst => write!(f, "maccatalyst"),
+            Platform::Tvos => write!(f, "tvos"),
+            Platform::TvosSim => write!(f, "tvos-sim"),
+            Platform::Visionos => write!(f, "visionos"),
+            Platform::VisionosSim => write!(f, "visionos-sim"),
         }
     }
 }
 
+impl FromStr for Platform {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_enum_value(s).with_context(|| format!("Unknown platform: {s}"))
+    }
+}
+
 impl Platform {
     pub fn is_darwin(&self) -> bool {
-        matches!(self, Platform::Macos | Platform::Ios | Platform::IosSim)
+        matches!(
+            self,
+            Platform::Macos
+                | Platform::Ios
+                | Platform::IosSim
+                | Platform::MacCatalyst
+                | Platform::Tvos
+                | Platform::TvosSim
+                | Platform::Visionos
+                | Platform::VisionosSim
+        )
     }
 }
 
@@ -186,6 +296,9 @@ pub enum Arch {
     X86_64,
     #[serde(rename = "arm64")]
     Arm64,
+    /// Apple arm64 with pointer authentication
+    #[serde(rename = "arm64e")]
+    Arm64e,
     #[serde(rename = "armeabi-v7a")]
     ArmeabiV7a,
     #[serde(rename = "arm64-v8a")]
@@ -199,6 +312,14 @@ impl std::fmt::Display for Arch {
     }
 }
 
+impl FromStr for Arch {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_enum_value(s).with_context(|| format!("Unknown arch: {s}"))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LibType {
@@ -207,6 +328,13 @@ pub enum LibType {
 }
 
 impl LibType {
+    /// Lowercase name as written in `lib_types`, e.g. `"static"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LibType::Static => "static",
+            LibType::Shared => "shared",
+        }
+    }
     pub fn linux_ext(&self) -> &'static str {
         match self {
             LibType::Static => "a",
@@ -227,36 +355,169 @@ pub struct PlatformConfig {
     pub ios: DarwinConfig,
     #[serde(rename = "ios-sim")]
     pub ios_sim: DarwinConfig,
+    #[serde(default = "default_maccatalyst")]
+    pub maccatalyst: DarwinConfig,
+    #[serde(default = "default_tvos")]
+    pub tvos: DarwinConfig,
+    #[serde(rename = "tvos-sim", default = "default_tvos_sim")]
+    pub tvos_sim: DarwinConfig,
+    #[serde(default = "default_visionos")]
+    pub visionos: DarwinConfig,
+    #[serde(rename = "visionos-sim", default = "default_visionos")]
+    pub visionos_sim: DarwinConfig,
     pub android: AndroidConfig,
     pub harmony: HarmonyConfig,
 }
 
+fn default_maccatalyst() -> DarwinConfig {
+    DarwinConfig {
+        // iOS version, Mac Catalyst requires at least 13.1
+        min_version: "13.1".into(),
+        archs: vec![Arch::Arm64, Arch::X86_64],
+        lib_types: vec![LibType::Static],
+    }
+}
+
+fn default_tvos() -> DarwinConfig {
+    DarwinConfig {
+        min_version: "12.0".into(),
+        archs: vec![Arch::Arm64],
+        lib_types: vec![LibType::Static],
+    }
+}
+
+fn default_tvos_sim() -> DarwinConfig {
+    DarwinConfig {
+        min_version: "12.0".into(),
+        archs: vec![Arch::Arm64, Arch::X86_64],
+        lib_types: vec![LibType::Static],
+    }
+}
+
+fn default_visionos() -> DarwinConfig {
+    DarwinConfig {
+        min_version: "1.0".into(),
+        archs: vec![Arch::Arm64],
+        lib_types: vec![LibType::Static],
+    }
+}
+
 impl PlatformConfig {
+    pub fn darwin_config(&self, platform: &Platform) -> Option<&DarwinConfig> {
+        match platform {
+            Platform::Macos => Some(&self.macos),
+            Platform::Ios => Some(&self.ios),
+            Platform::IosSim => Some(&self.ios_sim),
+            Platform::MacCatalyst => Some(&self.maccatalyst),
+            Platform::Tvos => Some(&self.tvos),
+            Platform::TvosSim => Some(&self.tvos_sim),
+            Platform::Visionos => Some(&self.visionos),
+            Platform::VisionosSim => Some(&self.visionos_sim),
+            Platform::Android | Platform::Harmony => None,
+        }
+    }
     pub fn get_archs_for_platform(&self, platform: &Platform) -> &[Arch] {
         match platform {
             Platform::Macos => &self.macos.archs,
             Platform::Ios => &self.ios.archs,
             Platform::IosSim => &self.ios_sim.archs,
+            Platform::MacCatalyst => &self.maccatalyst.archs,
+            Platform::Tvos => &self.tvos.archs,
+            Platform::TvosSim => &self.tvos_sim.archs,
+            Platform::Visionos => &self.visionos.archs,
+            Platform::VisionosSim => &self.visionos_sim.archs,
             Platform::Android => &self.android.archs,
             Platform::Harmony => &self.harmony.archs,
         }
     }
-    pub fn get_lib_type_for_platform(&self, platform: &Platform) -> LibType {
+    pub fn get_lib_types_for_platform(&self, platform: &Platform) -> &[LibType] {
         match platform {
-            Platform::Macos => self.macos.lib_type,
-            Platform::Ios => self.ios.lib_type,
-            Platform::IosSim => self.ios_sim.lib_type,
-            Platform::Android => self.android.lib_type,
-            Platform::Harmony => self.harmony.lib_type,
+            Platform::Macos => &self.macos.lib_types,
+            Platform::Ios => &self.ios.lib_types,
+            Platform::IosSim => &self.ios_sim.lib_types,
+            Platform::MacCatalyst => &self.maccatalyst.lib_types,
+            Platform::Tvos => &self.tvos.lib_types,
+            Platform::TvosSim => &self.tvos_sim.lib_types,
+            Platform::Visionos => &self.visionos.lib_types,
+            Platform::VisionosSim => &self.visionos_sim.lib_types,
+            Platform::Android => &self.android.lib_types,
+            Platform::Harmony => &self.harmony.lib_types,
+        }
+    }
+}
+
+/// Accepts both `lib_types = ["static", "shared"]` and the older `lib_type = "static"`.
+fn deserialize_lib_types<'de, D>(deserializer: D) -> std::result::Result<Vec<LibType>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(LibType),
+        Many(Vec<LibType>),
+    }
+
+    let mut lib_types = match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(lib_type) => vec![lib_type],
+        OneOrMany::Many(lib_types) => lib_types,
+    };
+    if lib_types.is_empty() {
+        return Err(serde::de::Error::custom("lib_types must not be empty"));
+    }
+    lib_types.sort();
+    lib_types.dedup();
+    Ok(lib_types)
+}
+
+/// Minimum deployment target: either `"11.0"` or a per-arch table such as
+/// `{ default = "11.0", arm64 = "13.0" }`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MinVersion {
+    Single(String),
+    PerArch {
+        default: String,
+        #[serde(flatten)]
+        archs: BTreeMap<Arch, String>,
+    },
+}
+
+impl MinVersion {
+    pub fn for_arch(&self, arch: Arch) -> &str {
+        match self {
+            MinVersion::Single(version) => version,
+            MinVersion::PerArch { default, archs } => archs.get(&arch).unwrap_or(default),
         }
     }
 }
 
+impl From<&str> for MinVersion {
+    fn from(version: &str) -> Self {
+        MinVersion::Single(version.to_string())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DarwinConfig {
-    pub min_version: String,
+    pub min_version: MinVersion,
     pub archs: Vec<Arch>,
-    pub lib_type: LibType,
+    #[serde(alias = "lib_type", deserialize_with = "deserialize_lib_types")]
+    pub lib_types: Vec<LibType>,
+}
+
+/// Settings shared by all Apple platforms.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct DarwinOptions {
+    /// Build with `-fembed-bitcode` (not supported by Xcode 14 and later).
+    pub embed_bitcode: bool,
+    /// Name of the merged xcframework created when `merge_static` is enabled
+    /// (defaults to `libopus_bundle`).
+    pub bundle_name: Option<String>,
+    /// Identity passed to `codesign --sign` for every created xcframework.
+    /// Signing is skipped when unset.
+    pub codesign_identity: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -264,7 +525,50 @@ pub struct AndroidConfig {
     pub native_api_level: u32,
     pub ndk_path: PathBuf,
     pub archs: Vec<Arch>,
-    pub lib_type: LibType,
+    #[serde(alias = "lib_type", deserialize_with = "deserialize_lib_types")]
+    pub lib_types: Vec<LibType>,
+    /// Link shared libraries with 16KB page alignment, required by Android 15+
+    /// devices and the Play Store.
+    #[serde(default = "default_page_size_16k")]
+    pub page_size_16k: bool,
+    /// Keep unstripped copies of shared libraries under `build/symbols/android/<abi>/`
+    /// for crash symbolication.
+    #[serde(default)]
+    pub symbols: bool,
+    /// C++ runtime linked into shared libraries.
+    #[serde(default)]
+    pub stl: AndroidStl,
+}
+
+/// The NDK C++ runtime to link, see
+/// <https://developer.android.com/ndk/guides/cpp-support>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AndroidStl {
+    /// Don't link a C++ runtime; enough for the C-only opus libraries.
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    /// Link `libc++_shared.so`, which then has to ship next to the libraries.
+    #[serde(rename = "c++_shared")]
+    CxxShared,
+    /// Link libc++ statically into each shared library.
+    #[serde(rename = "c++_static")]
+    CxxStatic,
+}
+
+impl AndroidStl {
+    /// Linker flags selecting this runtime.
+    pub fn ldflags(self) -> Option<&'static str> {
+        match self {
+            AndroidStl::None => None,
+            AndroidStl::CxxShared => Some("-lc++_shared"),
+            AndroidStl::CxxStatic => Some("-static-libstdc++ -lc++_static -lc++abi"),
+        }
+    }
+}
+
+fn default_page_size_16k() -> bool {
+    true
 }
 
 impl Default for AndroidConfig {
@@ -273,7 +577,10 @@ impl Default for AndroidConfig {
             native_api_level: 21,
             ndk_path: PathBuf::from("/usr/local/NDK-r28c"),
             archs: vec![Arch::Arm64V8a, Arch::ArmeabiV7a, Arch::X86_64, Arch::X86],
-            lib_type: LibType::Shared,
+            lib_types: vec![LibType::Shared],
+            page_size_16k: default_page_size_16k(),
+            symbols: false,
+            stl: AndroidStl::None,
         }
     }
 }
@@ -282,7 +589,8 @@ impl Default for AndroidConfig {
 pub struct HarmonyConfig {
     pub ndk_path: PathBuf,
     pub archs: Vec<Arch>,
-    pub lib_type: LibType,
+    #[serde(alias = "lib_type", deserialize_with = "deserialize_lib_types")]
+    pub lib_types: Vec<LibType>,
 }
 
 impl Default for HarmonyConfig {
@@ -292,7 +600,7 @@ impl Default for HarmonyConfig {
                 "/usr/local/command-line-tools/sdk/HarmonyOS-NEXT-DB3/openharmony",
             ),
             archs: vec![Arch::ArmeabiV7a, Arch::Arm64V8a, Arch::X86_64],
-            lib_type: LibType::Shared,
+            lib_types: vec![LibType::Shared],
         }
     }
 }
@@ -300,15 +608,32 @@ impl Default for HarmonyConfig {
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(default)]
 pub struct GeneralConfig {
+    /// Schema version the file was written for, see [`CONFIG_VERSION`].
+    pub config_version: u32,
     pub platforms: Vec<Platform>,
     pub libraries: Vec<Library>,
     pub keep_intermediate: bool,
     pub repo_prefix: String,
+    /// Tee logs and full command output into `<build_dir>/logs/build-<timestamp>.log`.
+    pub log_file: bool,
+    /// Also merge every static library into one `libopus_bundle.a` per platform/arch.
+    pub merge_static: bool,
+    /// Prefix child process output with `[<library>/<platform>/<arch>]`.
+    pub prefix_output: bool,
+    /// Copy headers into `build/include/<platform>/` per platform instead of one shared copy.
+    pub per_platform_headers: bool,
+    /// Repos cloned, checked out and cleaned at the same time.
+    pub max_parallel_clones: usize,
 }
 
+/// Current `general.config_version`; files without it are version 0.
+/// `config migrate` upgrades older files.
+pub const CONFIG_VERSION: u32 = 2;
+
 impl Default for GeneralConfig {
     fn default() -> Self {
         Self {
+            config_version: CONFIG_VERSION,
             platforms: vec![
                 Platform::IosSim,
                 Platform::Ios,
@@ -323,6 +648,11 @@ impl Default for GeneralConfig {
             ],
             keep_intermediate: false,
             repo_prefix: "https://gitlab.xiph.org/xiph/".to_string(),
+            log_file: false,
+            merge_static: false,
+            per_platform_headers: false,
+            prefix_output: false,
+            max_parallel_clones: 4,
         }
     }
 }
@@ -330,8 +660,11 @@ impl Default for GeneralConfig {
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(default)]
 pub struct PathConfig {
+    /// Directories searched in order for `<repo_name>` (or `repo_subpath`).
     pub repo_path: Vec<PathBuf>,
     pub build_dir: PathBuf,
+    /// Also search the current directory and all of its parents after `repo_path`.
+    pub search_parent_dirs: bool,
 }
 
 impl Default for PathConfig {
@@ -339,27 +672,144 @@ impl Default for PathConfig {
         Self {
             repo_path: vec![PathBuf::from("repos")],
             build_dir: PathBuf::from("build"),
+            search_parent_dirs: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lto {
+    Off,
+    Thin,
+    #[default]
+    Full,
+}
+
+impl Lto {
+    /// Compiler/linker flag enabling this LTO mode, `None` for [`Lto::Off`].
+    pub fn flag(&self) -> Option<&'static str> {
+        match self {
+            Lto::Off => None,
+            Lto::Thin => Some("-flto=thin"),
+            Lto::Full => Some("-flto"),
+        }
+    }
+}
+
+/// Build system used to configure and build a library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildSystem {
+    /// `autogen.sh`, `configure` and `make`.
+    #[default]
+    Autotools,
+    /// `cmake -S . -B <obj>` with the platform's toolchain file.
+    Cmake,
+    /// `meson setup` with a generated cross file, then `ninja`.
+    Meson,
+}
+
+impl std::fmt::Display for BuildSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildSystem::Autotools => write!(f, "autotools"),
+            BuildSystem::Cmake => write!(f, "cmake"),
+            BuildSystem::Meson => write!(f, "meson"),
         }
     }
 }
 
+/// What to do with the libtool `.la` archives `make install` leaves in `lib/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaFiles {
+    /// Delete them; pkg-config and plain `-l` linking don't need them.
+    #[default]
+    Delete,
+    /// Replace absolute paths in `dependency_libs` by `-l<name>` flags.
+    Relocate,
+    /// Leave them as installed.
+    Keep,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(default)]
 pub struct Build {
+    /// `0` means "auto": use the number of logical CPUs.
     pub make_concurrent_jobs: u32,
     pub cflags: String,
     pub ldflags: String,
     pub configure_flags: Vec<String>,
+    /// Kill a build command (configure, make, ...) that runs longer than this; `0` disables it.
+    pub command_timeout_secs: u64,
+    /// Lines of output repeated at the bottom when a command fails in verbose mode.
+    pub failure_tail_lines: usize,
+    /// Handling of installed libtool `.la` files.
+    pub la_files: LaFiles,
+    /// Append `-ffast-math` to cflags; can be overridden per library.
+    pub fast_math: bool,
+    /// Link-time optimization, added to both cflags and ldflags.
+    pub lto: Lto,
+    /// Strip build paths and timestamps so repeated builds are byte-identical.
+    pub reproducible: bool,
+    /// Sanitizers to instrument with, e.g. `["address", "undefined"]`. Debugging only.
+    pub sanitizers: Vec<String>,
+    /// Link and run a tiny program against every built library where possible.
+    pub smoke_test: bool,
 }
 
 impl Default for Build {
     fn default() -> Self {
         Self {
             make_concurrent_jobs: 8,
-            cflags: "-O3 -g -DNDEBUG -ffast-math".to_string(),
-            ldflags: "-flto -fPIE".to_string(),
+            cflags: "-O3 -g -DNDEBUG".to_string(),
+            ldflags: String::new(),
             configure_flags: vec!["--with-pic".to_string()],
+            command_timeout_secs: 0,
+            failure_tail_lines: crate::utils::DEFAULT_FAILURE_TAIL_LINES,
+            la_files: LaFiles::Delete,
+            fast_math: true,
+            lto: Lto::Full,
+            reproducible: false,
+            sanitizers: Vec::new(),
+            smoke_test: false,
+        }
+    }
+}
+
+impl Build {
+    /// Number of jobs to pass to `make -j`, resolving `0` to the available CPUs.
+    pub fn resolved_make_jobs(&self) -> u32 {
+        if self.make_concurrent_jobs > 0 {
+            return self.make_concurrent_jobs;
         }
+        std::thread::available_parallelism()
+            .map(|n| n.get() as u32)
+            .unwrap_or(1)
+    }
+
+    /// `-fsanitize=...` flags for `sanitizers`, `None` when no sanitizer is enabled.
+    pub fn sanitize_flags(&self) -> Option<String> {
+        (!self.sanitizers.is_empty()).then(|| {
+            format!(
+                "-fsanitize={} -fno-omit-frame-pointer",
+                self.sanitizers.join(",")
+            )
+        })
+    }
+
+    /// Whether `library` is compiled with `-ffast-math`.
+    pub fn fast_math_for(&self, library_options: Option<&LibraryBuildOptions>) -> bool {
+        library_options
+            .and_then(|opts| opts.fast_math)
+            .unwrap_or(self.fast_math)
+    }
+
+    /// Per-command timeout, `None` when `command_timeout_secs` is `0`.
+    pub fn command_timeout(&self) -> Option<std::time::Duration> {
+        (self.command_timeout_secs > 0)
+            .then(|| std::time::Duration::from_secs(self.command_timeout_secs))
     }
 }
 
@@ -370,22 +820,296 @@ pub struct LibraryBuildOptions {
     pub cflags: Option<String>,
     pub ldflags: Option<String>,
     pub configure_flags: Option<Vec<String>>,
+    /// Glob patterns, relative to the repo root, of files to keep across repo cleans.
+    /// Defaults to [`Library::default_cache_globs`].
+    pub cache_globs: Option<Vec<String>>,
+    /// Path of the library inside a `repo_path` entry, e.g. `vendor/opus` in a monorepo.
+    /// The checkout is then treated as managed by the enclosing repo.
+    pub repo_subpath: Option<PathBuf>,
+    /// libopus only: base URL the DNN model `opus_data-<version>.tar.gz` is fetched from.
+    pub model_url: Option<String>,
+    /// libopus only: model version (hash) to fetch; read from opus's `autogen.sh` when unset.
+    pub model_version: Option<String>,
+    /// libopusfile only: build with `--enable-http` instead of `--disable-http` (requires OpenSSL).
+    /// Only written when set, as it means nothing for the other libraries.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub enable_http: bool,
+    /// Overrides `build.fast_math` for this library.
+    pub fast_math: Option<bool>,
+    /// `autotools` (default), `cmake` or `meson`.
+    pub build_system: BuildSystem,
+    /// Configure and build inside the source tree instead of a separate object
+    /// directory, for build systems that don't support VPATH builds.
+    pub in_tree: bool,
+    /// Extra environment for this library's configure/build/install commands,
+    /// applied after (and so overriding) the toolchain's.
+    pub env: BTreeMap<String, String>,
+    /// Where the sources come from when not the default git repo.
+    pub source: Option<LibrarySource>,
+    /// Run the library's `make check` after building it for a host-runnable target.
+    pub run_tests: bool,
+    /// Targets run after `make`, in order; `["install"]` when unset.
+    pub make_targets: Option<Vec<String>>,
+    /// Extra arguments for every `make` invocation, e.g. `V=1`.
+    pub make_args: Vec<String>,
+}
+
+impl LibraryBuildOptions {
+    pub fn make_targets(&self) -> Vec<String> {
+        self.make_targets
+            .clone()
+            .unwrap_or_else(|| vec!["install".to_string()])
+    }
+}
+
+/// `source = { git = "..." }` or `source = { tarball = "..." }`; exactly one must be set.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LibrarySource {
+    /// Clone this git URL instead of `general.repo_prefix` + the repo name.
+    pub git: Option<String>,
+    /// Extract this release tarball (a local path or an http(s) URL) instead of
+    /// cloning; `version` is then only used to name the artifacts.
+    pub tarball: Option<String>,
+    /// Expected SHA256 (hex) of `tarball`; a mismatch fails before extraction.
+    pub sha256: Option<String>,
+}
+
+/// Orders `libraries` so each one comes after its [`Library::deps`], keeping the
+/// configured order otherwise. Dependencies that are not configured are skipped.
+pub fn sort_by_dependencies(libraries: &[Library]) -> Result<Vec<Library>> {
+    fn visit(
+        library: Library,
+        libraries: &[Library],
+        visiting: &mut Vec<Library>,
+        sorted: &mut Vec<Library>,
+    ) -> Result<()> {
+        if sorted.contains(&library) {
+            return Ok(());
+        }
+        if let Some(pos) = visiting.iter().position(|l| *l == library) {
+            let cycle: Vec<_> = visiting[pos..].iter().map(|l| l.to_string()).collect();
+            anyhow::bail!(
+                "Dependency cycle between libraries: {} -> {library}",
+                cycle.join(" -> ")
+            );
+        }
+        visiting.push(library);
+        for dep in library.deps() {
+            if libraries.contains(dep) {
+                visit(*dep, libraries, visiting, sorted)?;
+            }
+        }
+        visiting.pop();
+        sorted.push(library);
+        Ok(())
+    }
+
+    let mut sorted = Vec::with_capacity(libraries.len());
+    for library in libraries {
+        visit(*library, libraries, &mut Vec::new(), &mut sorted)?;
+    }
+    Ok(sorted)
+}
+
+/// Deserializes a unit enum variant from its serialized (config file) name.
+fn parse_enum_value<T: serde::de::DeserializeOwned>(s: &str) -> Result<T> {
+    use serde::de::IntoDeserializer;
+    let deserializer: serde::de::value::StrDeserializer<serde::de::value::Error> =
+        s.trim().into_deserializer();
+    Ok(T::deserialize(deserializer)?)
+}
+
+pub const ENV_JOBS: &str = "OPUS_BUILDER_JOBS";
+pub const ENV_PLATFORMS: &str = "OPUS_BUILDER_PLATFORMS";
+pub const ENV_LIBRARIES: &str = "OPUS_BUILDER_LIBRARIES";
+pub const ENV_BUILD_DIR: &str = "OPUS_BUILDER_BUILD_DIR";
+pub const ENV_ANDROID_NDK: &str = "OPUS_BUILDER_ANDROID_NDK";
+pub const ENV_HARMONY_NDK: &str = "OPUS_BUILDER_HARMONY_NDK";
+/// Standard NDK location variables, used when `ndk_path` doesn't exist.
+pub const ENV_ANDROID_NDK_FALLBACKS: [&str; 2] = ["ANDROID_NDK_HOME", "ANDROID_NDK_ROOT"];
+
+/// Applies `OPUS_BUILDER_*` environment variable overrides onto `config`.
+///
+/// Precedence is env > file > default. Empty values are ignored.
+pub fn apply_env_overrides(config: &mut Config) -> Result<()> {
+    apply_overrides_from(config, |key| env::var(key).ok())
+}
+
+/// Same as [`apply_env_overrides`], but reads values through `lookup`.
+pub fn apply_overrides_from(
+    config: &mut Config,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    let lookup = |key: &str| {
+        let value = lookup(key).filter(|v| !v.trim().is_empty());
+        if let Some(value) = &value {
+            log::trace!("Environment override {key}={value}");
+        }
+        value
+    };
+
+    if let Some(jobs) = lookup(ENV_JOBS) {
+        config.build.make_concurrent_jobs = jobs
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid {ENV_JOBS}: {jobs}"))?;
+    }
+    if let Some(platforms) = lookup(ENV_PLATFORMS) {
+        config.general.platforms = parse_list(&platforms)
+            .with_context(|| format!("Invalid {ENV_PLATFORMS}: {platforms}"))?;
+    }
+    if let Some(libraries) = lookup(ENV_LIBRARIES) {
+        config.general.libraries = parse_list(&libraries)
+            .with_context(|| format!("Invalid {ENV_LIBRARIES}: {libraries}"))?;
+    }
+    if let Some(build_dir) = lookup(ENV_BUILD_DIR) {
+        config.paths.build_dir = PathBuf::from(build_dir);
+    }
+    if let Some(ndk) = lookup(ENV_ANDROID_NDK) {
+        config.platforms.android.ndk_path = PathBuf::from(ndk);
+    } else if !config.platforms.android.ndk_path.exists()
+        && let Some(ndk) = ENV_ANDROID_NDK_FALLBACKS.iter().find_map(|key| lookup(key))
+    {
+        config.platforms.android.ndk_path = PathBuf::from(ndk);
+    }
+    if let Some(ndk) = lookup(ENV_HARMONY_NDK) {
+        config.platforms.harmony.ndk_path = PathBuf::from(ndk);
+    }
+    Ok(())
+}
+
+/// Parses a comma-separated list, skipping empty entries.
+fn parse_list<T: FromStr<Err = anyhow::Error>>(s: &str) -> Result<Vec<T>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(T::from_str)
+        .collect()
 }
 
-pub fn load_or_create_config(path: &PathBuf) -> Result<Config> {
-    if path.exists() {
+/// Applies `edit` to the config file at `path` through `toml_edit`, so user
+/// comments, key order and formatting survive the write-back. The edited file
+/// must still parse as a [`Config`].
+pub fn edit_config_file(
+    path: &Path,
+    edit: impl FnOnce(&mut toml_edit::DocumentMut) -> Result<()>,
+) -> Result<()> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut document: toml_edit::DocumentMut = contents
+        .parse()
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    edit(&mut document)?;
+    let edited = document.to_string();
+    toml::from_str::<Config>(&edited)
+        .with_context(|| format!("Refusing to write an invalid {}", path.display()))?;
+    fs::write(path, edited).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Sets `[libraries.<name>] version` for each of `versions` in the config file at `path`.
+pub fn write_library_versions(path: &Path, versions: &[(Library, String)]) -> Result<()> {
+    edit_config_file(path, |document| {
+        let libraries = document
+            .entry("libraries")
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                toml_edit::Item::Table(table)
+            })
+            .as_table_mut()
+            .context("`libraries` is not a table")?;
+        for (library, version) in versions {
+            let name = library.name_with_lib_prefix();
+            match libraries.get_mut(&name) {
+                Some(options) => options["version"] = toml_edit::value(version.as_str()),
+                // a new `[libraries.<name>]` section rather than an inline table
+                None => {
+                    let mut options = toml_edit::Table::new();
+                    options["version"] = toml_edit::value(version.as_str());
+                    libraries.insert(&name, toml_edit::Item::Table(options));
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// The default config as written to a new `build_config.toml`.
+pub fn default_config_toml() -> Result<String> {
+    let body = toml::to_string_pretty(&Config::default())?;
+    Ok(format!(
+        "# opus-builder configuration, see README.md for every option.\n\n{body}"
+    ))
+}
+
+/// Writes the default config to `path`, refusing to replace an existing file
+/// unless `force` is set.
+pub fn init_config(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists; pass --force to overwrite it or --stdout to print the default",
+            path.display()
+        );
+    }
+    fs::write(path, default_config_toml()?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Warn about keys of `value` the schema doesn't know, which would otherwise
+/// be silently ignored; fail instead when `strict`.
+fn check_unknown_keys(path: &Path, value: &toml::Value, strict: bool) -> Result<()> {
+    let unknown = crate::migrate::unknown_keys(value)?;
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    if strict {
+        anyhow::bail!(
+            "Unknown key(s) in {}: {}",
+            path.display(),
+            unknown.join(", ")
+        );
+    }
+    for key in unknown {
+        log::warn!("Unknown key `{key}` in {} is ignored", path.display());
+    }
+    Ok(())
+}
+
+/// Loads `path` (writing a default config there if missing) and applies env overrides.
+///
+/// Unknown keys are logged, or fail the load with `strict_config` (the
+/// `--strict-config` flag).
+pub fn load_or_create_config(path: &Path, strict_config: bool) -> Result<Config> {
+    let mut config = if path.exists() {
         log::info!("Loading config from {:?}", path);
         let config_str = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&config_str)?;
-        Ok(config)
+        let value: toml::Value = toml::from_str(&config_str)?;
+        check_unknown_keys(path, &value, strict_config)?;
+        let version = crate::migrate::file_config_version(&value);
+        if version < CONFIG_VERSION {
+            log::warn!(
+                "{} is config version {version}, the current one is {CONFIG_VERSION}; run `opus-builder config migrate` to upgrade it",
+                path.display()
+            );
+        }
+        value.try_into()?
     } else {
         log::info!(
             "Config file not found, creating a default one at {:?}",
             path
         );
-        let config = Config::default();
-        let config_str = toml::to_string_pretty(&config)?;
-        fs::write(path, config_str)?;
-        Ok(config)
-    }
+        fs::write(path, default_config_toml()?)?;
+        Config::default()
+    };
+    apply_env_overrides(&mut config)?;
+    log::trace!(
+        "Resolved config: platforms {:?}, libraries {:?}, build_dir {}",
+        config.general.platforms,
+        config.general.libraries,
+        config.paths.build_dir.display()
+    );
+    Ok(config)
 }
@@ -0,0 +1,66 @@
+use crate::config;
+use crate::repo;
+use crate::utils::ExecOptions;
+use anyhow::Result;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FetchOptions {
+    pub verbose: u8,
+    pub dry_run: bool,
+    pub offline: bool,
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
+}
+
+impl FetchOptions {
+    pub fn exec(&self) -> ExecOptions {
+        ExecOptions {
+            verbose: self.verbose,
+            dry_run: self.dry_run,
+            offline: self.offline,
+            ..ExecOptions::default()
+        }
+    }
+}
+
+/// Clone, check out and clean every configured library's repo (plus the opus
+/// model) without building anything. All repos are attempted; the errors are
+/// reported together at the end.
+pub async fn run(options: FetchOptions) -> Result<()> {
+    let config_path = PathBuf::from("build_config.toml");
+    let config = config::load_or_create_config(&config_path, options.strict_config)?;
+    let exec = options.exec();
+
+    let repos = repo::get_repos(&config)?;
+    let results = repo::prepare_parallel(
+        &repos,
+        config.general.max_parallel_clones,
+        |repo| async move {
+            repo.ensure(exec).await?;
+            repo.clean(exec).await?;
+            repo.ensure_model(exec).await
+        },
+    )
+    .await;
+
+    let mut failures = Vec::new();
+    for (repo, result) in repos.iter().zip(results) {
+        match result {
+            Ok(()) => match (&repo.tarball, repo.head_commit().await) {
+                (Some(tarball), _) => println!("{}: {} ({tarball})", repo.name, repo.version),
+                (None, Ok(commit)) => println!("{}: {} ({commit})", repo.name, repo.version),
+                (None, Err(_)) => println!("{}: {}", repo.name, repo.version),
+            },
+            Err(e) => {
+                log::error!("{}: {e:#}", repo.name);
+                failures.push(repo.name.clone());
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        anyhow::bail!("Failed to fetch {}", failures.join(", "));
+    }
+    Ok(())
+}
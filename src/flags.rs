@@ -0,0 +1,60 @@
+use crate::builder;
+use crate::config::{self, Arch, Library, Platform};
+use anyhow::Result;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlagsOptions {
+    pub library: Option<Library>,
+    pub platform: Option<Platform>,
+    pub arch: Option<Arch>,
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
+}
+
+/// Print the compiler and linker flags every configured (library, platform,
+/// arch) would be built with, without building anything.
+pub async fn run(options: FlagsOptions) -> Result<()> {
+    let config_path = PathBuf::from("build_config.toml");
+    let config = config::load_or_create_config(&config_path, options.strict_config)?;
+    let libraries = config::sort_by_dependencies(&config.general.libraries)?;
+
+    for platform in &config.general.platforms {
+        if options.platform.is_some_and(|p| p != *platform) {
+            continue;
+        }
+        let lib_types = config.platforms.get_lib_types_for_platform(platform);
+        for arch in config.platforms.get_archs_for_platform(platform) {
+            if options.arch.is_some_and(|a| a != *arch) {
+                continue;
+            }
+            let toolchain = match builder::prepare_toolchain(*platform, *arch, &config).await {
+                Ok(toolchain) => toolchain,
+                Err(e) => {
+                    log::warn!("{e:#}");
+                    continue;
+                }
+            };
+            for library in &libraries {
+                if options.library.is_some_and(|l| l != *library) {
+                    continue;
+                }
+                let flags = builder::assemble_flags(&config, library, &toolchain, lib_types, true)?;
+                println!(
+                    "{} / {platform} / {}",
+                    library.name_with_lib_prefix(),
+                    toolchain.arch_dir
+                );
+                println!("  CC:              {}", toolchain.cc);
+                println!("  CFLAGS:          {}", flags.cflags.trim());
+                println!("  CPPFLAGS:        {}", flags.cppflags.trim());
+                println!("  LDFLAGS:         {}", flags.ldflags.trim());
+                println!("  PKG_CONFIG_PATH: {}", flags.pkg_config_path);
+                for (key, value) in &flags.env {
+                    println!("  env:             {key}={value}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
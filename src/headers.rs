@@ -0,0 +1,27 @@
+use crate::config;
+use crate::post_build;
+use anyhow::Result;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeadersOptions {
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
+}
+
+/// Refresh `build/include` from existing build artifacts without rebuilding.
+pub async fn run(options: HeadersOptions) -> Result<()> {
+    let config_path = PathBuf::from("build_config.toml");
+    let config = config::load_or_create_config(&config_path, options.strict_config)?;
+
+    let copied = post_build::copy_headers_from_build_artifacts(&config)?;
+    if copied == 0 {
+        anyhow::bail!(
+            "No build artifacts found under {}. Run `build` first (with `keep_intermediate = true` so the per-arch include dirs are kept).",
+            config.paths.build_dir.display()
+        );
+    }
+
+    println!("Copied headers for {copied} libraries");
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use crate::builder;
+use crate::config::{self, Config, Library};
+use crate::post_build;
+use crate::repo;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy)]
+pub struct InfoOptions {
+    pub library: Library,
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
+}
+
+/// The commit `library` was last built from, as recorded in `build/manifest.json`.
+pub fn manifest_commit(config: &Config, library: &Library) -> Option<String> {
+    let manifest = fs::read_to_string(config.paths.build_dir.join("manifest.json")).ok()?;
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).ok()?;
+    manifest["libraries"]
+        .as_array()?
+        .iter()
+        .find(|entry| entry["name"] == library.name_with_lib_prefix())?["commit"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Print what the config says about one library, without building anything.
+pub async fn run(options: InfoOptions) -> Result<()> {
+    let config_path = PathBuf::from("build_config.toml");
+    let mut config = config::load_or_create_config(&config_path, options.strict_config)?;
+    let library = options.library;
+    config.general.libraries = vec![library];
+
+    let version = config.get_library_version(&library)?;
+    let lib_options = config.libraries.get(&library);
+    let repo = repo::get_repos(&config)?
+        .into_iter()
+        .next()
+        .context("get_repos returned no repo for the library")?;
+
+    println!("{}", library.name_with_lib_prefix());
+    println!("  version:         {version}");
+    match &repo.tarball {
+        Some(tarball) => println!("  tarball:         {tarball}"),
+        None => println!("  repo:            {}", repo.url),
+    }
+    let checkout = if !repo.local_path.exists() {
+        "not cloned".to_string()
+    } else if repo.tarball.is_some() {
+        "extracted".to_string()
+    } else {
+        match repo.head_commit().await {
+            Ok(head) => format!("HEAD {head}"),
+            Err(_) => "not a git checkout".to_string(),
+        }
+    };
+    println!(
+        "  checkout:        {} ({checkout})",
+        repo.local_path.display()
+    );
+    if let Some(commit) = manifest_commit(&config, &library) {
+        println!("  built commit:    {commit} (build/manifest.json)");
+    }
+    let deps = library.deps();
+    println!(
+        "  dependencies:    {}",
+        if deps.is_empty() {
+            "none".to_string()
+        } else {
+            deps.iter()
+                .map(Library::name_with_lib_prefix)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    );
+    if let Some(opts) = lib_options {
+        println!("  build system:    {}", opts.build_system);
+    }
+    println!(
+        "  configure flags: {}",
+        builder::configure_flags(&config, &library).join(" ")
+    );
+    println!(
+        "  headers:         {}",
+        config.paths.build_dir.join(library.include_dir()).display()
+    );
+    println!("  artifacts:");
+    for dir in post_build::output_dirs(&config, &library)? {
+        println!("    {}", dir.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use crate::config::Arch;
+use crate::platforms::darwin;
+use crate::utils;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+#[derive(Debug, Clone)]
+pub struct InspectOptions {
+    pub path: PathBuf,
+}
+
+const MACHO_MAGICS: [[u8; 4]; 6] = [
+    [0xfe, 0xed, 0xfa, 0xce],
+    [0xfe, 0xed, 0xfa, 0xcf],
+    [0xce, 0xfa, 0xed, 0xfe],
+    [0xcf, 0xfa, 0xed, 0xfe],
+    [0xca, 0xfe, 0xba, 0xbe],
+    [0xbe, 0xba, 0xfe, 0xca],
+];
+
+fn is_macho(data: &[u8]) -> bool {
+    MACHO_MAGICS.iter().any(|magic| data.starts_with(magic))
+}
+
+fn is_elf(data: &[u8]) -> bool {
+    data.starts_with(b"\x7fELF")
+}
+
+/// Architectures contained in a library or object file, named like the build
+/// directories (`arm64`, `x86_64`, `arm64-v8a`, ...). Mach-O files are read with
+/// `lipo -archs`, ELF files with `readelf -h` (or `llvm-readobj` as a fallback).
+pub async fn library_archs(path: &Path) -> Result<Vec<String>> {
+    let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    // a static archive holds the format of its first object
+    let first_object = if data.starts_with(b"!<arch>\n") {
+        utils::archive_members(&data)
+            .with_context(|| format!("Failed to parse {}", path.display()))?
+            .first()
+            .copied()
+            .unwrap_or_default()
+    } else {
+        &data
+    };
+
+    if is_macho(first_object) {
+        darwin::build::lipo_archs(path).await
+    } else if is_elf(first_object) {
+        elf_archs(path).await
+    } else {
+        anyhow::bail!("{} is neither a Mach-O nor an ELF file", path.display())
+    }
+}
+
+async fn elf_archs(path: &Path) -> Result<Vec<String>> {
+    let mut output = None;
+    for (tool, args) in [("readelf", "-h"), ("llvm-readobj", "--file-headers")] {
+        if let Ok(out) = Command::new(tool).arg(args).arg(path).output().await
+            && out.status.success()
+        {
+            output = Some(out);
+            break;
+        }
+    }
+    let output = output.context("Neither readelf nor llvm-readobj could read the file")?;
+
+    let mut archs = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some(machine) = line.trim().strip_prefix("Machine:") else {
+            continue;
+        };
+        let arch = elf_machine_arch(machine.trim()).unwrap_or(machine.trim());
+        if !archs.iter().any(|a| a == arch) {
+            archs.push(arch.to_string());
+        }
+    }
+    Ok(archs)
+}
+
+/// Android/Harmony ABI name for a `Machine:` value of `readelf` or `llvm-readobj`.
+fn elf_machine_arch(machine: &str) -> Option<&'static str> {
+    match machine.split_whitespace().next()? {
+        "AArch64" | "EM_AARCH64" => Some("arm64-v8a"),
+        "ARM" | "EM_ARM" => Some("armeabi-v7a"),
+        "Intel" | "EM_386" => Some("x86"),
+        "Advanced" | "EM_X86_64" => Some("x86_64"),
+        _ => None,
+    }
+}
+
+/// The arch a build output should contain, from its `<platform>/<arch>/...` path.
+pub fn expected_arch(path: &Path) -> Option<Arch> {
+    path.components()
+        .filter_map(|c| c.as_os_str().to_str()?.parse().ok())
+        .next_back()
+}
+
+pub async fn run(options: InspectOptions) -> Result<()> {
+    let archs = library_archs(&options.path).await?;
+    println!("{}: {}", options.path.display(), archs.join(", "));
+
+    if let Some(expected) = expected_arch(&options.path) {
+        if !archs
+            .iter()
+            .any(|a| a.parse::<Arch>().ok() == Some(expected))
+        {
+            anyhow::bail!(
+                "{} is in the build directory of {expected} but contains [{}]",
+                options.path.display(),
+                archs.join(", ")
+            );
+        }
+        println!("Matches the expected arch {expected} of its path");
+    }
+    Ok(())
+}
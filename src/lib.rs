@@ -1,9 +1,68 @@
+//! Build the opus family of libraries (libogg, libopus, libopusenc, libopusfile)
+//! for Apple, Android and HarmonyOS targets.
+//!
+//! The `opus-builder` binary is a thin wrapper over this crate. To embed it,
+//! load a [`Config`] and call [`build::run_with_config`]:
+//!
+//! ```no_run
+//! # async fn demo() -> anyhow::Result<()> {
+//! let config = opus_builder::load_or_create_config("build_config.toml".as_ref(), false)?;
+//! let report = opus_builder::build::run_with_config(
+//!     config,
+//!     opus_builder::BuildOptions {
+//!         verbose: 0,
+//!         dry_run: false,
+//!         offline: false,
+//!         jobs: None,
+//!         force: false,
+//!         log_file: false,
+//!         keep_going: false,
+//!         reclone: false,
+//!         reclone_user_repos: false,
+//!         resume: false,
+//!         trust_cache: false,
+//!         strict: false,
+//!         run_tests: false,
+//!         smoke_test: false,
+//!         strict_config: false,
+//!     },
+//! )
+//! .await?;
+//! println!("{} artifacts", report.artifacts.len());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A single target can be built with [`Builder`] after preparing its [`repo::Repo`].
+
 pub mod build;
 pub mod builder;
 pub mod clean;
 pub mod cli;
 pub mod config;
+pub mod fetch;
+pub mod flags;
+pub mod headers;
+pub mod info;
+pub mod inspect;
+pub mod logging;
+pub mod migrate;
+pub mod package;
 pub mod platforms;
 pub mod post_build;
 pub mod repo;
+pub mod sizes;
+pub mod smoke;
+pub mod timings;
+pub mod ui;
+pub mod update;
 pub mod utils;
+pub mod verify;
+#[cfg(feature = "watch")]
+pub mod watch;
+
+pub use build::{ArtifactInfo, BuildOptions, BuildReport};
+pub use builder::Builder;
+pub use clean::CleanOptions;
+pub use config::{Arch, Config, LibType, Library, Platform, load_or_create_config};
+pub use utils::ExecOptions;
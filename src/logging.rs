@@ -0,0 +1,136 @@
+use crate::ui::ColorChoice;
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+static LOG_FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable `env_logger` output
+    #[default]
+    Text,
+    /// One JSON object per line: `{level, ts, target, msg}`
+    Json,
+}
+
+/// Wraps `env_logger` so log lines are also written to the per-build log file.
+struct TeeLogger {
+    inner: env_logger::Logger,
+    format: LogFormat,
+}
+
+impl log::Log for TeeLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        match self.format {
+            LogFormat::Text => {
+                self.inner.log(record);
+                if log_file_active() {
+                    append(&format!(
+                        "[{} {:<5} {}] {}",
+                        jiff::Timestamp::now(),
+                        record.level(),
+                        record.target(),
+                        record.args()
+                    ));
+                }
+            }
+            LogFormat::Json => {
+                let line = serde_json::json!({
+                    "level": record.level().as_str(),
+                    "ts": jiff::Timestamp::now().to_string(),
+                    "target": record.target(),
+                    "msg": record.args().to_string(),
+                });
+                eprintln!("{line}");
+                append(&line.to_string());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Initializes the global logger from `RUST_LOG`; `color` overrides
+/// `RUST_LOG_STYLE` unless it is `auto`.
+///
+/// Fails if another logger was already installed, e.g. by an embedding application.
+pub fn init_logger(format: LogFormat, color: ColorChoice) -> Result<()> {
+    let mut builder = env_logger::Builder::from_default_env();
+    match color {
+        ColorChoice::Auto => {}
+        ColorChoice::Always => {
+            builder.write_style(env_logger::WriteStyle::Always);
+        }
+        ColorChoice::Never => {
+            builder.write_style(env_logger::WriteStyle::Never);
+        }
+    }
+    let inner = builder.build();
+    log::set_max_level(inner.filter());
+    LOG_FORMAT.get_or_init(|| format);
+    log::set_boxed_logger(Box::new(TeeLogger { inner, format }))
+        .context("A global logger is already initialized")
+}
+
+/// Emits a machine-readable build event such as `build_start`/`build_done`.
+///
+/// Events are only printed with `--log-format json`; `fields` must be a JSON object.
+pub fn emit_event(event: &str, fields: serde_json::Value) {
+    if LOG_FORMAT.get() != Some(&LogFormat::Json) {
+        return;
+    }
+    let mut line = serde_json::json!({
+        "event": event,
+        "ts": jiff::Timestamp::now().to_string(),
+    });
+    if let (Some(line), serde_json::Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    eprintln!("{line}");
+    append(&line.to_string());
+}
+
+/// Starts teeing log lines and command output into a timestamped file under `dir`.
+pub fn start_log_file(dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let timestamp = jiff::Timestamp::now().strftime("%Y%m%dT%H%M%SZ");
+    let path = dir.join(format!("build-{timestamp}.log"));
+    let file = File::create(&path)
+        .with_context(|| format!("Failed to create log file: {}", path.display()))?;
+    *LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner) = Some(file);
+    Ok(path)
+}
+
+pub fn log_file_active() -> bool {
+    LOG_FILE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Appends `text` to the log file, if one was started.
+pub(crate) fn append(text: &str) {
+    if let Some(file) = LOG_FILE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        let _ = file.write_all(text.as_bytes());
+        if !text.ends_with('\n') {
+            let _ = file.write_all(b"\n");
+        }
+    }
+}
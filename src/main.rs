@@ -2,16 +2,25 @@ use std::env;
 
 use clap::{CommandFactory, Parser};
 
-use opus_builder::{build, clean, cli};
+use opus_builder::utils::VERBOSE_TRACE;
+use opus_builder::{
+    build, clean, cli, config, fetch, flags, headers, info, inspect, logging, migrate, package, ui,
+    update, verify,
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = cli::Cli::parse();
     if env::var("RUST_LOG").is_err() {
-        unsafe { env::set_var("RUST_LOG", "info") };
+        let filter = if cli.verbose >= VERBOSE_TRACE {
+            "info,opus_builder=trace"
+        } else {
+            "info"
+        };
+        unsafe { env::set_var("RUST_LOG", filter) };
     }
-    env_logger::init();
-
-    let cli = cli::Cli::parse();
+    logging::init_logger(cli.log_format, cli.color)?;
+    ui::init(cli.color, cli.log_format);
 
     let Some(command) = cli.command else {
         cli::Cli::command().print_help()?;
@@ -21,18 +30,158 @@ async fn main() -> anyhow::Result<()> {
 
     match command {
         cli::Commands::Build(args) => {
-            build::run(build::BuildOptions {
+            let options = build::BuildOptions {
                 verbose: cli.verbose,
+                dry_run: cli.dry_run,
+                offline: cli.offline,
+                jobs: cli.jobs,
                 force: args.force,
-            })
-            .await?;
+                log_file: args.log_file,
+                keep_going: args.keep_going,
+                reclone: args.reclone,
+                reclone_user_repos: args.reclone_user_repos,
+                resume: args.resume,
+                trust_cache: false,
+                run_tests: args.run_tests,
+                smoke_test: args.smoke_test,
+                strict_config: cli.strict_config,
+                // CI systems set CI=true; treat "false"/"0" as unset
+                strict: args.strict
+                    || env::var("CI").is_ok_and(|ci| !matches!(ci.as_str(), "" | "0" | "false")),
+            };
+            let mut config =
+                config::load_or_create_config("build_config.toml".as_ref(), cli.strict_config)?;
+            if let Some(git_ref) = &args.git_ref {
+                let Some(library) = args.library.or(args.only) else {
+                    anyhow::bail!("--ref needs --library or --only to know which library to build");
+                };
+                if args.only.is_none() && !config.general.libraries.contains(&library) {
+                    anyhow::bail!("{library:?} is not in general.libraries, add it or use --only");
+                }
+                config.override_version(&library, git_ref)?;
+            }
+            for platform in &args.clean {
+                clean::clean_platform(&config, *platform, cli.dry_run)?;
+            }
+            let report = match (args.only, args.platform, args.arch) {
+                (Some(library), Some(platform), Some(arch)) => {
+                    build::run_single(config, library, platform, arch, options).await?
+                }
+                _ => build::run_with_config(config, options).await?,
+            };
+            println!(
+                "{} artifacts: {} built, {} reused from cache",
+                report.artifacts.len(),
+                report.built_count(),
+                report.cached_count()
+            );
         }
         cli::Commands::Clean(args) => {
-            let (clean_build_dir, clean_repos) = args.normalized();
+            let (clean_build_dir, clean_repos, clean_cache) = args.normalized();
             clean::run(clean::CleanOptions {
                 verbose: cli.verbose,
+                dry_run: cli.dry_run,
                 clean_build_dir,
                 clean_repos,
+                clean_cache,
+                reclone: args.reclone,
+                reclone_user_repos: args.reclone_user_repos,
+                strict_config: cli.strict_config,
+            })
+            .await?;
+        }
+        cli::Commands::Headers => {
+            headers::run(headers::HeadersOptions {
+                strict_config: cli.strict_config,
+            })
+            .await?;
+        }
+        cli::Commands::Verify => {
+            verify::run(verify::VerifyOptions {
+                verbose: cli.verbose,
+                strict_config: cli.strict_config,
+            })
+            .await?;
+        }
+        cli::Commands::Package(args) => {
+            package::run(package::PackageOptions {
+                output_dir: args.output,
+                format: args.format,
+                strict_config: cli.strict_config,
+            })
+            .await?;
+        }
+        cli::Commands::Inspect(args) => {
+            inspect::run(inspect::InspectOptions { path: args.path }).await?;
+        }
+        cli::Commands::Config(cli::ConfigCommands::Init(args)) => {
+            if args.stdout {
+                print!("{}", config::default_config_toml()?);
+            } else {
+                let path = std::path::Path::new("build_config.toml");
+                config::init_config(path, args.force)?;
+                println!("Wrote default config to {}", path.display());
+            }
+        }
+        cli::Commands::Config(cli::ConfigCommands::Migrate) => {
+            migrate::run(migrate::MigrateOptions {
+                dry_run: cli.dry_run,
+            })?;
+        }
+        cli::Commands::Info(args) => {
+            info::run(info::InfoOptions {
+                library: args.library,
+                strict_config: cli.strict_config,
+            })
+            .await?;
+        }
+        cli::Commands::Flags(args) => {
+            flags::run(flags::FlagsOptions {
+                library: args.library,
+                platform: args.platform,
+                arch: args.arch,
+                strict_config: cli.strict_config,
+            })
+            .await?;
+        }
+        cli::Commands::Fetch => {
+            fetch::run(fetch::FetchOptions {
+                verbose: cli.verbose,
+                dry_run: cli.dry_run,
+                offline: cli.offline,
+                strict_config: cli.strict_config,
+            })
+            .await?;
+        }
+        cli::Commands::Update => {
+            update::run(update::UpdateOptions {
+                dry_run: cli.dry_run,
+                offline: cli.offline,
+                strict_config: cli.strict_config,
+            })
+            .await?;
+        }
+        #[cfg(feature = "watch")]
+        cli::Commands::Watch(args) => {
+            opus_builder::watch::run(opus_builder::watch::WatchOptions {
+                build: build::BuildOptions {
+                    verbose: cli.verbose,
+                    dry_run: cli.dry_run,
+                    offline: cli.offline,
+                    jobs: cli.jobs,
+                    force: false,
+                    log_file: false,
+                    keep_going: args.keep_going,
+                    reclone: false,
+                    reclone_user_repos: false,
+                    resume: true,
+                    trust_cache: true,
+                    strict: false,
+                    run_tests: false,
+                    smoke_test: false,
+                    strict_config: cli.strict_config,
+                },
+                debounce: std::time::Duration::from_millis(args.debounce_ms),
             })
             .await?;
         }
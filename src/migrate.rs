@@ -0,0 +1,252 @@
+use crate::config::{self, CONFIG_VERSION, Config, LibrarySource};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MigrateOptions {
+    pub dry_run: bool,
+}
+
+/// Keys that were renamed, as `(old, new)`; the old ones are still accepted.
+const RENAMED_KEYS: [(&str, &str); 1] = [("lib_type", "lib_types")];
+
+/// `general.config_version` of a parsed config file; 0 when it predates the field.
+pub fn file_config_version(value: &toml::Value) -> u32 {
+    value
+        .get("general")
+        .and_then(|general| general.get("config_version"))
+        .and_then(toml::Value::as_integer)
+        .and_then(|version| u32::try_from(version).ok())
+        .unwrap_or(0)
+}
+
+/// The default config with every optional table and skipped-when-unset value
+/// set, so that serializing it yields every key the schema knows. Plain
+/// optional values need no entry here: serde_json keeps them as `null`.
+fn schema_sample() -> Config {
+    let mut config = Config::default();
+    for options in config.libraries.values_mut() {
+        options.source = Some(LibrarySource::default());
+        // only serialized when set
+        options.enable_http = true;
+    }
+    config
+}
+
+/// Dotted paths of the keys in `value` the config schema doesn't know, e.g.
+/// `build.make_concurent_jobs`. Renamed keys still accepted are not reported.
+pub fn unknown_keys(value: &toml::Value) -> Result<Vec<String>> {
+    let schema = serde_json::to_value(schema_sample())?;
+    let mut unknown = Vec::new();
+    collect_unknown_keys(value, &schema, "", &mut unknown);
+    Ok(unknown)
+}
+
+fn collect_unknown_keys(
+    value: &toml::Value,
+    schema: &serde_json::Value,
+    prefix: &str,
+    unknown: &mut Vec<String>,
+) {
+    let (Some(table), Some(schema_table)) = (value.as_table(), schema.as_object()) else {
+        return;
+    };
+    // an empty table in the schema is a free-form map
+    if schema_table.is_empty() {
+        return;
+    }
+    for (key, item) in table {
+        let path = format!("{prefix}{key}");
+        let known = RENAMED_KEYS
+            .iter()
+            .find(|(old, _)| old == key)
+            .map_or(key.as_str(), |(_, new)| new);
+        match schema_table.get(known) {
+            Some(schema_item) => {
+                collect_unknown_keys(item, schema_item, &format!("{path}."), unknown)
+            }
+            None => unknown.push(path),
+        }
+    }
+}
+
+/// Upgrade `document` to [`CONFIG_VERSION`] in place, returning a description
+/// of every change.
+pub fn migrate_document(document: &mut toml_edit::DocumentMut) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if let Some(platforms) = document
+        .get_mut("platforms")
+        .and_then(toml_edit::Item::as_table_like_mut)
+    {
+        for (platform, item) in platforms.iter_mut() {
+            let Some(platform_table) = item.as_table_like_mut() else {
+                continue;
+            };
+            for (old, new) in RENAMED_KEYS {
+                let Some(old_item) = platform_table.remove(old) else {
+                    continue;
+                };
+                if platform_table.contains_key(new) {
+                    changes.push(format!(
+                        "platforms.{platform}: removed {old}, {new} is already set"
+                    ));
+                    continue;
+                }
+                let new_item = match old_item.as_str() {
+                    Some(single) => {
+                        toml_edit::value(toml_edit::Array::from_iter([single.to_string()]))
+                    }
+                    None => old_item,
+                };
+                platform_table.insert(new, new_item);
+                changes.push(format!("platforms.{platform}: renamed {old} to {new}"));
+            }
+        }
+    }
+
+    // `-ffast-math` used to be part of the default cflags, now it's build.fast_math
+    if let Some(build) = document
+        .get_mut("build")
+        .and_then(toml_edit::Item::as_table_like_mut)
+        && let Some(cflags) = build.get("cflags").and_then(toml_edit::Item::as_str)
+        && cflags.split_whitespace().any(|flag| flag == "-ffast-math")
+    {
+        let cflags = cflags
+            .split_whitespace()
+            .filter(|flag| *flag != "-ffast-math")
+            .collect::<Vec<_>>()
+            .join(" ");
+        build.insert("cflags", toml_edit::value(cflags));
+        if !build.contains_key("fast_math") {
+            build.insert("fast_math", toml_edit::value(true));
+        }
+        changes.push("build: moved -ffast-math from cflags to fast_math".to_string());
+    }
+
+    // `build.lto` (config version 2) replaced the `-flto` of the old default ldflags
+    let file_version = document
+        .get("general")
+        .and_then(|general| general.get("config_version"))
+        .and_then(toml_edit::Item::as_integer)
+        .unwrap_or(0);
+    if file_version < 2
+        && let Some(build) = document
+            .entry("build")
+            .or_insert_with(|| toml_edit::Item::Table(toml_edit::Table::new()))
+            .as_table_like_mut()
+        && !build.contains_key("lto")
+    {
+        match build.get("ldflags").and_then(toml_edit::Item::as_str) {
+            Some(ldflags) => {
+                let lto = ldflags.split_whitespace().find_map(|flag| match flag {
+                    "-flto" | "-flto=full" => Some("full"),
+                    "-flto=thin" => Some("thin"),
+                    _ => None,
+                });
+                if let Some(lto) = lto {
+                    let ldflags = ldflags
+                        .split_whitespace()
+                        .filter(|flag| !flag.starts_with("-flto"))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    build.insert("ldflags", toml_edit::value(ldflags));
+                    build.insert("lto", toml_edit::value(lto));
+                    changes.push(format!(
+                        "build: moved -flto from ldflags to lto = \"{lto}\""
+                    ));
+                } else {
+                    // custom ldflags without -flto built without LTO, which is no longer the default
+                    build.insert("lto", toml_edit::value("off"));
+                    changes.push(
+                        "build: set lto = \"off\" as the configured ldflags have no -flto"
+                            .to_string(),
+                    );
+                }
+            }
+            None => {
+                build.insert("lto", toml_edit::value("full"));
+                changes.push(
+                    "build: set lto = \"full\" for the -flto of the old default ldflags; set it to \"off\" to build without LTO"
+                        .to_string(),
+                );
+            }
+        }
+    }
+
+    let general = document
+        .entry("general")
+        .or_insert_with(|| toml_edit::Item::Table(toml_edit::Table::new()));
+    if general
+        .get("config_version")
+        .and_then(toml_edit::Item::as_integer)
+        != Some(i64::from(CONFIG_VERSION))
+    {
+        general["config_version"] = toml_edit::value(i64::from(CONFIG_VERSION));
+        changes.push(format!("general: set config_version = {CONFIG_VERSION}"));
+    }
+
+    changes
+}
+
+/// Upgrade `path` to the current schema, keeping the original as `<path>.bak`.
+pub fn migrate_file(path: &Path, dry_run: bool) -> Result<Vec<String>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let value: toml::Value =
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+    for key in unknown_keys(&value)? {
+        log::warn!("Unknown key `{key}` in {}", path.display());
+    }
+
+    let mut document: toml_edit::DocumentMut = contents
+        .parse()
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let changes = migrate_document(&mut document);
+    if changes.is_empty() || dry_run {
+        return Ok(changes);
+    }
+
+    let migrated = document.to_string();
+    toml::from_str::<Config>(&migrated)
+        .with_context(|| format!("Migrated {} does not parse", path.display()))?;
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    fs::copy(path, &backup).with_context(|| format!("Failed to back up {}", path.display()))?;
+    fs::write(path, migrated).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(changes)
+}
+
+/// Migrate `build_config.toml` in the current directory.
+pub fn run(options: MigrateOptions) -> Result<()> {
+    let config_path = PathBuf::from("build_config.toml");
+    if !config_path.exists() {
+        anyhow::bail!(
+            "{} not found; run `opus-builder config init` to create one",
+            config_path.display()
+        );
+    }
+    let changes = migrate_file(&config_path, options.dry_run)?;
+    if changes.is_empty() {
+        println!(
+            "{} is already at config version {}",
+            config_path.display(),
+            config::CONFIG_VERSION
+        );
+        return Ok(());
+    }
+    for change in &changes {
+        println!("{change}");
+    }
+    if options.dry_run {
+        println!("Dry run, {} not modified", config_path.display());
+    } else {
+        println!(
+            "Migrated {} (original kept as {}.bak)",
+            config_path.display(),
+            config_path.display()
+        );
+    }
+    Ok(())
+}
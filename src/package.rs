@@ -0,0 +1,118 @@
+use crate::config::{self, Config, Library};
+use crate::post_build::collect_files;
+use anyhow::{Context, Result};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArchiveFormat {
+    #[default]
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn ext(&self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PackageOptions {
+    pub output_dir: PathBuf,
+    pub format: ArchiveFormat,
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
+}
+
+/// Archive `build/include` and `build/lib` into `opus-<version>-<platforms>.<ext>`.
+pub async fn run(options: PackageOptions) -> Result<PathBuf> {
+    let config_path = PathBuf::from("build_config.toml");
+    let config = config::load_or_create_config(&config_path, options.strict_config)?;
+    package(&config, &options)
+}
+
+pub fn package(config: &Config, options: &PackageOptions) -> Result<PathBuf> {
+    let build_dir = &config.paths.build_dir;
+    let sources: Vec<_> = ["include", "lib"]
+        .iter()
+        .map(|dir| build_dir.join(dir))
+        .filter(|dir| dir.exists())
+        .collect();
+    if sources.is_empty() {
+        anyhow::bail!(
+            "Nothing to package: {} has no include/ or lib/ directory, run `build` first",
+            build_dir.display()
+        );
+    }
+
+    let root_name = archive_root_name(config)?;
+    let platforms = config
+        .general
+        .platforms
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join("_");
+    fs::create_dir_all(&options.output_dir)?;
+    let archive_path = options.output_dir.join(format!(
+        "{}-{}.{}",
+        root_name,
+        platforms,
+        options.format.ext()
+    ));
+
+    log::info!("Packaging artifacts into {}", archive_path.display());
+    let file = File::create(&archive_path)
+        .with_context(|| format!("Failed to create {}", archive_path.display()))?;
+    match options.format {
+        ArchiveFormat::TarGz => {
+            let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            for source in &sources {
+                let file_name = source
+                    .file_name()
+                    .with_context(|| format!("{} has no file name", source.display()))?;
+                let name = Path::new(&root_name).join(file_name);
+                archive.append_dir_all(name, source)?;
+            }
+            archive.into_inner()?.finish()?;
+        }
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipWriter::new(file);
+            let file_options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated);
+            for source in &sources {
+                for path in collect_files(source)? {
+                    let relative = path.strip_prefix(build_dir).unwrap_or(&path);
+                    let name = Path::new(&root_name).join(relative);
+                    archive.start_file(name.to_string_lossy(), file_options)?;
+                    std::io::copy(&mut File::open(&path)?, &mut archive)?;
+                }
+            }
+            archive.finish()?;
+        }
+    }
+
+    println!("Packaged {}", archive_path.display());
+    Ok(archive_path)
+}
+
+/// Top-level folder name, derived from the libopus version when it is configured.
+fn archive_root_name(config: &Config) -> Result<String> {
+    let library = if config.libraries.contains_key(&Library::Libopus) {
+        Library::Libopus
+    } else {
+        *config
+            .general
+            .libraries
+            .first()
+            .context("No libraries configured")?
+    };
+    let version = config.get_library_version(&library)?;
+    Ok(format!("opus-{}", version.trim_start_matches('v')))
+}
@@ -1,9 +1,10 @@
 use crate::builder::AutotoolsToolchain;
-use crate::config::{Arch, Config, LibType, Library};
+use crate::config::{AndroidStl, Arch, Config, LibType, Library};
 use anyhow::{Context, Result};
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
 
 pub mod build {
     use super::*;
@@ -28,14 +29,241 @@ pub mod build {
         }
     }
 
+    /// Directory of `arch` under the NDK sysroot's `usr/lib`.
+    fn sysroot_triple(arch: Arch) -> Result<&'static str> {
+        match arch {
+            Arch::ArmeabiV7a => Ok("arm-linux-androideabi"),
+            _ => host_triple(arch),
+        }
+    }
+
+    /// The NDK's `toolchains/llvm/prebuilt/<dir>` for a host `os` (as in
+    /// `std::env::consts::OS`). Apple Silicon hosts use the `darwin-x86_64`
+    /// (universal) toolchain too.
+    pub fn host_platform_for(os: &str) -> Result<&'static str> {
+        match os {
+            "macos" => Ok("darwin-x86_64"),
+            "linux" => Ok("linux-x86_64"),
+            "windows" => Ok("windows-x86_64"),
+            _ => anyhow::bail!("Unsupported host OS for Android NDK: {os}"),
+        }
+    }
+
     fn host_platform() -> Result<&'static str> {
-        if cfg!(target_os = "macos") {
-            Ok("darwin-x86_64")
-        } else if cfg!(target_os = "linux") {
-            Ok("linux-x86_64")
-        } else {
-            anyhow::bail!("Unsupported host OS for Android NDK: {}", env::consts::OS)
+        host_platform_for(env::consts::OS)
+    }
+
+    /// Path of the NDK tool `name` in `bin`, with the `.exe` suffix the NDK
+    /// uses on Windows hosts.
+    fn tool(bin: &Path, name: &str) -> PathBuf {
+        bin.join(format!("{name}{}", env::consts::EXE_SUFFIX))
+    }
+
+    /// Oldest NDK with the unified `toolchains/llvm/prebuilt/<host>` layout.
+    pub const MIN_NDK_MAJOR: u32 = 19;
+
+    /// The `Pkg.Revision` (e.g. `28.2.13676358`) from the NDK's `source.properties`.
+    pub fn ndk_revision(ndk_path: &Path) -> Result<String> {
+        let properties = ndk_path.join("source.properties");
+        let contents = fs::read_to_string(&properties).with_context(|| {
+            format!(
+                "{} not found; is {} an Android NDK (r{MIN_NDK_MAJOR} or newer)?",
+                properties.display(),
+                ndk_path.display()
+            )
+        })?;
+        contents
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.trim() == "Pkg.Revision")
+            .map(|(_, value)| value.trim().to_string())
+            .with_context(|| format!("No Pkg.Revision in {}", properties.display()))
+    }
+
+    /// Check the configured NDK is recent enough and has the toolchain layout
+    /// the build expects, instead of failing deep inside `configure`.
+    pub fn validate_ndk(config: &Config) -> Result<()> {
+        let ndk_path = &config.platforms.android.ndk_path;
+        if !ndk_path.is_dir() {
+            anyhow::bail!(
+                "Android NDK not found at {}; set platforms.android.ndk_path, {} or ANDROID_NDK_HOME",
+                ndk_path.display(),
+                crate::config::ENV_ANDROID_NDK
+            );
+        }
+        let revision = ndk_revision(ndk_path)?;
+        let major: u32 = revision
+            .split('.')
+            .next()
+            .and_then(|major| major.parse().ok())
+            .with_context(|| format!("Unrecognized NDK revision {revision}"))?;
+        if major < MIN_NDK_MAJOR {
+            anyhow::bail!(
+                "Android NDK {revision} at {} is not supported, r{MIN_NDK_MAJOR} or newer is required",
+                ndk_path.display()
+            );
+        }
+        let bin = toolchain_bin(config)?;
+        if !tool(&bin, "clang").exists() {
+            anyhow::bail!(
+                "Android NDK {revision} at {} has no clang in {}; unsupported layout or host",
+                ndk_path.display(),
+                bin.display()
+            );
+        }
+        log::info!("Using Android NDK {revision} at {}", ndk_path.display());
+        Ok(())
+    }
+
+    fn toolchain_bin(config: &Config) -> Result<PathBuf> {
+        Ok(config
+            .platforms
+            .android
+            .ndk_path
+            .join("toolchains/llvm/prebuilt")
+            .join(host_platform()?)
+            .join("bin"))
+    }
+
+    fn sanitizer_arch(arch: Arch) -> Result<&'static str> {
+        match arch {
+            Arch::ArmeabiV7a => Ok("arm"),
+            Arch::Arm64V8a => Ok("aarch64"),
+            Arch::X86 => Ok("i686"),
+            Arch::X86_64 => Ok("x86_64"),
+            _ => anyhow::bail!("Unsupported architecture for Android: {:?}", arch),
+        }
+    }
+
+    /// Copy the NDK's sanitizer runtimes for `build.sanitizers` next to the
+    /// packaged library, since the app has to ship them in its `jniLibs`.
+    pub fn copy_sanitizer_runtimes(config: &Config, arch: Arch, dest_dir: &Path) -> Result<()> {
+        if config.build.sanitizers.is_empty() {
+            return Ok(());
+        }
+        let clang_lib = toolchain_bin(config)?.join("../lib/clang");
+        let runtime_arch = sanitizer_arch(arch)?;
+        for sanitizer in &config.build.sanitizers {
+            let runtime = match sanitizer.as_str() {
+                "address" => "asan",
+                "hwaddress" => "hwasan",
+                "undefined" => "ubsan_standalone",
+                _ => continue,
+            };
+            let pattern = clang_lib.join(format!(
+                "*/lib/linux/libclang_rt.{runtime}-{runtime_arch}-android.so"
+            ));
+            let Some(source) = glob::glob(&pattern.to_string_lossy())?.flatten().next() else {
+                log::warn!(
+                    "No {sanitizer} sanitizer runtime for {runtime_arch} found in {}",
+                    clang_lib.display()
+                );
+                continue;
+            };
+            let Some(file_name) = source.file_name() else {
+                continue;
+            };
+            log::info!("Copying sanitizer runtime {}", source.display());
+            fs::create_dir_all(dest_dir)?;
+            fs::copy(&source, dest_dir.join(file_name)).with_context(|| {
+                format!("Failed to copy sanitizer runtime {}", source.display())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Copy `libc++_shared.so` next to the packaged library when `android.stl`
+    /// is `c++_shared`, since the app has to ship it in its `jniLibs`.
+    pub fn copy_stl_runtime(config: &Config, arch: Arch, dest_dir: &Path) -> Result<()> {
+        if config.platforms.android.stl != AndroidStl::CxxShared {
+            return Ok(());
+        }
+        let source = toolchain_bin(config)?
+            .join("../sysroot/usr/lib")
+            .join(sysroot_triple(arch)?)
+            .join("libc++_shared.so");
+        log::info!("Copying C++ runtime {}", source.display());
+        fs::create_dir_all(dest_dir)?;
+        fs::copy(&source, dest_dir.join("libc++_shared.so"))
+            .with_context(|| format!("Failed to copy C++ runtime {}", source.display()))?;
+        Ok(())
+    }
+
+    /// CMake arguments selecting the NDK's own toolchain file for `arch`.
+    pub fn cmake_args(arch: Arch, config: &Config) -> Result<Vec<String>> {
+        let android_config = &config.platforms.android;
+        let toolchain_file = android_config
+            .ndk_path
+            .join("build/cmake/android.toolchain.cmake");
+        let stl = match android_config.stl {
+            AndroidStl::None => "none",
+            AndroidStl::CxxShared => "c++_shared",
+            AndroidStl::CxxStatic => "c++_static",
+        };
+        Ok(vec![
+            format!("-DCMAKE_TOOLCHAIN_FILE={}", toolchain_file.display()),
+            format!("-DANDROID_ABI={}", arch_dir_name(arch)?),
+            format!(
+                "-DANDROID_PLATFORM=android-{}",
+                android_config.native_api_level
+            ),
+            format!("-DANDROID_STL={stl}"),
+        ])
+    }
+
+    /// Page size Android 15+ devices may use; shared libraries must be aligned to it.
+    pub const PAGE_SIZE_16K: u64 = 16384;
+
+    fn page_size_ldflags() -> String {
+        format!("-Wl,-z,max-page-size={PAGE_SIZE_16K} -Wl,-z,common-page-size={PAGE_SIZE_16K}")
+    }
+
+    /// Check with `llvm-readelf` that every `LOAD` segment of `lib` is aligned
+    /// to at least 16KB.
+    pub async fn verify_page_alignment(config: &Config, lib: &Path) -> Result<()> {
+        let readelf = tool(&toolchain_bin(config)?, "llvm-readelf");
+        let output = Command::new(&readelf)
+            .arg("-lW")
+            .arg(lib)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run {}", readelf.display()))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} failed for {}: {}",
+                readelf.display(),
+                lib.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut load_segments = 0;
+        for line in stdout.lines().map(str::trim) {
+            if !line.starts_with("LOAD") {
+                continue;
+            }
+            load_segments += 1;
+            let align = line
+                .split_whitespace()
+                .last()
+                .and_then(|align| u64::from_str_radix(align.trim_start_matches("0x"), 16).ok())
+                .with_context(|| format!("Unexpected LOAD segment in {}: {line}", lib.display()))?;
+            if align < PAGE_SIZE_16K {
+                anyhow::bail!(
+                    "{} has a LOAD segment aligned to {align} bytes, Android 15+ requires {PAGE_SIZE_16K}; \
+                     make sure the linker honours -z max-page-size (NDK r23 or newer)",
+                    lib.display()
+                );
+            }
         }
+        if load_segments == 0 {
+            anyhow::bail!("No LOAD segments found in {}", lib.display());
+        }
+        Ok(())
+    }
+
+    pub fn llvm_ar(config: &Config) -> Result<PathBuf> {
+        Ok(tool(&toolchain_bin(config)?, "llvm-ar"))
     }
 
     pub fn prepare_toolchain(arch: Arch, config: &Config) -> Result<AutotoolsToolchain> {
@@ -43,44 +271,48 @@ pub mod build {
 
         let arch_dir = arch_dir_name(arch)?.to_string();
         let host = host_triple(arch)?.to_string();
-        let host_platform = host_platform()?;
-
-        let toolchain_bin = android_config
-            .ndk_path
-            .join("toolchains/llvm/prebuilt")
-            .join(host_platform)
-            .join("bin");
+        let toolchain_bin = toolchain_bin(config)?;
 
         let api_level = android_config.native_api_level;
         let cc_target = format!("{}{}", host, api_level);
 
-        let clang = toolchain_bin.join("clang");
-        let clangxx = toolchain_bin.join("clang++");
+        let clang = tool(&toolchain_bin, "clang");
+        let clangxx = tool(&toolchain_bin, "clang++");
 
         let cc = format!("{} --target={}", clang.display(), cc_target);
         let cxx = format!("{} --target={}", clangxx.display(), cc_target);
 
+        let mut base_ldflags = config.build.ldflags.clone();
+        if android_config.page_size_16k && android_config.lib_types.contains(&LibType::Shared) {
+            base_ldflags.push_str(&format!(" {}", page_size_ldflags()));
+        }
+        if let Some(stl_ldflags) = android_config.stl.ldflags()
+            && android_config.lib_types.contains(&LibType::Shared)
+        {
+            base_ldflags.push_str(&format!(" {stl_ldflags}"));
+        }
+
         let extra_env = vec![
             (
                 "AR".to_string(),
-                toolchain_bin.join("llvm-ar").display().to_string(),
+                tool(&toolchain_bin, "llvm-ar").display().to_string(),
             ),
             ("AS".to_string(), cc.clone()),
             (
                 "LD".to_string(),
-                toolchain_bin.join("ld").display().to_string(),
+                tool(&toolchain_bin, "ld").display().to_string(),
             ),
             (
                 "NM".to_string(),
-                toolchain_bin.join("llvm-nm").display().to_string(),
+                tool(&toolchain_bin, "llvm-nm").display().to_string(),
             ),
             (
                 "RANLIB".to_string(),
-                toolchain_bin.join("llvm-ranlib").display().to_string(),
+                tool(&toolchain_bin, "llvm-ranlib").display().to_string(),
             ),
             (
                 "STRIP".to_string(),
-                toolchain_bin.join("llvm-strip").display().to_string(),
+                tool(&toolchain_bin, "llvm-strip").display().to_string(),
             ),
         ];
 
@@ -92,17 +324,41 @@ pub mod build {
             cxx: Some(cxx),
             extra_env,
             base_cflags: config.build.cflags.clone(),
-            base_ldflags: config.build.ldflags.clone(),
+            base_ldflags,
         })
     }
 
+    /// Copy the shared library `lib` to `build/symbols/android/<abi>/`, the layout
+    /// Crashlytics' `unstrippedNativeLibsDir` and the Play Console expect. Packaged
+    /// libraries aren't stripped, so the copy only matters to keep symbols apart
+    /// from what gets shipped or stripped later by the app's own build.
+    pub fn copy_unstripped_symbols(build_dir: &Path, arch: Arch, lib: &Path) -> Result<()> {
+        let Some(file_name) = lib.file_name() else {
+            return Ok(());
+        };
+        if !lib.exists() {
+            return Ok(());
+        }
+        let dest_dir = build_dir
+            .join("symbols")
+            .join("android")
+            .join(arch_dir_name(arch)?);
+        fs::create_dir_all(&dest_dir)?;
+        let dest = dest_dir.join(file_name);
+        log::info!("Saving unstripped {} to {}", lib.display(), dest.display());
+        fs::copy(lib, &dest)
+            .with_context(|| format!("Failed to copy {} to {}", lib.display(), dest.display()))?;
+        Ok(())
+    }
+
     pub fn move_android_package(
         build_dir: &Path,
         library: &Library,
         version: &str,
         arch: Arch,
         lib_type: LibType,
-    ) -> Result<()> {
+        strict: bool,
+    ) -> Result<Option<PathBuf>> {
         let lib_name = library.name_with_lib_prefix();
         let repo_name = library.repo_name();
         let version = version.trim_start_matches('v');
@@ -123,28 +379,30 @@ pub mod build {
             .join(arch_dir)
             .join(format!("{}-{}", lib_name, version));
 
+        if !source_lib.exists() {
+            if strict {
+                anyhow::bail!("Library file not found: {}", source_lib.display());
+            }
+            log::warn!("Library file not found: {}, skipping", source_lib.display());
+            return Ok(None);
+        }
+
         fs::create_dir_all(&dest_dir)?;
         let dest_lib = dest_dir.join(&file_name);
-
-        if source_lib.exists() {
-            log::info!(
-                "Moving {} from {} to {}",
+        log::info!(
+            "Moving {} from {} to {}",
+            lib_name,
+            source_lib.display(),
+            dest_lib.display()
+        );
+        fs::copy(&source_lib, &dest_lib).with_context(|| {
+            format!(
+                "Failed to copy {} from {} to {}",
                 lib_name,
                 source_lib.display(),
                 dest_lib.display()
-            );
-            fs::copy(&source_lib, &dest_lib).with_context(|| {
-                format!(
-                    "Failed to copy {} from {} to {}",
-                    lib_name,
-                    source_lib.display(),
-                    dest_lib.display()
-                )
-            })?;
-        } else {
-            log::warn!("Library file not found: {}, skipping", source_lib.display());
-        }
-
-        Ok(())
+            )
+        })?;
+        Ok(Some(dest_lib))
     }
 }
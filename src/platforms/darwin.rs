@@ -1,8 +1,12 @@
 use crate::builder::AutotoolsToolchain;
 use crate::config::{Arch, Config, LibType, Library, Platform};
-use anyhow::Result;
+use crate::post_build::collect_files;
+use crate::utils::{self, CommandVerboseExt, ExecOptions};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
 pub mod build {
@@ -12,6 +16,7 @@ pub mod build {
         match arch {
             Arch::X86_64 => Ok("x86_64"),
             Arch::Arm64 => Ok("arm64"),
+            Arch::Arm64e => Ok("arm64e"),
             _ => anyhow::bail!("Architecture not supported for Darwin platform: {:?}", arch),
         }
     }
@@ -21,6 +26,11 @@ pub mod build {
             Platform::Macos => Ok("macos"),
             Platform::Ios => Ok("ios"),
             Platform::IosSim => Ok("ios-sim"),
+            Platform::MacCatalyst => Ok("maccatalyst"),
+            Platform::Tvos => Ok("tvos"),
+            Platform::TvosSim => Ok("tvos-sim"),
+            Platform::Visionos => Ok("visionos"),
+            Platform::VisionosSim => Ok("visionos-sim"),
             _ => anyhow::bail!("Platform not supported for Darwin: {:?}", platform),
         }
     }
@@ -30,24 +40,34 @@ pub mod build {
             Platform::Macos => Ok("macosx"),
             Platform::Ios => Ok("iphoneos"),
             Platform::IosSim => Ok("iphonesimulator"),
+            // Mac Catalyst builds against the macOS SDK with the `-macabi` target
+            Platform::MacCatalyst => Ok("macosx"),
+            Platform::Tvos => Ok("appletvos"),
+            Platform::TvosSim => Ok("appletvsimulator"),
+            Platform::Visionos => Ok("xros"),
+            Platform::VisionosSim => Ok("xrsimulator"),
             _ => anyhow::bail!("Platform not supported for Darwin: {:?}", platform),
         }
     }
 
-    fn min_ver_flag(platform: Platform, config: &Config) -> Result<String> {
+    fn min_version(platform: Platform, arch: Arch, config: &Config) -> Result<&str> {
+        config
+            .platforms
+            .darwin_config(&platform)
+            .map(|c| c.min_version.for_arch(arch))
+            .with_context(|| format!("Platform not supported for Darwin: {:?}", platform))
+    }
+
+    fn min_ver_flag(platform: Platform, arch: Arch, config: &Config) -> Result<String> {
+        let min_version = min_version(platform, arch, config)?;
         match platform {
-            Platform::Macos => Ok(format!(
-                "-mmacosx-version-min={}",
-                config.platforms.macos.min_version
-            )),
-            Platform::Ios => Ok(format!(
-                "-miphoneos-version-min={}",
-                config.platforms.ios.min_version
-            )),
-            Platform::IosSim => Ok(format!(
-                "-mios-simulator-version-min={}",
-                config.platforms.ios_sim.min_version
-            )),
+            Platform::Macos => Ok(format!("-mmacosx-version-min={min_version}")),
+            Platform::Ios => Ok(format!("-miphoneos-version-min={min_version}")),
+            Platform::IosSim => Ok(format!("-mios-simulator-version-min={min_version}")),
+            Platform::Tvos => Ok(format!("-mtvos-version-min={min_version}")),
+            Platform::TvosSim => Ok(format!("-mtvos-simulator-version-min={min_version}")),
+            // the deployment target is part of the `-target` triple
+            Platform::MacCatalyst | Platform::Visionos | Platform::VisionosSim => Ok(String::new()),
             _ => anyhow::bail!("Platform not supported for Darwin: {:?}", platform),
         }
     }
@@ -58,18 +78,37 @@ pub mod build {
     fn configure_host(arch: Arch) -> Result<&'static str> {
         match arch {
             Arch::Arm64 => Ok("arm64-apple-darwin"),
+            Arch::Arm64e => Ok("arm64e-apple-darwin"),
             Arch::X86_64 => Ok("x86_64-apple-darwin"),
             _ => anyhow::bail!("Architecture not supported for Darwin: {:?}", arch),
         }
     }
 
-    fn target(platform: Platform, arch: Arch) -> Result<&'static str> {
+    fn target(platform: Platform, arch: Arch, config: &Config) -> Result<String> {
         match (platform, arch) {
-            (Platform::Macos, Arch::Arm64) => Ok("arm64-apple-macos"),
-            (Platform::Macos, Arch::X86_64) => Ok("x86_64-apple-macos"),
-            (Platform::Ios, Arch::Arm64) => Ok("arm64-apple-ios"),
-            (Platform::IosSim, Arch::Arm64) => Ok("arm64-apple-ios-simulator"),
-            (Platform::IosSim, Arch::X86_64) => Ok("x86_64-apple-ios-simulator"),
+            (Platform::Macos, Arch::Arm64) => Ok("arm64-apple-macos".to_string()),
+            (Platform::Macos, Arch::X86_64) => Ok("x86_64-apple-macos".to_string()),
+            (Platform::Macos, Arch::Arm64e) => Ok("arm64e-apple-macos".to_string()),
+            (Platform::Ios, Arch::Arm64) => Ok("arm64-apple-ios".to_string()),
+            (Platform::Ios, Arch::Arm64e) => Ok("arm64e-apple-ios".to_string()),
+            (Platform::IosSim, Arch::Arm64) => Ok("arm64-apple-ios-simulator".to_string()),
+            (Platform::IosSim, Arch::X86_64) => Ok("x86_64-apple-ios-simulator".to_string()),
+            (Platform::Tvos, Arch::Arm64) => Ok("arm64-apple-tvos".to_string()),
+            (Platform::TvosSim, Arch::Arm64) => Ok("arm64-apple-tvos-simulator".to_string()),
+            (Platform::TvosSim, Arch::X86_64) => Ok("x86_64-apple-tvos-simulator".to_string()),
+            (Platform::Visionos, Arch::Arm64) => Ok(format!(
+                "arm64-apple-xros{}",
+                min_version(platform, arch, config)?
+            )),
+            (Platform::VisionosSim, Arch::Arm64) => Ok(format!(
+                "arm64-apple-xros{}-simulator",
+                min_version(platform, arch, config)?
+            )),
+            (Platform::MacCatalyst, Arch::Arm64 | Arch::X86_64) => Ok(format!(
+                "{}-apple-ios{}-macabi",
+                arch_dir_name(arch)?,
+                min_version(platform, arch, config)?
+            )),
             _ => anyhow::bail!(
                 "{} architecture not supported for platform: {:?}",
                 arch_dir_name(arch)?,
@@ -93,6 +132,18 @@ pub mod build {
             .to_string())
     }
 
+    /// Platforms whose SDK ships only with newer Xcodes; they are skipped when missing.
+    pub fn sdk_is_optional(platform: Platform) -> bool {
+        matches!(platform, Platform::Visionos | Platform::VisionosSim)
+    }
+
+    pub async fn sdk_available(platform: Platform) -> bool {
+        match sdk_name(platform) {
+            Ok(sdk_name) => xcrun_show_sdk_path(sdk_name).await.is_ok(),
+            Err(_) => false,
+        }
+    }
+
     async fn xcrun_find_tool(sdk_name: &str, tool: &str) -> Result<String> {
         let tool_output = Command::new("xcrun")
             .arg("--sdk")
@@ -107,23 +158,107 @@ pub mod build {
         Ok(String::from_utf8(tool_output.stdout)?.trim().to_string())
     }
 
+    /// Compile an empty translation unit to make sure clang can target `arch`,
+    /// instead of producing an empty slice that later breaks `lipo`.
+    async fn ensure_compiler_accepts_arch(
+        cc: &str,
+        target: &str,
+        arch: &str,
+        sdk_root: &str,
+    ) -> Result<()> {
+        let output = Command::new(cc)
+            .args(["-target", target, "-arch", arch, "-isysroot", sdk_root])
+            .args(["-x", "c", "-c", "/dev/null", "-o", "/dev/null"])
+            .output()
+            .await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "The toolchain does not support {arch} for target {target}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    const EMBED_BITCODE_FLAG: &str = "-fembed-bitcode";
+
+    /// Whether clang still accepts `-fembed-bitcode` (Xcode 14 removed bitcode).
+    async fn compiler_accepts_bitcode(cc: &str, target: &str, sdk_root: &str) -> bool {
+        Command::new(cc)
+            .args(["-target", target, "-isysroot", sdk_root, EMBED_BITCODE_FLAG])
+            .args(["-Werror", "-x", "c", "-c", "/dev/null", "-o", "/dev/null"])
+            .output()
+            .await
+            .is_ok_and(|output| output.status.success())
+    }
+
+    /// Whether `toolchain` was prepared with bitcode embedding enabled.
+    pub fn embeds_bitcode(toolchain: &AutotoolsToolchain) -> bool {
+        toolchain
+            .base_cflags
+            .split_whitespace()
+            .any(|flag| flag == EMBED_BITCODE_FLAG)
+    }
+
+    /// Fail unless `lib` contains an `__LLVM,__bitcode` section.
+    pub async fn verify_bitcode(lib: &Path) -> Result<()> {
+        let output = Command::new("otool").arg("-l").arg(lib).output().await?;
+        if !output.status.success() {
+            anyhow::bail!("otool -l failed for {}", lib.display());
+        }
+        if !String::from_utf8_lossy(&output.stdout).contains("sectname __bitcode") {
+            anyhow::bail!(
+                "{} has no __LLVM,__bitcode section although darwin.embed_bitcode is set",
+                lib.display()
+            );
+        }
+        Ok(())
+    }
+
     pub async fn prepare_toolchain(
         platform: Platform,
         arch: Arch,
         config: &Config,
     ) -> Result<AutotoolsToolchain> {
-        let platform_dir = platform_dir(platform)?.to_string();
         let sdk_name = sdk_name(platform)?;
-        let min_ver_flag = min_ver_flag(platform, config)?;
-
-        let arch_dir = arch_dir_name(arch)?.to_string();
-
-        let host = configure_host(arch)?.to_string();
-
         let sdk_root = xcrun_show_sdk_path(sdk_name).await?;
         let cc = xcrun_find_tool(sdk_name, "clang").await?;
-        let target = target(platform, arch)?;
+        let target = target(platform, arch, config)?;
+        if arch == Arch::Arm64e {
+            ensure_compiler_accepts_arch(&cc, &target, arch_dir_name(arch)?, &sdk_root).await?;
+        }
+
+        let mut toolchain = toolchain_for_sdk(platform, arch, config, &sdk_root, cc)?;
+        if config.darwin.embed_bitcode {
+            if compiler_accepts_bitcode(&toolchain.cc, &target, &sdk_root).await {
+                toolchain
+                    .base_cflags
+                    .push_str(&format!(" {EMBED_BITCODE_FLAG}"));
+                toolchain
+                    .base_ldflags
+                    .push_str(&format!(" {EMBED_BITCODE_FLAG}"));
+            } else {
+                log::warn!(
+                    "darwin.embed_bitcode is set but the toolchain rejects {EMBED_BITCODE_FLAG} (removed in Xcode 14); building {platform} ({arch}) without bitcode"
+                );
+            }
+        }
+        Ok(toolchain)
+    }
 
+    /// The toolchain for `platform`/`arch` given the SDK at `sdk_root` and the
+    /// compiler `cc`, which [`prepare_toolchain`] looks up with `xcrun`.
+    /// Bitcode, which needs probing the compiler, is left to the caller.
+    pub fn toolchain_for_sdk(
+        platform: Platform,
+        arch: Arch,
+        config: &Config,
+        sdk_root: &str,
+        cc: String,
+    ) -> Result<AutotoolsToolchain> {
+        let target = target(platform, arch, config)?;
+        let min_ver_flag = min_ver_flag(platform, arch, config)?;
+        let arch_dir = arch_dir_name(arch)?.to_string();
         let base_cflags = format!(
             "-target {target} -arch {arch_dir} -isysroot {sdk_root} {} {}",
             min_ver_flag, config.build.cflags
@@ -134,9 +269,9 @@ pub mod build {
         );
 
         Ok(AutotoolsToolchain {
-            platform_dir,
+            platform_dir: platform_dir(platform)?.to_string(),
             arch_dir,
-            host,
+            host: configure_host(arch)?.to_string(),
             cc,
             cxx: None,
             extra_env: Vec::new(),
@@ -145,12 +280,112 @@ pub mod build {
         })
     }
 
+    fn cmake_system_name(platform: Platform) -> Result<&'static str> {
+        match platform {
+            Platform::Macos | Platform::MacCatalyst => Ok("Darwin"),
+            Platform::Ios | Platform::IosSim => Ok("iOS"),
+            Platform::Tvos | Platform::TvosSim => Ok("tvOS"),
+            Platform::Visionos | Platform::VisionosSim => Ok("visionOS"),
+            _ => anyhow::bail!("Platform not supported for Darwin: {:?}", platform),
+        }
+    }
+
+    /// Write a CMake toolchain file for `platform`/`arch` into `dir` (Apple
+    /// platforms have none of their own) and return the arguments selecting it.
+    pub fn cmake_args(
+        platform: Platform,
+        arch: Arch,
+        config: &Config,
+        toolchain: &AutotoolsToolchain,
+        dir: &Path,
+        dry_run: bool,
+    ) -> Result<Vec<String>> {
+        let mut content = format!(
+            "set(CMAKE_SYSTEM_NAME {})\n\
+             set(CMAKE_OSX_SYSROOT {})\n\
+             set(CMAKE_OSX_ARCHITECTURES {})\n\
+             set(CMAKE_C_COMPILER \"{}\")\n\
+             set(CMAKE_TRY_COMPILE_TARGET_TYPE STATIC_LIBRARY)\n",
+            cmake_system_name(platform)?,
+            sdk_name(platform)?,
+            arch_dir_name(arch)?,
+            toolchain.cc
+        );
+        // Mac Catalyst's min_version is an iOS version, carried by `-target` in cflags
+        if platform != Platform::MacCatalyst {
+            content.push_str(&format!(
+                "set(CMAKE_OSX_DEPLOYMENT_TARGET {})\n",
+                min_version(platform, arch, config)?
+            ));
+        }
+
+        let toolchain_file = dir.join("apple.toolchain.cmake");
+        if !dry_run {
+            fs::write(&toolchain_file, content)?;
+        }
+        Ok(vec![format!(
+            "-DCMAKE_TOOLCHAIN_FILE={}",
+            toolchain_file.display()
+        )])
+    }
+
+    /// Returns the architectures contained in a Mach-O file, via `lipo -archs`.
+    pub async fn lipo_archs(path: &Path) -> Result<Vec<String>> {
+        let output = Command::new("lipo")
+            .arg("-archs")
+            .arg(path)
+            .output()
+            .await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "lipo -archs failed for {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8(output.stdout)?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Make sure every per-arch library contains exactly its expected architecture
+    /// and that no architecture would end up twice in the fat binary.
+    async fn validate_lipo_inputs(arch_libs: &[(&str, std::path::PathBuf)]) -> Result<()> {
+        let mut seen: Vec<(String, &Path)> = Vec::new();
+        for (expected_arch, lib) in arch_libs {
+            let actual = lipo_archs(lib).await?;
+            if !actual.iter().any(|a| a == expected_arch) {
+                anyhow::bail!(
+                    "{} was expected to contain {} but contains [{}]; remove it and rebuild with --force",
+                    lib.display(),
+                    expected_arch,
+                    actual.join(", ")
+                );
+            }
+            for arch in actual {
+                if let Some((_, other)) = seen.iter().find(|(a, _)| *a == arch) {
+                    anyhow::bail!(
+                        "Architecture {} appears in both {} and {}",
+                        arch,
+                        other.display(),
+                        lib.display()
+                    );
+                }
+                seen.push((arch, lib));
+            }
+        }
+        Ok(())
+    }
+
     pub async fn create_universal_binary(
         build_dir: &Path,
         platform: Platform,
         library: &Library,
         lib_type: LibType,
         archs: &[Arch],
+        strict: bool,
+        exec: ExecOptions,
     ) -> Result<()> {
         let universal_dir = build_dir
             .join(platform.to_string().to_lowercase())
@@ -160,7 +395,11 @@ pub mod build {
 
         let lib_name = library.name_with_lib_prefix();
         let file_name = format!("{}.{}", lib_name, lib_type.darwin_ext());
-        let lib_files: Vec<_> = archs
+        // canonical slice order regardless of how `archs` is listed in the config
+        let mut archs = archs.to_vec();
+        archs.sort();
+        archs.dedup();
+        let arch_libs: Vec<_> = archs
             .iter()
             .filter_map(|arch| {
                 let arch_dir = arch_dir_name(*arch).ok()?;
@@ -170,11 +409,42 @@ pub mod build {
                     .join(library.repo_name())
                     .join("lib")
                     .join(&file_name);
-                p.exists().then_some(p)
+                p.exists().then_some((arch_dir, p))
             })
             .collect();
+        if !exec.dry_run {
+            validate_lipo_inputs(&arch_libs).await?;
+        }
+        if !arch_libs.is_empty() && arch_libs.len() < archs.len() {
+            let missing: Vec<_> = archs
+                .iter()
+                .filter_map(|arch| arch_dir_name(*arch).ok())
+                .filter(|dir| arch_libs.iter().all(|(present, _)| present != dir))
+                .collect();
+            if strict {
+                anyhow::bail!(
+                    "Universal {} would lack {}: their libraries are missing",
+                    lib_name,
+                    missing.join(", ")
+                );
+            }
+            log::warn!(
+                "Universal {} lacks {}: their libraries are missing",
+                lib_name,
+                missing.join(", ")
+            );
+        }
+        // headers come from an arch that was built, not just the first configured one
+        let header_arch_dir = arch_libs.first().map(|(dir, _)| *dir);
+        let lib_files: Vec<_> = arch_libs.into_iter().map(|(_, p)| p).collect();
 
         if lib_files.is_empty() {
+            if strict {
+                anyhow::bail!(
+                    "No architecture-specific {} libraries found for the universal binary",
+                    lib_name
+                );
+            }
             log::warn!(
                 "Skipping universal binary for {} as no architecture-specific libraries were found.",
                 lib_name
@@ -184,31 +454,52 @@ pub mod build {
 
         let output_path = universal_dir.join("lib").join(&file_name);
 
-        log::info!(
-            "Creating universal binary for {} at {}",
-            lib_name,
-            output_path.display()
-        );
+        if let ([_], [single_lib]) = (archs.as_slice(), lib_files.as_slice()) {
+            // nothing to merge, so skip `lipo` and use the single-arch library as is
+            log::info!(
+                "Copying single-arch {} to {}",
+                lib_name,
+                output_path.display()
+            );
+            if exec.dry_run {
+                log::info!(
+                    "[dry-run] copy {} -> {}",
+                    single_lib.display(),
+                    output_path.display()
+                );
+            } else {
+                fs::copy(single_lib, &output_path).with_context(|| {
+                    format!(
+                        "Failed to copy {} to {}",
+                        single_lib.display(),
+                        output_path.display()
+                    )
+                })?;
+            }
+        } else {
+            log::info!(
+                "Creating universal binary for {} at {}",
+                lib_name,
+                output_path.display()
+            );
 
-        let mut cmd = Command::new("lipo");
-        cmd.arg("-create");
-        for lib_file in &lib_files {
-            cmd.arg(lib_file);
-        }
-        cmd.arg("-output");
-        cmd.arg(&output_path);
+            let mut cmd = Command::new("lipo");
+            cmd.arg("-create");
+            for lib_file in &lib_files {
+                cmd.arg(lib_file);
+            }
+            cmd.arg("-output");
+            cmd.arg(&output_path);
 
-        let status = cmd.status().await?;
-        if !status.success() {
-            anyhow::bail!("lipo failed for {}", lib_name);
+            cmd.run_with(exec)
+                .await
+                .with_context(|| format!("lipo failed for {}", lib_name))?;
         }
 
-        if let Some(first_arch) = archs.first().copied()
-            && let Ok(first_arch_dir) = arch_dir_name(first_arch)
-        {
+        if let Some(header_arch_dir) = header_arch_dir {
             let include_source = build_dir
                 .join(platform.to_string().to_lowercase())
-                .join(first_arch_dir)
+                .join(header_arch_dir)
                 .join(library.repo_name())
                 .join("include");
 
@@ -229,56 +520,193 @@ pub mod build {
         Ok(())
     }
 
+    /// Merge the universal static libs of `libraries` into one `<bundle_name>.a` using `libtool`.
+    ///
+    /// Returns `false` when none of the inputs exist for `platform`.
+    pub async fn merge_universal_static_libraries(
+        build_dir: &Path,
+        platform: Platform,
+        libraries: &[Library],
+        bundle_name: &str,
+        exec: ExecOptions,
+    ) -> Result<bool> {
+        let universal_root = build_dir.join(platform_dir(platform)?).join("universal");
+        let mut inputs: Vec<_> = libraries
+            .iter()
+            .map(|library| {
+                universal_root
+                    .join(library.repo_name())
+                    .join("lib")
+                    .join(format!("{}.a", library.name_with_lib_prefix()))
+            })
+            .filter(|p| p.exists())
+            .collect();
+        if inputs.is_empty() {
+            return Ok(false);
+        }
+        inputs.sort();
+
+        let bundle_dir = universal_root.join(bundle_name);
+        fs::create_dir_all(bundle_dir.join("lib"))?;
+        let output_path = bundle_dir.join("lib").join(format!("{bundle_name}.a"));
+
+        log::info!(
+            "Merging {} static libraries into {}",
+            inputs.len(),
+            output_path.display()
+        );
+        let mut cmd = Command::new("libtool");
+        cmd.arg("-static").arg("-o").arg(&output_path).args(&inputs);
+        cmd.run_with(exec)
+            .await
+            .with_context(|| format!("libtool failed for {}", bundle_name))?;
+
+        // start from a clean header tree so headers of removed libraries don't linger
+        let include_dest = bundle_dir.join("include");
+        if include_dest.exists() {
+            fs::remove_dir_all(&include_dest)?;
+        }
+        fs::create_dir_all(&include_dest)?;
+        let mut header_owners: HashMap<PathBuf, &str> = HashMap::new();
+        for library in libraries {
+            let include_source = universal_root.join(library.repo_name()).join("include");
+            for header in collect_files(&include_source)? {
+                let relative = header.strip_prefix(&include_source)?.to_path_buf();
+                let dest = include_dest.join(&relative);
+                if let Some(owner) = header_owners.get(&relative) {
+                    if fs::read(&dest)? != fs::read(&header)? {
+                        anyhow::bail!(
+                            "Header {} is provided by both {} and {} with different contents, cannot merge them into {}",
+                            relative.display(),
+                            owner,
+                            library.repo_name(),
+                            bundle_name
+                        );
+                    }
+                    continue;
+                }
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&header, &dest)?;
+                header_owners.insert(relative, library.repo_name());
+            }
+        }
+
+        Ok(true)
+    }
+
     pub async fn create_xcframework(
         build_dir: &Path,
         library: &Library,
         version: &str,
         lib_type: LibType,
+        name_suffix: &str,
+        options: XcframeworkOptions<'_>,
+        exec: ExecOptions,
     ) -> Result<()> {
-        let repo_name = library.repo_name();
         let lib_name = library.name_with_lib_prefix();
-
-        let final_dir = build_dir.join("lib").join("darwin");
-        fs::create_dir_all(&final_dir)?;
-
         let file_name = format!("{}.{}", lib_name, lib_type.darwin_ext());
         let xcframework_name = format!(
-            "{}-{}.xcframework",
+            "{}-{}{}.xcframework",
             lib_name,
-            version.trim_start_matches('v')
+            version.trim_start_matches('v'),
+            name_suffix
         );
-        let xcframework_path = final_dir.join(xcframework_name);
+        create_xcframework_from_universal(
+            build_dir,
+            library.repo_name(),
+            &file_name,
+            &xcframework_name,
+            options,
+            exec,
+        )
+        .await
+    }
 
-        if xcframework_path.exists() {
-            fs::remove_dir_all(&xcframework_path)?;
-        }
+    /// How an xcframework is (re)created.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct XcframeworkOptions<'a> {
+        /// Recreate it even if its inputs are unchanged.
+        pub force: bool,
+        /// Sign it with this identity after creation.
+        pub codesign_identity: Option<&'a str>,
+    }
 
-        let mut cmd = Command::new("xcodebuild");
-        cmd.arg("-create-xcframework");
+    /// Where the input hashes of created xcframeworks are kept.
+    const XCFRAMEWORK_CACHE_DIR: &str = "xcframework-cache";
+
+    /// Create `build/lib/darwin/<xcframework_name>` from every
+    /// `build/<platform>/universal/<universal_name>` slice that exists. Skipped
+    /// when the slices are unchanged since the last run, unless `force` is set,
+    /// and signed afterwards when a codesign identity is given.
+    pub async fn create_xcframework_from_universal(
+        build_dir: &Path,
+        universal_name: &str,
+        file_name: &str,
+        xcframework_name: &str,
+        options: XcframeworkOptions<'_>,
+        exec: ExecOptions,
+    ) -> Result<()> {
+        let final_dir = build_dir.join("lib").join("darwin");
+        fs::create_dir_all(&final_dir)?;
+        let xcframework_path = final_dir.join(xcframework_name);
 
-        let macos_universal_path = build_dir.join("macos").join("universal").join(repo_name);
-        let ios_universal_path = build_dir.join("ios").join("universal").join(repo_name);
-        let ios_sim_universal_path = build_dir.join("ios-sim").join("universal").join(repo_name);
+        let mut slices = Vec::new();
+        for platform in [
+            Platform::Macos,
+            Platform::Ios,
+            Platform::IosSim,
+            Platform::MacCatalyst,
+            Platform::Tvos,
+            Platform::TvosSim,
+            Platform::Visionos,
+            Platform::VisionosSim,
+        ] {
+            let universal_path = build_dir
+                .join(platform_dir(platform)?)
+                .join("universal")
+                .join(universal_name);
+            if universal_path.exists() {
+                slices.push((
+                    universal_path.join("lib").join(file_name),
+                    universal_path.join("include"),
+                ));
+            }
+        }
 
-        if macos_universal_path.exists() {
-            cmd.arg("-library");
-            cmd.arg(macos_universal_path.join("lib").join(&file_name));
-            cmd.arg("-headers");
-            cmd.arg(macos_universal_path.join("include"));
+        let marker = build_dir
+            .join(XCFRAMEWORK_CACHE_DIR)
+            .join(format!("{xcframework_name}.sha256"));
+        let inputs_hash = if exec.dry_run {
+            None
+        } else {
+            Some(xcframework_inputs_hash(
+                build_dir,
+                &slices,
+                options.codesign_identity,
+            )?)
+        };
+        if !options.force
+            && xcframework_path.exists()
+            && inputs_hash.is_some()
+            && fs::read_to_string(&marker).ok() == inputs_hash
+        {
+            log::info!(
+                "{} is up to date, skipping xcodebuild",
+                xcframework_path.display()
+            );
+            return Ok(());
         }
 
-        if ios_universal_path.exists() {
-            cmd.arg("-library");
-            cmd.arg(ios_universal_path.join("lib").join(&file_name));
-            cmd.arg("-headers");
-            cmd.arg(ios_universal_path.join("include"));
+        if xcframework_path.exists() && !exec.dry_run {
+            fs::remove_dir_all(&xcframework_path)?;
         }
 
-        if ios_sim_universal_path.exists() {
-            cmd.arg("-library");
-            cmd.arg(ios_sim_universal_path.join("lib").join(&file_name));
-            cmd.arg("-headers");
-            cmd.arg(ios_sim_universal_path.join("include"));
+        let mut cmd = Command::new("xcodebuild");
+        cmd.arg("-create-xcframework");
+        for (lib, headers) in &slices {
+            cmd.arg("-library").arg(lib).arg("-headers").arg(headers);
         }
 
         cmd.arg("-output");
@@ -286,15 +714,141 @@ pub mod build {
 
         log::info!(
             "Creating xcframework for {} at {}",
-            repo_name,
+            universal_name,
             xcframework_path.display()
         );
 
-        let status = cmd.status().await?;
-        if !status.success() {
-            anyhow::bail!("xcodebuild failed for {}", repo_name);
+        cmd.run_with(exec)
+            .await
+            .with_context(|| format!("xcodebuild failed for {}", universal_name))?;
+
+        if !exec.dry_run {
+            verify_xcframework(&xcframework_path).await?;
+        }
+        if let Some(identity) = options.codesign_identity {
+            codesign_xcframework(&xcframework_path, identity, exec).await?;
+        }
+        if let Some(hash) = inputs_hash {
+            fs::create_dir_all(build_dir.join(XCFRAMEWORK_CACHE_DIR))?;
+            fs::write(&marker, hash)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sign `xcframework_path` with `identity` and verify the signature.
+    pub async fn codesign_xcframework(
+        xcframework_path: &Path,
+        identity: &str,
+        exec: ExecOptions,
+    ) -> Result<()> {
+        log::info!(
+            "Signing {} with identity \"{}\"",
+            xcframework_path.display(),
+            identity
+        );
+        let mut cmd = Command::new("codesign");
+        cmd.arg("--sign")
+            .arg(identity)
+            .arg("--timestamp")
+            .arg(xcframework_path);
+        cmd.run_with(exec).await.with_context(|| {
+            format!(
+                "codesign failed for {} with identity \"{}\". Check that the identity is \
+                 listed by `security find-identity -v -p codesigning`, that its keychain is \
+                 unlocked, and that the timestamp server is reachable",
+                xcframework_path.display(),
+                identity
+            )
+        })?;
+
+        let mut cmd = Command::new("codesign");
+        cmd.arg("--verify").arg("--verbose").arg(xcframework_path);
+        cmd.run_with(exec).await.with_context(|| {
+            format!(
+                "codesign could not verify the signature of {}",
+                xcframework_path.display()
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Hash of every slice's library and headers (and the signing identity),
+    /// to skip an unchanged xcframework.
+    fn xcframework_inputs_hash(
+        build_dir: &Path,
+        slices: &[(PathBuf, PathBuf)],
+        codesign_identity: Option<&str>,
+    ) -> Result<String> {
+        let mut inputs = String::new();
+        if let Some(identity) = codesign_identity {
+            inputs.push_str(&format!("codesign  {identity}\n"));
         }
+        for (lib, headers) in slices {
+            for file in std::iter::once(lib.clone()).chain(collect_files(headers)?) {
+                let relative = file.strip_prefix(build_dir).unwrap_or(&file);
+                inputs.push_str(&format!(
+                    "{}  {}\n",
+                    utils::sha256_file(&file)?,
+                    relative.display()
+                ));
+            }
+        }
+        Ok(Sha256::digest(inputs.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect())
+    }
 
+    /// Check that every library declared in the xcframework's `Info.plist`
+    /// contains exactly the architectures it claims to support.
+    pub async fn verify_xcframework(xcframework_path: &Path) -> Result<()> {
+        let plist_path = xcframework_path.join("Info.plist");
+        let output = Command::new("plutil")
+            .args(["-convert", "json", "-o", "-"])
+            .arg(&plist_path)
+            .output()
+            .await?;
+        if !output.status.success() {
+            anyhow::bail!("Failed to read {}", plist_path.display());
+        }
+        let plist: serde_json::Value = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("Failed to parse {}", plist_path.display()))?;
+
+        let libraries = plist["AvailableLibraries"]
+            .as_array()
+            .with_context(|| format!("No AvailableLibraries in {}", plist_path.display()))?;
+        for library in libraries {
+            let identifier = library["LibraryIdentifier"].as_str().unwrap_or_default();
+            let library_path = library["LibraryPath"].as_str().unwrap_or_default();
+            let mut declared: Vec<String> = library["SupportedArchitectures"]
+                .as_array()
+                .map(|archs| {
+                    archs
+                        .iter()
+                        .filter_map(|a| a.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            let binary = xcframework_path.join(identifier).join(library_path);
+            let mut actual = lipo_archs(&binary).await?;
+            declared.sort();
+            actual.sort();
+            if declared != actual {
+                anyhow::bail!(
+                    "xcframework slice {} declares [{}] but {} contains [{}]",
+                    identifier,
+                    declared.join(", "),
+                    binary.display(),
+                    actual.join(", ")
+                );
+            }
+            log::debug!(
+                "Verified xcframework slice {identifier}: [{}]",
+                actual.join(", ")
+            );
+        }
         Ok(())
     }
 }
@@ -1,6 +1,7 @@
 use crate::builder::AutotoolsToolchain;
 use crate::config::{Arch, Config, LibType, Library};
 use anyhow::{Context, Result};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -61,6 +62,53 @@ pub mod build {
         Ok(sysroot)
     }
 
+    /// Check that the configured SDK ships a sysroot lib dir
+    /// (`native/sysroot/usr/lib/<target>`) for every configured arch, instead
+    /// of failing during `configure`.
+    pub fn validate_archs(config: &Config) -> Result<()> {
+        let harmony_config = &config.platforms.harmony;
+        let lib_root = sysroot(&harmony_config.ndk_path)?.join("usr/lib");
+        let available: Vec<_> = [Arch::ArmeabiV7a, Arch::Arm64V8a, Arch::X86_64]
+            .into_iter()
+            .filter(|arch| clang_target(*arch).is_ok_and(|target| lib_root.join(target).is_dir()))
+            .collect();
+        for arch in &harmony_config.archs {
+            let target = clang_target(*arch)?;
+            if !available.contains(arch) {
+                anyhow::bail!(
+                    "The Harmony SDK at {} has no {target} sysroot ({} is missing), so {arch} cannot be built; \
+                     remove it from platforms.harmony.archs (available: {})",
+                    harmony_config.ndk_path.display(),
+                    lib_root.join(target).display(),
+                    available
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// CMake arguments selecting the OHOS SDK's toolchain file for `arch`.
+    pub fn cmake_args(arch: Arch, config: &Config) -> Result<Vec<String>> {
+        let toolchain_file = config
+            .platforms
+            .harmony
+            .ndk_path
+            .join("native/build/cmake/ohos.toolchain.cmake");
+        Ok(vec![
+            format!("-DCMAKE_TOOLCHAIN_FILE={}", toolchain_file.display()),
+            format!("-DOHOS_ARCH={}", arch_dir_name(arch)?),
+            "-DOHOS_PLATFORM=OHOS".to_string(),
+        ])
+    }
+
+    pub fn llvm_ar(config: &Config) -> Result<PathBuf> {
+        Ok(toolchain_bin(&config.platforms.harmony.ndk_path)?.join("llvm-ar"))
+    }
+
     pub fn prepare_toolchain(arch: Arch, config: &Config) -> Result<AutotoolsToolchain> {
         let harmony_config = &config.platforms.harmony;
 
@@ -121,13 +169,73 @@ pub mod build {
         })
     }
 
+    /// File listing every packaged Harmony library, relative to `build/lib/harmony`.
+    pub const PACKAGE_INDEX: &str = "index.json";
+
+    /// Write `build/lib/harmony/index.json` mapping each library to its version
+    /// and, per arch and lib type, the packaged file. ELF archives can't hold
+    /// several architectures like Mach-O universal binaries, so this index is
+    /// the Harmony counterpart of Darwin's `universal` directory.
+    pub fn write_package_index(config: &Config) -> Result<PathBuf> {
+        let harmony_dir = config.paths.build_dir.join("lib").join("harmony");
+        let mut libraries = serde_json::Map::new();
+        for library in &config.general.libraries {
+            let version = config.get_library_version(library)?;
+            let mut archs = BTreeMap::new();
+            for arch in &config.platforms.harmony.archs {
+                let mut files = BTreeMap::new();
+                for lib_type in &config.platforms.harmony.lib_types {
+                    let relative = Path::new(arch_dir_name(*arch)?)
+                        .join(format!(
+                            "{}-{}",
+                            library.name_with_lib_prefix(),
+                            version.trim_start_matches('v')
+                        ))
+                        .join(format!(
+                            "{}.{}",
+                            library.name_with_lib_prefix(),
+                            lib_type.linux_ext()
+                        ));
+                    if harmony_dir.join(&relative).exists() {
+                        files.insert(
+                            lib_type.as_str(),
+                            relative.to_string_lossy().replace('\\', "/"),
+                        );
+                    }
+                }
+                if !files.is_empty() {
+                    archs.insert(arch_dir_name(*arch)?, files);
+                }
+            }
+            if archs.is_empty() {
+                continue;
+            }
+            libraries.insert(
+                library.name_with_lib_prefix(),
+                serde_json::json!({
+                    "version": version.trim_start_matches('v'),
+                    "archs": archs,
+                }),
+            );
+        }
+
+        fs::create_dir_all(&harmony_dir)?;
+        let path = harmony_dir.join(PACKAGE_INDEX);
+        let json = serde_json::to_string_pretty(&serde_json::json!({ "libraries": libraries }))?;
+        fs::write(&path, json + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Wrote Harmony package index to {}", path.display());
+        Ok(path)
+    }
+
     pub fn move_harmony_package(
         build_dir: &Path,
         library: &Library,
         version: &str,
         arch: Arch,
         lib_type: LibType,
-    ) -> Result<()> {
+        strict: bool,
+    ) -> Result<Option<PathBuf>> {
         let lib_name = library.name_with_lib_prefix();
         let repo_name = library.repo_name();
         let version = version.trim_start_matches('v');
@@ -148,28 +256,30 @@ pub mod build {
             .join(arch_dir)
             .join(format!("{}-{}", lib_name, version));
 
+        if !source_lib.exists() {
+            if strict {
+                anyhow::bail!("Library file not found: {}", source_lib.display());
+            }
+            log::warn!("Library file not found: {}, skipping", source_lib.display());
+            return Ok(None);
+        }
+
         fs::create_dir_all(&dest_dir)?;
         let dest_lib = dest_dir.join(&file_name);
-
-        if source_lib.exists() {
-            log::info!(
-                "Moving {} from {} to {}",
+        log::info!(
+            "Moving {} from {} to {}",
+            lib_name,
+            source_lib.display(),
+            dest_lib.display()
+        );
+        fs::copy(&source_lib, &dest_lib).with_context(|| {
+            format!(
+                "Failed to copy {} from {} to {}",
                 lib_name,
                 source_lib.display(),
                 dest_lib.display()
-            );
-            fs::copy(&source_lib, &dest_lib).with_context(|| {
-                format!(
-                    "Failed to copy {} from {} to {}",
-                    lib_name,
-                    source_lib.display(),
-                    dest_lib.display()
-                )
-            })?;
-        } else {
-            log::warn!("Library file not found: {}, skipping", source_lib.display());
-        }
-
-        Ok(())
+            )
+        })?;
+        Ok(Some(dest_lib))
     }
 }
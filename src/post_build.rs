@@ -1,34 +1,52 @@
-use crate::config::{Config, Platform};
-use anyhow::Result;
+use crate::config::{Config, LibType, Library, Platform};
+use crate::platforms::darwin::build::XcframeworkOptions;
+use crate::repo::Repo;
+use crate::timings::BuildTimings;
+use crate::utils::CommandVerboseExt;
+use crate::utils::{self, ExecOptions};
+use anyhow::{Context, Result};
+use serde::Serialize;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Copy public headers into `build/include`, returning how many libraries were copied.
+///
+/// With `general.per_platform_headers`, each platform's headers go to
+/// `build/include/<platform>/` instead of one shared copy.
+pub fn copy_headers_from_build_artifacts(config: &Config) -> Result<usize> {
+    let per_platform = config.general.per_platform_headers;
+    let include_root = config.paths.build_dir.join("include");
+    let mut copied = 0;
 
-pub fn copy_headers_from_build_artifacts(config: &Config) -> Result<()> {
     for library in &config.general.libraries {
         let lib_name = library.name_with_lib_prefix();
         let repo_name = library.repo_name();
-
-        // copy headers from first available platform since headers are same
-        let mut include_source = None;
+        let include_dir = library.include_dir();
+        let mut found = false;
 
         for platform in &config.general.platforms {
             let platform_str = platform.to_string().to_lowercase();
 
-            if let Some(path) = include_source_for_platform(
+            let Some(include_source) = include_source_for_platform(
                 config,
                 *platform,
                 &platform_str,
                 repo_name,
-                &library.include_dir(),
-            ) {
-                include_source = Some(path);
-                break;
-            }
-        }
+                &include_dir,
+            ) else {
+                continue;
+            };
 
-        if let Some(include_source) = include_source {
-            let include_dest = config.paths.build_dir.join("include").join(lib_name);
-            fs::create_dir_all(&include_dest)?;
+            // keep the `opus/` or `ogg/` prefix so `#include <opus/opus.h>` resolves against the include root
+            let include_dest = match include_dir.file_name() {
+                Some(subdir) if per_platform => include_root.join(&platform_str).join(subdir),
+                _ => config.paths.build_dir.join(&include_dir),
+            };
 
             log::info!(
                 "Copying headers from {} to {}",
@@ -36,20 +54,17 @@ pub fn copy_headers_from_build_artifacts(config: &Config) -> Result<()> {
                 include_dest.display()
             );
 
-            // copy header files only
-            for entry in fs::read_dir(&include_source)? {
-                let entry = entry?;
-                let path = entry.path();
-
-                if path.extension().is_some_and(|ext| ext == "h") && path.is_file() {
-                    let dest_file = include_dest.join(path.file_name().unwrap());
-                    fs::copy(&path, &dest_file)?;
-                    log::debug!(
-                        "Copied header: {}",
-                        path.file_name().unwrap().to_string_lossy()
-                    );
-                }
+            copy_headers_recursive(&include_source, &include_dest)?;
+            found = true;
+
+            // shared headers are copied from the first available platform only
+            if !per_platform {
+                break;
             }
+        }
+
+        if found {
+            copied += 1;
         } else {
             log::warn!(
                 "No include directory found in build artifacts for library: {}",
@@ -58,6 +73,25 @@ pub fn copy_headers_from_build_artifacts(config: &Config) -> Result<()> {
         }
     }
 
+    Ok(copied)
+}
+
+/// Copy `.h` files from `src` to `dest`, preserving subdirectories.
+fn copy_headers_recursive(src: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+
+        if path.is_dir() {
+            copy_headers_recursive(&path, &dest.join(file_name))?;
+        } else if path.extension().is_some_and(|ext| ext == "h") {
+            fs::copy(&path, dest.join(file_name))?;
+            log::debug!("Copied header: {}", file_name.to_string_lossy());
+        }
+    }
     Ok(())
 }
 
@@ -89,7 +123,14 @@ fn include_source_for_platform(
                 .join(include_dir);
             path.exists().then_some(path)
         }
-        Platform::Macos | Platform::Ios | Platform::IosSim => {
+        Platform::Macos
+        | Platform::Ios
+        | Platform::IosSim
+        | Platform::MacCatalyst
+        | Platform::Tvos
+        | Platform::TvosSim
+        | Platform::Visionos
+        | Platform::VisionosSim => {
             let arch = config
                 .platforms
                 .get_archs_for_platform(&platform)
@@ -108,8 +149,33 @@ fn include_source_for_platform(
     }
 }
 
+/// `xcodebuild -create-xcframework` runs allowed at the same time.
+const MAX_PARALLEL_XCFRAMEWORKS: usize = 4;
+
+/// The xcframeworks built per library for the Apple `lib_types`, as
+/// `(lib_type, name suffix)`: the static (or only) variant keeps the plain
+/// name, a shared one next to it gets `-shared`.
+pub fn xcframework_variants(lib_types: &[LibType]) -> Vec<(LibType, &'static str)> {
+    lib_types
+        .iter()
+        .map(|lib_type| {
+            let suffix = if lib_types.len() > 1 && *lib_type == LibType::Shared {
+                "-shared"
+            } else {
+                ""
+            };
+            (*lib_type, suffix)
+        })
+        .collect()
+}
+
 /// Create an xcframework if any Apple platform was built.
-pub async fn create_xcframework_if_needed(config: &Config) -> Result<()> {
+pub async fn create_xcframework_if_needed(
+    config: &Config,
+    force: bool,
+    exec: ExecOptions,
+    timings: &mut BuildTimings,
+) -> Result<()> {
     let has_apple_platform = config.general.platforms.iter().any(|p| p.is_darwin());
 
     if !has_apple_platform {
@@ -117,18 +183,390 @@ pub async fn create_xcframework_if_needed(config: &Config) -> Result<()> {
         return Ok(());
     }
 
+    // created up front so the concurrent tasks don't race on it
+    fs::create_dir_all(config.paths.build_dir.join("lib").join("darwin"))?;
+
+    let permits = Arc::new(Semaphore::new(MAX_PARALLEL_XCFRAMEWORKS));
+    let variants =
+        xcframework_variants(config.platforms.get_lib_types_for_platform(&Platform::Ios));
+    let mut tasks = JoinSet::new();
+    for library in &config.general.libraries {
+        let library = *library;
+        let version = config.get_library_version(&library)?.to_string();
+        let build_dir = config.paths.build_dir.clone();
+        let variants = variants.clone();
+        let codesign_identity = config.darwin.codesign_identity.clone();
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire().await?;
+            let started = Instant::now();
+            for (lib_type, name_suffix) in &variants {
+                crate::platforms::darwin::build::create_xcframework(
+                    &build_dir,
+                    &library,
+                    &version,
+                    *lib_type,
+                    name_suffix,
+                    XcframeworkOptions {
+                        force,
+                        codesign_identity: codesign_identity.as_deref(),
+                    },
+                    exec,
+                )
+                .await?;
+            }
+            anyhow::Ok((library, started.elapsed()))
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        let (library, elapsed) = result??;
+        timings.record("xcframework", library, "darwin", "-", elapsed);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub libraries: Vec<ManifestLibrary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestLibrary {
+    pub name: String,
+    pub version: String,
+    pub commit: Option<String>,
+    pub source_url: String,
+    pub platforms: Vec<ManifestPlatform>,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestPlatform {
+    pub platform: String,
+    pub archs: Vec<String>,
+    pub lib_types: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Where `library`'s shipped artifacts land for the configured platforms: one
+/// directory per Android/HarmonyOS ABI and the xcframeworks for Apple platforms.
+pub fn output_dirs(config: &Config, library: &Library) -> Result<Vec<PathBuf>> {
+    let build_dir = &config.paths.build_dir;
+    let version = config.get_library_version(library)?;
+    let output_name = format!(
+        "{}-{}",
+        library.name_with_lib_prefix(),
+        version.trim_start_matches('v')
+    );
+
+    let mut output_dirs = Vec::new();
+    for platform in &config.general.platforms {
+        match platform {
+            Platform::Android | Platform::Harmony => {
+                for arch in config.platforms.get_archs_for_platform(platform) {
+                    let arch_dir = match platform {
+                        Platform::Android => {
+                            crate::platforms::android::build::arch_dir_name(*arch)?
+                        }
+                        _ => crate::platforms::harmony::build::arch_dir_name(*arch)?,
+                    };
+                    output_dirs.push(
+                        build_dir
+                            .join("lib")
+                            .join(platform.to_string())
+                            .join(arch_dir)
+                            .join(&output_name),
+                    );
+                }
+            }
+            Platform::Macos
+            | Platform::Ios
+            | Platform::IosSim
+            | Platform::MacCatalyst
+            | Platform::Tvos
+            | Platform::TvosSim
+            | Platform::Visionos
+            | Platform::VisionosSim => {}
+        }
+    }
+    if config.general.platforms.iter().any(|p| p.is_darwin()) {
+        let lib_types = config.platforms.get_lib_types_for_platform(&Platform::Ios);
+        for (_, suffix) in xcframework_variants(lib_types) {
+            output_dirs.push(
+                build_dir
+                    .join("lib")
+                    .join("darwin")
+                    .join(format!("{output_name}{suffix}.xcframework")),
+            );
+        }
+    }
+    Ok(output_dirs)
+}
+
+/// Write `build/manifest.json` describing every shipped library artifact.
+pub async fn write_manifest(config: &Config, repos: &[Repo]) -> Result<PathBuf> {
+    let build_dir = &config.paths.build_dir;
+    let mut libraries = Vec::new();
+
     for library in &config.general.libraries {
+        let lib_name = library.name_with_lib_prefix();
         let version = config.get_library_version(library)?;
+        let repo = repos.iter().find(|r| r.name == library.repo_name());
+
+        let commit = match repo {
+            Some(repo) => repo.head_commit().await.ok(),
+            None => None,
+        };
+
+        let platforms = config
+            .general
+            .platforms
+            .iter()
+            .map(|platform| ManifestPlatform {
+                platform: platform.to_string(),
+                archs: config
+                    .platforms
+                    .get_archs_for_platform(platform)
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect(),
+                lib_types: config
+                    .platforms
+                    .get_lib_types_for_platform(platform)
+                    .iter()
+                    .map(|t| t.as_str().to_string())
+                    .collect(),
+            })
+            .collect();
+        let output_dirs = output_dirs(config, library)?;
+
+        let mut files = Vec::new();
+        for dir in &output_dirs {
+            for path in collect_files(dir)? {
+                files.push(ManifestFile {
+                    path: path
+                        .strip_prefix(build_dir)
+                        .unwrap_or(&path)
+                        .display()
+                        .to_string(),
+                    size: fs::metadata(&path)?.len(),
+                    sha256: utils::sha256_file(&path)?,
+                });
+            }
+        }
+
+        libraries.push(ManifestLibrary {
+            name: lib_name,
+            version: version.to_string(),
+            commit,
+            source_url: repo.map(|r| r.url.clone()).unwrap_or_default(),
+            platforms,
+            files,
+        });
+    }
+
+    let manifest_path = build_dir.join("manifest.json");
+    fs::write(
+        &manifest_path,
+        serde_json::to_string_pretty(&Manifest { libraries })?,
+    )?;
+    log::info!("Wrote manifest to {}", manifest_path.display());
+    Ok(manifest_path)
+}
+
+/// Recursively lists regular files under `dir` in a stable order.
+pub(crate) fn collect_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(collect_files(&path)?);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
 
-        let lib_type = config.platforms.get_lib_type_for_platform(&Platform::Ios);
-        crate::platforms::darwin::build::create_xcframework(
-            &config.paths.build_dir,
-            library,
-            version,
-            lib_type,
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// Write `<build_dir>/SHA256SUMS` covering every file under `build/lib` and `build/include`.
+pub fn write_checksums(build_dir: &Path) -> Result<PathBuf> {
+    let mut lines = String::new();
+    for dir in ["lib", "include"] {
+        for path in collect_files(&build_dir.join(dir))? {
+            let relative = path.strip_prefix(build_dir).unwrap_or(&path);
+            lines.push_str(&format!(
+                "{}  {}\n",
+                utils::sha256_file(&path)?,
+                relative.display()
+            ));
+        }
+    }
+    let checksums_path = build_dir.join(CHECKSUMS_FILE);
+    fs::write(&checksums_path, lines)?;
+    log::info!("Wrote checksums to {}", checksums_path.display());
+    Ok(checksums_path)
+}
+
+/// Base name of the merged static library produced with `general.merge_static`.
+pub const BUNDLE_NAME: &str = "libopus_bundle";
+
+/// Merge all configured static libraries into one `libopus_bundle.a` per platform/arch
+/// (and per universal slice plus a combined xcframework on Apple platforms).
+/// With `force` the xcframework is recreated even if its inputs are unchanged.
+pub async fn merge_static_libraries_if_needed(
+    config: &Config,
+    force: bool,
+    exec: ExecOptions,
+) -> Result<()> {
+    if !config.general.merge_static {
+        return Ok(());
+    }
+    let build_dir = &config.paths.build_dir;
+    let mut has_darwin_bundle = false;
+    let darwin_bundle = config.darwin.bundle_name.as_deref().unwrap_or(BUNDLE_NAME);
+
+    for platform in &config.general.platforms {
+        if !config
+            .platforms
+            .get_lib_types_for_platform(platform)
+            .contains(&LibType::Static)
+        {
+            log::warn!("Skipping merged static library for {platform}: lib_types has no static");
+            continue;
+        }
+        match platform {
+            Platform::Android | Platform::Harmony => {
+                let ar = match platform {
+                    Platform::Android => crate::platforms::android::build::llvm_ar(config)?,
+                    _ => crate::platforms::harmony::build::llvm_ar(config)?,
+                };
+                for arch in config.platforms.get_archs_for_platform(platform) {
+                    let arch_dir = match platform {
+                        Platform::Android => {
+                            crate::platforms::android::build::arch_dir_name(*arch)?
+                        }
+                        _ => crate::platforms::harmony::build::arch_dir_name(*arch)?,
+                    };
+                    let mut inputs: Vec<_> = config
+                        .general
+                        .libraries
+                        .iter()
+                        .map(|library| {
+                            build_dir
+                                .join(platform.to_string())
+                                .join(arch_dir)
+                                .join(library.repo_name())
+                                .join("lib")
+                                .join(format!("{}.a", library.name_with_lib_prefix()))
+                        })
+                        .filter(|p| p.exists())
+                        .collect();
+                    if inputs.is_empty() {
+                        continue;
+                    }
+                    // same member order however `libraries` is listed
+                    inputs.sort();
+                    let output = build_dir
+                        .join("lib")
+                        .join(platform.to_string())
+                        .join(arch_dir)
+                        .join(format!("{BUNDLE_NAME}.a"));
+                    merge_with_ar_script(&ar, &inputs, &output, exec).await?;
+                }
+            }
+            Platform::Macos
+            | Platform::Ios
+            | Platform::IosSim
+            | Platform::MacCatalyst
+            | Platform::Tvos
+            | Platform::TvosSim
+            | Platform::Visionos
+            | Platform::VisionosSim => {
+                has_darwin_bundle |=
+                    crate::platforms::darwin::build::merge_universal_static_libraries(
+                        build_dir,
+                        *platform,
+                        &config.general.libraries,
+                        darwin_bundle,
+                        exec,
+                    )
+                    .await?;
+            }
+        }
+    }
+
+    if has_darwin_bundle {
+        crate::platforms::darwin::build::create_xcframework_from_universal(
+            build_dir,
+            darwin_bundle,
+            &format!("{darwin_bundle}.a"),
+            &format!("{darwin_bundle}.xcframework"),
+            XcframeworkOptions {
+                force,
+                codesign_identity: config.darwin.codesign_identity.as_deref(),
+            },
+            exec,
         )
         .await?;
     }
 
     Ok(())
 }
+
+/// Merge ELF static archives into `output` with an `ar -M` (MRI) script.
+pub(crate) async fn merge_with_ar_script(
+    ar: &Path,
+    inputs: &[PathBuf],
+    output: &Path,
+    exec: ExecOptions,
+) -> Result<()> {
+    let output_dir = output
+        .parent()
+        .context("merged archive has no parent dir")?;
+    fs::create_dir_all(output_dir)?;
+    let file_name = output
+        .file_name()
+        .context("merged archive has no file name")?;
+    let output = fs::canonicalize(output_dir)?.join(file_name);
+
+    let mut script = format!("CREATE {}\n", output.display());
+    for input in inputs {
+        script.push_str(&format!("ADDLIB {}\n", fs::canonicalize(input)?.display()));
+    }
+    script.push_str("SAVE\nEND\n");
+    let script_path = output.with_extension("mri");
+    fs::write(&script_path, script)?;
+
+    log::info!(
+        "Merging {} static libraries into {}",
+        inputs.len(),
+        output.display()
+    );
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(format!(
+        "\"{}\" -M < \"{}\"",
+        ar.display(),
+        script_path.display()
+    ));
+    let result = cmd
+        .run_with(exec)
+        .await
+        .with_context(|| format!("ar merge failed for {}", output.display()));
+    let _ = fs::remove_file(&script_path);
+    result
+}
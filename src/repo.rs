@@ -1,29 +1,133 @@
-use crate::config::Config;
-use crate::utils::CommandVerboseExt;
+use crate::config::{self, BuildSystem, Config, Library};
+use crate::utils::{self, CommandVerboseExt, ExecOptions};
 use anyhow::Context;
 use anyhow::Result;
 use glob::glob;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::process::Command;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Repo {
     pub name: String,
     pub url: String,
     pub local_path: PathBuf,
     pub version: String,
+    /// Glob patterns of files restored after `git clean -fdx`.
+    pub cache_globs: Vec<String>,
+    /// Where to fetch the DNN model from; only set for opus.
+    pub model: Option<ModelSource>,
+    /// Lives in a subfolder of a larger (mono)repo: never cloned, checked out or reset as a whole.
+    pub in_monorepo: bool,
+    /// Release tarball (path or URL) extracted to `local_path` instead of cloning `url`.
+    pub tarball: Option<String>,
+    /// Expected SHA256 of `tarball`, checked before it is extracted.
+    pub tarball_sha256: Option<String>,
+}
+
+pub const DEFAULT_MODEL_URL: &str = "https://media.xiph.org/opus/models";
+
+/// Where repos not found in `paths.repo_path` are cloned to.
+pub const DEFAULT_CLONE_DIR: &str = "repos";
+
+/// Cache of the opus DNN model tarballs.
+pub const MODEL_CACHE_DIR: &str = "opus-model";
+/// Per-repo caches of files matching `cache_globs`.
+pub const REPO_CACHE_DIR: &str = "repo-cache";
+/// Downloaded source tarballs.
+pub const SOURCE_CACHE_DIR: &str = "source-cache";
+/// Written into a tarball's extracted tree, holding the tarball it came from.
+const TARBALL_MARKER: &str = ".opus-builder-tarball";
+
+#[derive(Debug, Clone)]
+pub struct ModelSource {
+    pub base_url: String,
+    pub version: Option<String>,
 }
 
 impl Repo {
-    pub async fn ensure(&self, verbose: bool) -> Result<()> {
-        if self.local_path.exists() {
+    pub async fn ensure(&self, exec: ExecOptions) -> Result<()> {
+        if let Some(tarball) = &self.tarball {
+            let marker = fs::read_to_string(self.local_path.join(TARBALL_MARKER)).ok();
+            if marker.as_deref() == Some(tarball.as_str()) {
+                log::info!(
+                    "Found '{}' extracted from {} at {}",
+                    self.name,
+                    tarball,
+                    self.local_path.display()
+                );
+                return Ok(());
+            }
+            return self.extract_tarball(tarball, exec).await;
+        }
+        if self.in_monorepo {
+            if !self.local_path.exists() {
+                anyhow::bail!(
+                    "Repo '{}' not found at {} (repo_subpath is set, so it is not cloned)",
+                    self.name,
+                    self.local_path.display()
+                );
+            }
+            log::info!(
+                "Using '{}' from monorepo at {}, skipping checkout of '{}'",
+                self.name,
+                self.local_path.display(),
+                self.version
+            );
+            return Ok(());
+        }
+
+        let exists = self.local_path.exists();
+        let valid = exists && self.is_valid_checkout().await;
+        if exists && !valid {
+            if self.is_user_dir() {
+                anyhow::bail!(
+                    "{} is not a valid git checkout of '{}'. It was found via paths.repo_path and may hold your own work, so it is not deleted; fix or remove it, or drop its parent from paths.repo_path to clone into {DEFAULT_CLONE_DIR}/",
+                    self.local_path.display(),
+                    self.name
+                );
+            }
+            log::warn!(
+                "{} is not a valid git checkout (interrupted clone?), re-cloning '{}'",
+                self.local_path.display(),
+                self.name
+            );
+            if exec.dry_run {
+                log::info!("[dry-run] Would remove {}", self.local_path.display());
+            } else {
+                fs::remove_dir_all(&self.local_path)
+                    .with_context(|| format!("Failed to remove {}", self.local_path.display()))?;
+            }
+        }
+
+        if valid && config::tracked_branch(&self.version).is_none() && self.is_at_version().await {
+            log::info!(
+                "Repo '{}' at {} is already at '{}'",
+                self.name,
+                self.local_path.display(),
+                self.version
+            );
+            return Ok(());
+        }
+
+        if valid {
             log::info!(
                 "Found repo '{}' at {}",
                 self.name,
                 self.local_path.display()
             );
+        } else if exec.offline {
+            anyhow::bail!(
+                "Repo '{}' not found at {} and --offline forbids cloning {}; clone it there or add its parent directory to paths.repo_path",
+                self.name,
+                self.local_path.display(),
+                self.url
+            );
         } else {
             log::info!(
                 "Cloning repo '{}' from {} to {}",
@@ -35,11 +139,35 @@ impl Repo {
                 .arg("clone")
                 .arg(&self.url)
                 .arg(&self.local_path)
-                .run_with_verbose(verbose)
+                .run_with(exec)
                 .await
                 .context(format!("Failed to clone repo '{}'", self.name))?;
         }
 
+        if let Some(branch) = config::tracked_branch(&self.version) {
+            return self.checkout_branch_tip(branch, exec).await;
+        }
+
+        // e.g. a tag or commit newer than the clone, as given to `build --ref`
+        if valid && !exec.offline && !exec.dry_run && self.version_commit().await.is_none() {
+            log::info!(
+                "'{}' is not in the clone of '{}', fetching",
+                self.version,
+                self.name
+            );
+            Command::new("git")
+                .args(["fetch", "--tags", "origin"])
+                .current_dir(&self.local_path)
+                .run_with(exec)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to fetch '{}' for repo '{}'",
+                        self.version, self.name
+                    )
+                })?;
+        }
+
         log::info!(
             "Checking out version '{}' for repo '{}'",
             self.version,
@@ -49,7 +177,7 @@ impl Repo {
             .arg("checkout")
             .arg(&self.version)
             .current_dir(&self.local_path)
-            .run_with_verbose(verbose)
+            .run_with(exec)
             .await
             .context(format!(
                 "Failed to checkout version '{}' for repo '{}'",
@@ -59,123 +187,680 @@ impl Repo {
         Ok(())
     }
 
-    fn cache_opus_model_before_clean(&self) -> Result<()> {
-        let opus_model_dir = PathBuf::from("opus-model");
-        if !opus_model_dir.exists() {
-            fs::create_dir_all(&opus_model_dir)?;
+    /// Delete the checkout so the next [`Repo::ensure`] clones it fresh. A
+    /// checkout outside [`DEFAULT_CLONE_DIR`] was provided by the user via
+    /// `paths.repo_path` and is only deleted with `allow_user_dir`.
+    pub fn remove_for_reclone(&self, allow_user_dir: bool, exec: ExecOptions) -> Result<()> {
+        if self.in_monorepo {
+            log::warn!(
+                "Not re-cloning '{}': it is part of the repo at {}",
+                self.name,
+                self.local_path.display()
+            );
+            return Ok(());
+        }
+        if !self.local_path.exists() {
+            return Ok(());
+        }
+        if self.is_user_dir() && !allow_user_dir {
+            log::warn!(
+                "{} was found via paths.repo_path and may hold your own work",
+                self.local_path.display()
+            );
+            anyhow::bail!(
+                "Refusing to delete {} for --reclone; pass --reclone-user-repos as well to delete it",
+                self.local_path.display()
+            );
+        }
+
+        if exec.dry_run {
+            log::info!(
+                "[dry-run] Would remove {} to re-clone '{}'",
+                self.local_path.display(),
+                self.name
+            );
+            return Ok(());
+        }
+        log::info!(
+            "Removing {} to re-clone '{}'",
+            self.local_path.display(),
+            self.name
+        );
+        fs::remove_dir_all(&self.local_path)
+            .with_context(|| format!("Failed to remove {}", self.local_path.display()))
+    }
+
+    /// Whether the checkout lies outside [`DEFAULT_CLONE_DIR`], i.e. was found
+    /// via `paths.repo_path` rather than cloned by this tool.
+    fn is_user_dir(&self) -> bool {
+        // absolute paths, so `./repos` or `/abs/path/to/repos` count as ours too
+        match (
+            std::path::absolute(&self.local_path),
+            std::path::absolute(DEFAULT_CLONE_DIR),
+        ) {
+            (Ok(local_path), Ok(clone_dir)) => !local_path.starts_with(clone_dir),
+            _ => !self.local_path.starts_with(DEFAULT_CLONE_DIR),
+        }
+    }
+
+    /// Whether `local_path` is the root of a working git checkout, as opposed to
+    /// a partial clone or a plain directory inside some other repo.
+    async fn is_valid_checkout(&self) -> bool {
+        self.local_path.join(".git").exists()
+            && Command::new("git")
+                .args(["status", "--porcelain"])
+                .current_dir(&self.local_path)
+                .output()
+                .await
+                .is_ok_and(|output| output.status.success())
+    }
+
+    /// Whether HEAD already is the commit `version` names, so checking it out
+    /// again can be skipped (e.g. after `fetch`).
+    async fn is_at_version(&self) -> bool {
+        match (self.version_commit().await, self.head_commit().await) {
+            (Some(version), Ok(head)) => version == head,
+            _ => false,
+        }
+    }
+
+    /// The commit `version` names in the local clone, `None` if it isn't known there.
+    async fn version_commit(&self) -> Option<String> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("{}^{{commit}}", self.version))
+            .current_dir(&self.local_path)
+            .output()
+            .await
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Fetch `branch` and check out `origin/<branch>` detached, so `clean`'s
+    /// `git reset --hard` keeps the fresh tip instead of a stale local branch.
+    async fn checkout_branch_tip(&self, branch: &str, exec: ExecOptions) -> Result<()> {
+        if exec.offline {
+            log::warn!(
+                "--offline: not fetching '{}', using the last fetched origin/{}",
+                self.name,
+                branch
+            );
+        } else {
+            log::info!("Fetching branch '{}' for repo '{}'", branch, self.name);
+            Command::new("git")
+                .args(["fetch", "origin", branch])
+                .current_dir(&self.local_path)
+                .run_with(exec)
+                .await
+                .context(format!(
+                    "Failed to fetch branch '{}' for repo '{}'",
+                    branch, self.name
+                ))?;
+        }
+
+        let remote_ref = format!("origin/{branch}");
+        log::info!("Checking out '{}' for repo '{}'", remote_ref, self.name);
+        Command::new("git")
+            .args(["checkout", "--detach"])
+            .arg(&remote_ref)
+            .current_dir(&self.local_path)
+            .run_with(exec)
+            .await
+            .context(format!(
+                "Failed to checkout '{}' for repo '{}'",
+                remote_ref, self.name
+            ))?;
+
+        Ok(())
+    }
+
+    /// The local tarball to extract, downloading `tarball` into
+    /// [`SOURCE_CACHE_DIR`] first when it is a URL, and checked against
+    /// `tarball_sha256` when set.
+    async fn tarball_archive(&self, tarball: &str, exec: ExecOptions) -> Result<PathBuf> {
+        if !is_url(tarball) {
+            let archive = PathBuf::from(tarball);
+            if exec.dry_run {
+                return Ok(archive);
+            }
+            if !archive.is_file() {
+                anyhow::bail!("Source tarball for '{}' not found: {}", self.name, tarball);
+            }
+            self.verify_tarball(&archive)?;
+            return Ok(archive);
         }
 
-        let model_in_cache_pattern = opus_model_dir.join("opus_data-*.tar.gz");
-        let model_in_cache = glob(&model_in_cache_pattern.to_string_lossy())?
+        let file_name = tarball
+            .rsplit('/')
             .next()
-            .is_some();
+            .filter(|name| !name.is_empty())
+            .with_context(|| format!("Tarball URL has no file name: {tarball}"))?;
+        let cached = PathBuf::from(SOURCE_CACHE_DIR)
+            .join(&self.name)
+            .join(file_name);
+        if cached.exists() {
+            self.verify_tarball(&cached)?;
+            return Ok(cached);
+        }
+        if exec.offline {
+            anyhow::bail!(
+                "Source tarball {} is not cached and --offline forbids downloading it; place it in {}",
+                file_name,
+                cached.parent().unwrap_or(&cached).display()
+            );
+        }
+        log::info!("Downloading '{}' sources from {}", self.name, tarball);
+        if !exec.dry_run {
+            fs::create_dir_all(cached.parent().unwrap_or(Path::new(SOURCE_CACHE_DIR)))?;
+        }
+        // download next to the final path so an interrupted download is never used
+        let partial = cached.with_extension("part");
+        Command::new("curl")
+            .args(["-fL", "--retry", "3", "-o"])
+            .arg(&partial)
+            .arg(tarball)
+            .run_with(exec)
+            .await
+            .with_context(|| format!("Failed to download {tarball}"))?;
+        if !exec.dry_run {
+            if let Err(e) = self.verify_tarball(&partial) {
+                let _ = fs::remove_file(&partial);
+                return Err(e);
+            }
+            fs::rename(&partial, &cached)?;
+        }
+        Ok(cached)
+    }
 
-        if !model_in_cache {
-            let model_in_repo_pattern = self.local_path.join("opus_data-*.tar.gz");
-            if let Some(Ok(model_in_repo)) = glob(&model_in_repo_pattern.to_string_lossy())?.next()
-                && let Some(model_filename) = model_in_repo.file_name()
-            {
-                log::info!(
-                    "Caching opus model file: {}",
-                    model_filename.to_string_lossy()
+    fn verify_tarball(&self, archive: &Path) -> Result<()> {
+        match &self.tarball_sha256 {
+            Some(expected) => verify_sha256(archive, expected)
+                .with_context(|| format!("Refusing to extract the sources of '{}'", self.name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Replace `local_path` with the contents of `tarball`, dropping the
+    /// tarball's top-level directory (`opus-1.5.2/`).
+    async fn extract_tarball(&self, tarball: &str, exec: ExecOptions) -> Result<()> {
+        let archive = self.tarball_archive(tarball, exec).await?;
+        log::info!(
+            "Extracting {} to {}",
+            archive.display(),
+            self.local_path.display()
+        );
+        if exec.dry_run {
+            log::info!("[dry-run] Would replace {}", self.local_path.display());
+        } else {
+            if self.local_path.join(".git").exists() {
+                anyhow::bail!(
+                    "{} is a git checkout; remove it to build '{}' from {}",
+                    self.local_path.display(),
+                    self.name,
+                    tarball
                 );
-                fs::copy(&model_in_repo, opus_model_dir.join(model_filename))?;
             }
+            if self.local_path.exists() {
+                fs::remove_dir_all(&self.local_path)
+                    .with_context(|| format!("Failed to remove {}", self.local_path.display()))?;
+            }
+            fs::create_dir_all(&self.local_path)?;
+        }
+        Command::new("tar")
+            .arg("-xf")
+            .arg(fs::canonicalize(&archive).unwrap_or(archive))
+            .arg("--strip-components=1")
+            .current_dir(&self.local_path)
+            .run_with(exec)
+            .await
+            .with_context(|| format!("Failed to extract the sources of '{}'", self.name))?;
+        if !exec.dry_run {
+            fs::write(self.local_path.join(TARBALL_MARKER), tarball)?;
         }
         Ok(())
     }
 
-    fn restore_opus_model_after_clean(&self) -> Result<()> {
-        let opus_model_dir = PathBuf::from("opus-model");
-        let model_in_cache_pattern = opus_model_dir.join("opus_data-*.tar.gz");
+    /// Returns the commit SHA currently checked out.
+    pub async fn head_commit(&self) -> Result<String> {
+        if let Some(tarball) = &self.tarball {
+            anyhow::bail!(
+                "'{}' is built from {}, not a git checkout",
+                self.name,
+                tarball
+            );
+        }
+        let output = Command::new("git")
+            .arg("rev-parse")
+            .arg("HEAD")
+            .current_dir(&self.local_path)
+            .output()
+            .await
+            .context(format!("Failed to resolve HEAD for repo '{}'", self.name))?;
+        if !output.status.success() {
+            anyhow::bail!("git rev-parse HEAD failed for repo '{}'", self.name);
+        }
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
 
-        if let Some(Ok(model_in_cache)) = glob(&model_in_cache_pattern.to_string_lossy())?.next()
-            && let Some(model_filename) = model_in_cache.file_name()
-        {
-            log::info!(
-                "Restoring opus model file: {}",
-                model_filename.to_string_lossy()
+    /// Committer timestamp (seconds since the epoch) of the checked out commit;
+    /// for tarball sources the newest modification time `tar` restored.
+    pub async fn head_commit_time(&self) -> Result<u64> {
+        if self.tarball.is_some() {
+            let mut newest = 0;
+            for entry in fs::read_dir(&self.local_path)? {
+                let entry = entry?;
+                if entry.file_name() == TARBALL_MARKER {
+                    continue;
+                }
+                let modified = entry.metadata()?.modified()?;
+                let secs = modified
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                newest = newest.max(secs);
+            }
+            return Ok(newest);
+        }
+        let output = Command::new("git")
+            .args(["log", "-1", "--format=%ct"])
+            .current_dir(&self.local_path)
+            .output()
+            .await
+            .context(format!("Failed to read HEAD time for repo '{}'", self.name))?;
+        if !output.status.success() {
+            anyhow::bail!("git log failed for repo '{}'", self.name);
+        }
+        Ok(String::from_utf8(output.stdout)?.trim().parse()?)
+    }
+
+    /// Where files matching `cache_globs` are kept while the repo is cleaned.
+    fn cache_dir(&self) -> PathBuf {
+        if self.name == "opus" {
+            // keep the location used before caching was configurable
+            PathBuf::from(MODEL_CACHE_DIR)
+        } else {
+            PathBuf::from(REPO_CACHE_DIR).join(&self.name)
+        }
+    }
+
+    fn cache_files_before_clean(&self) -> Result<()> {
+        let cache_dir = self.cache_dir();
+        for pattern in &self.cache_globs {
+            let pattern = self.local_path.join(pattern);
+            for path in glob(&pattern.to_string_lossy())?.flatten() {
+                let Ok(relative) = path.strip_prefix(&self.local_path) else {
+                    continue;
+                };
+                let cached = cache_dir.join(relative);
+                if !path.is_file() || cached.exists() {
+                    continue;
+                }
+                log::info!("Caching {} file: {}", self.name, relative.display());
+                if let Some(parent) = cached.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&path, &cached)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn restore_files_after_clean(&self) -> Result<()> {
+        let cache_dir = self.cache_dir();
+        for pattern in &self.cache_globs {
+            let pattern = cache_dir.join(pattern);
+            for cached in glob(&pattern.to_string_lossy())?.flatten() {
+                let Ok(relative) = cached.strip_prefix(&cache_dir) else {
+                    continue;
+                };
+                log::info!("Restoring {} file: {}", self.name, relative.display());
+                let dest = self.local_path.join(relative);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&cached, dest)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Model version opus's `autogen.sh` passes to `dnn/download_model.sh`, if any.
+    fn autogen_model_version(&self) -> Option<String> {
+        let autogen = fs::read_to_string(self.local_path.join("autogen.sh")).ok()?;
+        autogen
+            .lines()
+            .find(|line| line.contains("download_model.sh"))?
+            .split_whitespace()
+            .last()
+            .map(|v| v.trim_matches('"').to_string())
+            .filter(|v| !v.is_empty() && !v.starts_with('$'))
+    }
+
+    /// Make sure the opus DNN model tarball is in the checkout, downloading it
+    /// into `opus-model/` first if it isn't cached yet.
+    pub async fn ensure_model(&self, exec: ExecOptions) -> Result<()> {
+        let Some(model) = &self.model else {
+            return Ok(());
+        };
+        let in_repo = self.local_path.join("opus_data-*.tar.gz");
+        if glob(&in_repo.to_string_lossy())?.flatten().next().is_some() {
+            return Ok(());
+        }
+        let Some(version) = model
+            .version
+            .clone()
+            .or_else(|| self.autogen_model_version())
+        else {
+            log::debug!("Repo '{}' does not use a DNN model", self.name);
+            return Ok(());
+        };
+
+        let file_name = format!("opus_data-{version}.tar.gz");
+        let cached = self.cache_dir().join(&file_name);
+        if !cached.exists() && exec.offline {
+            anyhow::bail!(
+                "Opus model {} is not cached and --offline forbids downloading it; place it in {}",
+                file_name,
+                self.cache_dir().display()
             );
-            fs::copy(&model_in_cache, self.local_path.join(model_filename))?;
+        }
+        if !cached.exists() {
+            let url = format!("{}/{}", model.base_url.trim_end_matches('/'), file_name);
+            log::info!("Downloading opus model from {url}");
+            if !exec.dry_run {
+                fs::create_dir_all(self.cache_dir())?;
+            }
+            // download next to the cached file so an interrupted download is never used
+            let partial = cached.with_file_name(format!("{file_name}.part"));
+            let downloaded = Command::new("curl")
+                .args(["-fL", "--retry", "3", "-o"])
+                .arg(&partial)
+                .arg(&url)
+                .run_with(exec)
+                .await;
+            if let Err(e) = downloaded {
+                let _ = fs::remove_file(&partial);
+                return Err(e).with_context(|| format!("Failed to download opus model from {url}"));
+            }
+            if !exec.dry_run {
+                fs::rename(&partial, &cached)?;
+            }
+        }
+
+        if !exec.dry_run {
+            log::info!("Restoring opus model file: {file_name}");
+            fs::copy(&cached, self.local_path.join(&file_name))?;
         }
         Ok(())
     }
 
-    pub async fn clean(&self, verbose: bool) -> Result<()> {
-        if self.name == "opus" {
-            self.cache_opus_model_before_clean()?;
+    pub async fn clean(&self, exec: ExecOptions) -> Result<()> {
+        if !exec.dry_run {
+            self.cache_files_before_clean()?;
+        }
+
+        if let Some(tarball) = &self.tarball {
+            log::info!("Cleaning '{}' by extracting {} again", self.name, tarball);
+            self.extract_tarball(tarball, exec).await?;
+            if !exec.dry_run {
+                self.restore_files_after_clean()?;
+            }
+            return Ok(());
         }
 
         log::info!("Cleaning repo '{}'", self.name);
-        Command::new("git")
-            .arg("reset")
-            .arg("--hard")
-            .current_dir(&self.local_path)
-            .run_with_verbose(verbose)
-            .await
-            .context(format!("Failed to clean repo '{}'", self.name))?;
+        if self.in_monorepo {
+            // only touch this library's subfolder, not the whole enclosing repo
+            Command::new("git")
+                .args(["checkout", "--", "."])
+                .current_dir(&self.local_path)
+                .run_with(exec)
+                .await
+                .context(format!("Failed to clean repo '{}'", self.name))?;
+        } else {
+            Command::new("git")
+                .arg("reset")
+                .arg("--hard")
+                .current_dir(&self.local_path)
+                .run_with(exec)
+                .await
+                .context(format!("Failed to clean repo '{}'", self.name))?;
+        }
 
         Command::new("git")
-            .arg("clean")
-            .arg("-fdx")
+            .args(["clean", "-fdx", "--", "."])
             .current_dir(&self.local_path)
-            .run_with_verbose(verbose)
+            .run_with(exec)
             .await
             .context(format!("Failed to clean repo '{}'", self.name))?;
 
-        if self.name == "opus" {
-            self.restore_opus_model_after_clean()?;
+        if !exec.dry_run {
+            self.restore_files_after_clean()?;
         }
 
         Ok(())
     }
 }
 
-pub fn get_repos(config: &Config) -> anyhow::Result<Vec<Repo>> {
-    let repo_prefix = &config.general.repo_prefix;
+/// Run `prepare` (clone, checkout, clean, ...) for every repo, at most
+/// `max_parallel` at a time, and return the results in the order of `repos`.
+/// Every repo is attempted even if others fail. Libraries in a monorepo take
+/// turns, as git locks the index of the enclosing repo; the opus model cache is
+/// only touched by the opus repo.
+pub async fn prepare_parallel<F, Fut>(
+    repos: &[Repo],
+    max_parallel: usize,
+    prepare: F,
+) -> Vec<Result<()>>
+where
+    F: Fn(Repo) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let slots = Arc::new(Semaphore::new(max_parallel.max(1)));
+    let monorepo = Arc::new(Mutex::new(()));
+    let mut tasks = JoinSet::new();
+    for (index, repo) in repos.iter().cloned().enumerate() {
+        let slots = slots.clone();
+        let monorepo = monorepo.clone();
+        let in_monorepo = repo.in_monorepo;
+        let prepared = prepare(repo);
+        tasks.spawn(async move {
+            let _slot = slots.acquire_owned().await;
+            let _turn = match in_monorepo {
+                true => Some(monorepo.lock_owned().await),
+                false => None,
+            };
+            (index, prepared.await)
+        });
+    }
+
+    let mut results: Vec<Option<Result<()>>> = repos.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, result)) => results[index] = Some(result),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+    results
+        .into_iter()
+        .map(|result| result.expect("every repo task reports back"))
+        .collect()
+}
+
+/// Upstream git URL of `library`: its `source.git`, or derived from `general.repo_prefix`.
+pub fn remote_url(config: &Config, library: &Library) -> String {
+    config
+        .libraries
+        .get(library)
+        .and_then(|opts| opts.source.as_ref())
+        .and_then(|source| source.git.clone())
+        .unwrap_or_else(|| format!("{}{}.git", config.general.repo_prefix, library.repo_name()))
+}
+
+/// Fail unless the SHA256 of `path` is `expected` (hex, case-insensitive).
+pub fn verify_sha256(path: &Path, expected: &str) -> Result<()> {
+    let actual = utils::sha256_file(path)?;
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        anyhow::bail!(
+            "SHA256 mismatch for {}: expected {}, got {actual}",
+            path.display(),
+            expected.trim()
+        );
+    }
+    log::info!("Verified SHA256 of {}", path.display());
+    Ok(())
+}
 
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// The numeric components of a release tag such as `v1.5.2`; `None` for
+/// pre-releases (`v1.5-rc1`) and anything else that isn't a plain version.
+pub fn parse_release_version(tag: &str) -> Option<Vec<u64>> {
+    let version = tag.strip_prefix('v').unwrap_or(tag);
+    version
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()
+        .filter(|parts| !parts.is_empty())
+}
+
+/// The highest release among `tags`, comparing `v1.5` as `v1.5.0`. Of equal
+/// releases the first one wins, so `update` keeps a configured `v1.5` rather
+/// than rewriting it to the same release spelled `v1.5.0`.
+pub fn latest_release<'a>(tags: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let padded = |tag: &str| {
+        let mut parts = parse_release_version(tag)?;
+        parts.resize(parts.len().max(3), 0);
+        Some(parts)
+    };
+    tags.into_iter()
+        .filter_map(|tag| Some((padded(tag)?, tag)))
+        .reduce(|best, next| if next.0 > best.0 { next } else { best })
+        .map(|(_, tag)| tag)
+}
+
+/// The highest release tag of the remote repo at `url`, via `git ls-remote`.
+pub async fn latest_remote_tag(url: &str) -> Result<Option<String>> {
+    let output = Command::new("git")
+        .args(["ls-remote", "--tags", "--refs"])
+        .arg(url)
+        .output()
+        .await
+        .context("Failed to run git ls-remote")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git ls-remote --tags {url} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let tags = stdout
+        .lines()
+        .filter_map(|line| line.split_once("refs/tags/").map(|(_, tag)| tag.trim()));
+    Ok(latest_release(tags).map(str::to_string))
+}
+
+pub fn get_repos(config: &Config) -> anyhow::Result<Vec<Repo>> {
     let mut search_paths = config.paths.repo_path.to_vec();
-    let current_dir = env::current_dir()?;
-    search_paths.push(current_dir.clone());
-    let mut parent = current_dir.parent();
-    while let Some(p) = parent {
-        search_paths.push(p.to_path_buf());
-        parent = p.parent();
+    if config.paths.search_parent_dirs {
+        let current_dir = env::current_dir()?;
+        search_paths.extend(current_dir.ancestors().map(Path::to_path_buf));
     }
+    log::trace!("Repo search paths: {search_paths:?}");
 
     let mut repos = Vec::new();
     for lib in &config.general.libraries {
         let name = lib.repo_name();
-        let url = format!("{}{}.git", repo_prefix, name);
+        let url = remote_url(config, lib);
 
-        let version = if let Some(lib_config) = config.libraries.get(lib) {
-            if let Some(v) = &lib_config.version {
-                v
-            } else {
-                anyhow::bail!("Version not specified for library: {:?}", lib);
-            }
-        } else {
+        let Some(lib_config) = config.libraries.get(lib) else {
             anyhow::bail!("Library configuration not found for: {:?}", lib);
         };
+        let Some(version) = &lib_config.version else {
+            anyhow::bail!("Version not specified for library: {:?}", lib);
+        };
+        let cache_globs = match &lib_config.cache_globs {
+            Some(globs) => globs.clone(),
+            None => lib
+                .default_cache_globs()
+                .iter()
+                .map(|g| g.to_string())
+                .collect(),
+        };
+        let key = lib.name_with_lib_prefix();
+        let tarball = match &lib_config.source {
+            Some(source) if source.git.is_some() && source.tarball.is_some() => {
+                anyhow::bail!("libraries.{key}.source: set either `git` or `tarball`, not both")
+            }
+            Some(source) => source.tarball.clone(),
+            None => None,
+        };
+        let tarball_sha256 = lib_config
+            .source
+            .as_ref()
+            .and_then(|source| source.sha256.clone());
+        if tarball_sha256.is_some() && tarball.is_none() {
+            anyhow::bail!("libraries.{key}.source: `sha256` is only checked for a `tarball`");
+        }
+        if tarball.is_some() && lib_config.repo_subpath.is_some() {
+            anyhow::bail!(
+                "libraries.{key}: `repo_subpath` cannot be combined with a tarball source"
+            );
+        }
+        if lib_config.build_system != BuildSystem::Autotools
+            && (lib_config.make_targets.is_some() || !lib_config.make_args.is_empty())
+        {
+            anyhow::bail!(
+                "libraries.{key}: `make_targets` and `make_args` only apply to autotools builds"
+            );
+        }
+        let model = (*lib == Library::Libopus).then(|| ModelSource {
+            base_url: lib_config
+                .model_url
+                .clone()
+                .unwrap_or_else(|| DEFAULT_MODEL_URL.to_string()),
+            version: lib_config.model_version.clone(),
+        });
 
+        let subpath = lib_config
+            .repo_subpath
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(name));
+        // tarballs are always extracted into the directory this tool manages
         let local_path = search_paths
             .iter()
+            .filter(|_| tarball.is_none())
             .find_map(|p| {
-                let potential_path = p.join(name);
+                let potential_path = p.join(&subpath);
                 if potential_path.exists() {
                     log::info!("Found repo '{}' at {}", name, potential_path.display());
                     Some(potential_path)
                 } else {
+                    log::trace!("No repo '{}' at {}", name, potential_path.display());
                     None
                 }
             })
-            .unwrap_or_else(|| PathBuf::from("repos").join(name));
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CLONE_DIR).join(&subpath));
 
+        log::trace!(
+            "Resolved {key}: version {version}, source {}, checkout {}",
+            tarball.as_deref().unwrap_or(url.as_ref()),
+            local_path.display()
+        );
         repos.push(Repo {
             name: name.to_string(),
             url: url.to_string(),
             local_path,
             version: version.to_string(),
+            cache_globs,
+            model,
+            in_monorepo: lib_config.repo_subpath.is_some(),
+            tarball,
+            tarball_sha256,
         });
     }
     Ok(repos)
@@ -0,0 +1,176 @@
+use crate::post_build;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Where the sizes of the previous build are kept for comparison.
+pub const LAST_SIZES_FILE: &str = "last_sizes.json";
+
+/// Relative change (either way) above which a file's size is flagged.
+pub const SIZE_CHANGE_THRESHOLD: f64 = 0.10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeEntry {
+    /// Relative to the build dir, e.g. `lib/android/arm64-v8a/libopus-1.5.2/libopus.a`.
+    pub path: String,
+    pub bytes: u64,
+}
+
+impl SizeEntry {
+    /// `<platform>/<library>` for files under `lib/`, e.g.
+    /// `android/libopus-1.5.2`; the header directory for files under `include/`.
+    pub fn group(&self) -> String {
+        let parts: Vec<&str> = self.path.split('/').collect();
+        match parts.as_slice() {
+            ["lib", platform, rest @ ..] => match rest.iter().find(|p| p.starts_with("lib")) {
+                Some(library) if rest.len() > 1 => format!("{platform}/{library}"),
+                _ => platform.to_string(),
+            },
+            ["include", dir, _, ..] => format!("include/{dir}"),
+            _ => parts[0].to_string(),
+        }
+    }
+}
+
+/// A file whose size changed by more than [`SIZE_CHANGE_THRESHOLD`] since the last build.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeChange {
+    pub path: String,
+    pub previous: u64,
+    pub current: u64,
+}
+
+impl SizeChange {
+    pub fn ratio(&self) -> f64 {
+        (self.current as f64 - self.previous as f64) / self.previous as f64
+    }
+}
+
+/// Sizes of the shipped files under `build/lib` and `build/include`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeSummary {
+    pub entries: Vec<SizeEntry>,
+}
+
+impl SizeSummary {
+    pub fn collect(build_dir: &Path) -> Result<Self> {
+        let mut entries = Vec::new();
+        for dir in ["lib", "include"] {
+            for path in post_build::collect_files(&build_dir.join(dir))? {
+                let relative = path.strip_prefix(build_dir).unwrap_or(&path);
+                entries.push(SizeEntry {
+                    path: relative
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                    bytes: fs::metadata(&path)?.len(),
+                });
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// The summary saved by [`SizeSummary::write_json`], if any.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.iter().map(|e| e.bytes).sum()
+    }
+
+    /// Files present in both summaries whose size changed by more than
+    /// [`SIZE_CHANGE_THRESHOLD`].
+    pub fn changes_since(&self, previous: &SizeSummary) -> Vec<SizeChange> {
+        let previous: BTreeMap<_, _> = previous
+            .entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.bytes))
+            .collect();
+        self.entries
+            .iter()
+            .filter_map(|e| {
+                let change = SizeChange {
+                    path: e.path.clone(),
+                    previous: *previous.get(e.path.as_str())?,
+                    current: e.bytes,
+                };
+                (change.previous > 0 && change.ratio().abs() > SIZE_CHANGE_THRESHOLD)
+                    .then_some(change)
+            })
+            .collect()
+    }
+
+    pub fn print_summary(&self, previous: Option<&SizeSummary>) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let mut groups: BTreeMap<String, Vec<&SizeEntry>> = BTreeMap::new();
+        for entry in &self.entries {
+            groups.entry(entry.group()).or_default().push(entry);
+        }
+        println!("\nOutput sizes:");
+        for (group, entries) in &groups {
+            let total: u64 = entries.iter().map(|e| e.bytes).sum();
+            println!("  {group:<60} {:>10}", format_size(total));
+            for entry in entries {
+                println!("    {:<58} {:>10}", entry.path, format_size(entry.bytes));
+            }
+        }
+        println!("  {:<60} {:>10}", "total", format_size(self.total_bytes()));
+
+        let Some(previous) = previous else {
+            return;
+        };
+        let changes = self.changes_since(previous);
+        if changes.is_empty() {
+            return;
+        }
+        println!(
+            "\nSize changes over {:.0}% since the last build:",
+            SIZE_CHANGE_THRESHOLD * 100.0
+        );
+        for change in &changes {
+            println!(
+                "  {}: {} -> {} ({:+.0}%)",
+                change.path,
+                format_size(change.previous),
+                format_size(change.current),
+                change.ratio() * 100.0
+            );
+        }
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// `bytes` in the largest binary unit that keeps it at or above 1, e.g. `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
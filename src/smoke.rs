@@ -0,0 +1,235 @@
+use crate::build;
+use crate::builder::AutotoolsToolchain;
+use crate::config::{Arch, Config, LibType, Library, Platform};
+use crate::utils::{CommandVerboseExt, ExecOptions};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Where smoke test binaries are pushed on an Android device.
+const ADB_DIR: &str = "/data/local/tmp/opus-builder-smoke";
+
+/// A C program exercising one entry point of `library`; it exits non-zero on failure.
+pub fn program_source(library: &Library) -> &'static str {
+    match library {
+        Library::Libogg => {
+            r#"#include <stdio.h>
+#include <ogg/ogg.h>
+
+int main(void) {
+    ogg_stream_state os;
+    if (ogg_stream_init(&os, 1) != 0) return 1;
+    ogg_stream_clear(&os);
+    puts("libogg ok");
+    return 0;
+}
+"#
+        }
+        Library::Libopus => {
+            r#"#include <stdio.h>
+#include <opus/opus.h>
+
+int main(void) {
+    int err;
+    OpusDecoder *dec = opus_decoder_create(48000, 2, &err);
+    if (err != OPUS_OK || dec == NULL) return 1;
+    opus_decoder_destroy(dec);
+    printf("%s ok\n", opus_get_version_string());
+    return 0;
+}
+"#
+        }
+        Library::Libopusenc => {
+            r#"#include <stdio.h>
+#include <opus/opusenc.h>
+
+int main(void) {
+    OggOpusComments *comments = ope_comments_create();
+    if (comments == NULL) return 1;
+    ope_comments_destroy(comments);
+    printf("%s ok\n", ope_get_version_string());
+    return 0;
+}
+"#
+        }
+        Library::Libopusfile => {
+            r#"#include <stdio.h>
+#include <opus/opusfile.h>
+
+int main(void) {
+    static const unsigned char not_ogg[] = "not an ogg stream";
+    int err = 0;
+    OggOpusFile *of = op_open_memory(not_ogg, sizeof(not_ogg), &err);
+    if (of != NULL || err == 0) return 1;
+    puts("libopusfile ok");
+    return 0;
+}
+"#
+        }
+    }
+}
+
+/// Libraries to link for `library`, dependents before their dependencies as
+/// static linking requires.
+pub fn link_order(library: &Library) -> Result<Vec<Library>> {
+    let mut libraries = library.with_dependencies()?;
+    libraries.reverse();
+    Ok(libraries)
+}
+
+/// How the smoke test binary of a target is run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Runner {
+    Host,
+    Adb,
+}
+
+/// Link a tiny program against the freshly built `library` for one target and
+/// run it, on this machine or on an attached Android device. Targets nothing
+/// can run are skipped with a warning.
+pub async fn run(
+    config: &Config,
+    platform: Platform,
+    arch: Arch,
+    library: &Library,
+    toolchain: &AutotoolsToolchain,
+    exec: ExecOptions,
+) -> Result<()> {
+    let Some(runner) = runner_for(platform, arch, exec).await else {
+        log::warn!(
+            "Skipping the {library} smoke test for {platform} ({arch}): it can't run on this host or an attached device"
+        );
+        return Ok(());
+    };
+
+    let lib_types = config.platforms.get_lib_types_for_platform(&platform);
+    let lib_type = if lib_types.contains(&LibType::Static) {
+        LibType::Static
+    } else {
+        LibType::Shared
+    };
+    let build_dir = &config.paths.build_dir;
+    let smoke_dir = build_dir
+        .join(&toolchain.platform_dir)
+        .join(&toolchain.arch_dir)
+        .join("obj")
+        .join(format!("smoke-{}", library.repo_name()));
+    let source = smoke_dir.join("main.c");
+    let exe = smoke_dir.join("smoke");
+    if !exec.dry_run {
+        fs::create_dir_all(&smoke_dir)?;
+        fs::write(&source, program_source(library))?;
+    }
+
+    let mut libs = Vec::new();
+    for lib in link_order(library)? {
+        libs.push(build::expected_library_path(
+            build_dir, platform, arch, &lib, lib_type,
+        )?);
+    }
+
+    let mut cc = toolchain.cc.split_whitespace();
+    let mut cmd = Command::new(cc.next().context("Empty compiler command")?);
+    cmd.args(cc)
+        .args(toolchain.base_cflags.split_whitespace())
+        .args(
+            config
+                .build
+                .sanitize_flags()
+                .iter()
+                .flat_map(|f| f.split_whitespace()),
+        );
+    if config.build.sanitizers.is_empty()
+        && let Some(flag) = config.build.lto.flag()
+    {
+        cmd.arg(flag);
+    }
+    for lib in &libs {
+        if let Some(prefix) = lib.parent().and_then(Path::parent) {
+            cmd.arg(format!("-I{}", prefix.join("include").display()));
+        }
+    }
+    cmd.arg(&source)
+        .arg("-o")
+        .arg(&exe)
+        .args(&libs)
+        .args(toolchain.base_ldflags.split_whitespace())
+        .arg("-lm")
+        .envs(
+            toolchain
+                .extra_env
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str())),
+        );
+    cmd.run_with(exec).await.with_context(|| {
+        format!("Failed to link the {library} smoke test for {platform} ({arch})")
+    })?;
+
+    let output = match runner {
+        Runner::Host => Command::new(&exe).run_with(exec).await,
+        Runner::Adb => run_on_device(&exe, &libs, lib_type, exec).await,
+    };
+    output.with_context(|| format!("{library} smoke test failed for {platform} ({arch})"))?;
+    log::info!("{library} smoke test passed for {platform} ({arch})");
+    Ok(())
+}
+
+async fn runner_for(platform: Platform, arch: Arch, exec: ExecOptions) -> Option<Runner> {
+    if build::runs_on_host(platform, arch) {
+        return Some(Runner::Host);
+    }
+    if platform != Platform::Android {
+        return None;
+    }
+    let abi = crate::platforms::android::build::arch_dir_name(arch).ok()?;
+    if exec.dry_run {
+        return Some(Runner::Adb);
+    }
+    adb_device_abis()
+        .await
+        .filter(|abis| abis.iter().any(|a| a == abi))
+        .map(|_| Runner::Adb)
+}
+
+/// ABIs of the single attached Android device, `None` without `adb` or a device.
+async fn adb_device_abis() -> Option<Vec<String>> {
+    let output = Command::new("adb")
+        .args(["shell", "getprop", "ro.product.cpu.abilist"])
+        .output()
+        .await
+        .ok()?;
+    output.status.success().then(|| {
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .split(',')
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+/// Push `exe` (and the shared libraries it needs) to the device and run it there.
+async fn run_on_device(
+    exe: &Path,
+    libs: &[PathBuf],
+    lib_type: LibType,
+    exec: ExecOptions,
+) -> Result<()> {
+    Command::new("adb")
+        .args(["shell", "mkdir", "-p", ADB_DIR])
+        .run_with(exec)
+        .await?;
+    let mut push = Command::new("adb");
+    push.arg("push").arg(exe);
+    if lib_type == LibType::Shared {
+        push.args(libs);
+    }
+    push.arg(format!("{ADB_DIR}/")).run_with(exec).await?;
+    Command::new("adb")
+        .arg("shell")
+        .arg(format!(
+            "cd {ADB_DIR} && chmod 755 smoke && LD_LIBRARY_PATH={ADB_DIR} ./smoke"
+        ))
+        .run_with(exec)
+        .await
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingEntry {
+    pub step: String,
+    pub library: String,
+    pub platform: String,
+    pub arch: String,
+    pub secs: f64,
+}
+
+/// Wall-clock durations of the individual build steps of one run.
+#[derive(Debug, Default, Serialize)]
+pub struct BuildTimings {
+    pub entries: Vec<TimingEntry>,
+}
+
+impl BuildTimings {
+    pub fn record(
+        &mut self,
+        step: &str,
+        library: impl ToString,
+        platform: impl ToString,
+        arch: impl ToString,
+        elapsed: Duration,
+    ) {
+        self.entries.push(TimingEntry {
+            step: step.to_string(),
+            library: library.to_string(),
+            platform: platform.to_string(),
+            arch: arch.to_string(),
+            secs: elapsed.as_secs_f64(),
+        });
+    }
+
+    pub fn total_secs(&self) -> f64 {
+        self.entries.iter().map(|e| e.secs).sum()
+    }
+
+    pub fn print_summary(&self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        println!("\nBuild timings:");
+        println!(
+            "  {:<12} {:<12} {:<10} {:<12} {:>9}",
+            "step", "library", "platform", "arch", "seconds"
+        );
+        for e in &self.entries {
+            println!(
+                "  {:<12} {:<12} {:<10} {:<12} {:>9.1}",
+                e.step, e.library, e.platform, e.arch, e.secs
+            );
+        }
+        println!("  {:<49} {:>9.1}", "total", self.total_secs());
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! Progress sections for people at a terminal, printed to stderr regardless of
+//! `RUST_LOG`. Details stay with the `log` crate.
+
+use crate::logging::{self, LogFormat};
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stderr is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output, given the value of `NO_COLOR` and whether
+    /// stderr is a terminal. Per <https://no-color.org>, an empty `NO_COLOR`
+    /// counts as unset.
+    pub fn use_color(self, no_color: Option<&str>, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && no_color.is_none_or(str::is_empty),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    color: bool,
+}
+
+static STYLE: OnceLock<Style> = OnceLock::new();
+
+/// Enables progress sections. With `--log-format json` they stay log records
+/// so stderr remains one JSON object per line.
+pub fn init(choice: ColorChoice, format: LogFormat) {
+    if format == LogFormat::Text {
+        let no_color = std::env::var("NO_COLOR").ok();
+        let color = choice.use_color(no_color.as_deref(), std::io::stderr().is_terminal());
+        STYLE.get_or_init(|| Style { color });
+    }
+}
+
+/// A step starting, e.g. "Building libopus for ios (arm64)".
+pub fn section(msg: impl Display) {
+    print("==>", "\x1b[1;34m", msg);
+}
+
+/// A step that finished successfully.
+pub fn success(msg: impl Display) {
+    print("==>", "\x1b[1;32m", msg);
+}
+
+fn print(marker: &str, color: &str, msg: impl Display) {
+    let Some(style) = STYLE.get() else {
+        // not initialized, e.g. when embedded as a library
+        log::info!("{msg}");
+        return;
+    };
+    if style.color {
+        eprintln!("{color}{marker}\x1b[0m \x1b[1m{msg}\x1b[0m");
+    } else {
+        eprintln!("{marker} {msg}");
+    }
+    logging::append(&format!("{marker} {msg}"));
+}
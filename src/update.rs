@@ -0,0 +1,72 @@
+use crate::config::{self, Library};
+use crate::repo;
+use anyhow::Result;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy)]
+pub struct UpdateOptions {
+    pub dry_run: bool,
+    pub offline: bool,
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
+}
+
+/// Bump each configured library's `version` in `build_config.toml` to the
+/// highest release tag of its upstream repo.
+pub async fn run(options: UpdateOptions) -> Result<()> {
+    if options.offline {
+        anyhow::bail!("`update` queries the upstream repos and cannot run with --offline");
+    }
+    let config_path = PathBuf::from("build_config.toml");
+    let config = config::load_or_create_config(&config_path, options.strict_config)?;
+
+    let mut updates: Vec<(Library, String)> = Vec::new();
+    for library in &config.general.libraries {
+        let version = config.get_library_version(library)?;
+        let current = version.as_ref();
+        if config.tracks_branch(library) {
+            println!("{library}: {current} tracks a branch, skipping");
+            continue;
+        }
+        if config
+            .libraries
+            .get(library)
+            .and_then(|opts| opts.source.as_ref())
+            .is_some_and(|source| source.tarball.is_some())
+        {
+            println!("{library}: built from a tarball, skipping");
+            continue;
+        }
+        let url = repo::remote_url(&config, library);
+        let Some(latest) = repo::latest_remote_tag(&url).await? else {
+            log::warn!("No release tags found for {library} at {url}");
+            continue;
+        };
+        if repo::parse_release_version(current).is_none() {
+            println!("{library}: {current} is not a release tag (latest is {latest}), skipping");
+            continue;
+        }
+        if repo::latest_release([current, latest.as_str()]) == Some(current) {
+            println!("{library}: {current} is up to date");
+            continue;
+        }
+        println!("{library}: {current} -> {latest}");
+        updates.push((*library, latest));
+    }
+
+    if updates.is_empty() {
+        return Ok(());
+    }
+    if options.dry_run {
+        println!("Dry run, {} not modified", config_path.display());
+        return Ok(());
+    }
+    config::write_library_versions(&config_path, &updates)?;
+    println!(
+        "Updated {} librar{} in {}",
+        updates.len(),
+        if updates.len() == 1 { "y" } else { "ies" },
+        config_path.display()
+    );
+    Ok(())
+}
@@ -1,41 +1,428 @@
-use anyhow::Result;
-use tokio::process::Command;
+use crate::config::{Arch, Library, Platform};
+use crate::logging;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::future::Future;
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+
+/// Options controlling how external commands are executed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecOptions {
+    /// Verbosity level (`-v` count): from 1 command output is streamed instead
+    /// of captured, from [`VERBOSE_COMMAND_ENV`] each command's working
+    /// directory and environment are logged too.
+    pub verbose: u8,
+    /// Log the rendered command and report success without spawning it.
+    pub dry_run: bool,
+    /// Forbid network access: no clones, fetches or downloads.
+    pub offline: bool,
+    /// Prefix every line of child output with `[<library>/<platform>/<arch>]`.
+    pub prefix_target: Option<(Library, Platform, Arch)>,
+    /// Kill the command if it runs longer than this.
+    pub timeout: Option<Duration>,
+    /// Lines of streamed output repeated in the error when a verbose command
+    /// fails; [`DEFAULT_FAILURE_TAIL_LINES`] when unset.
+    pub failure_tail_lines: Option<usize>,
+}
+
+pub const DEFAULT_FAILURE_TAIL_LINES: usize = 40;
+
+/// Verbosity from which spawned commands log their working directory and environment.
+pub const VERBOSE_COMMAND_ENV: u8 = 2;
+
+/// Verbosity from which config and repo resolution is traced.
+pub const VERBOSE_TRACE: u8 = 3;
+
+impl ExecOptions {
+    fn line_prefix(&self) -> Option<String> {
+        self.prefix_target
+            .map(|(library, platform, arch)| format!("[{library}/{platform}/{arch}] "))
+    }
+}
+
+/// Prepends `prefix` to every line of `text`.
+fn prefix_lines(text: &str, prefix: Option<&str>) -> String {
+    match prefix {
+        Some(prefix) => text
+            .lines()
+            .map(|line| format!("{prefix}{line}"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => text.to_string(),
+    }
+}
+
+/// Computes the hex-encoded SHA256 of a file without loading it into memory.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+/// Object members of a static (`ar`) archive, without symbol tables. Both GNU
+/// and BSD (`#1/<len>` names) member headers are understood.
+pub fn archive_members(data: &[u8]) -> Result<Vec<&[u8]>> {
+    let Some(mut rest) = data.strip_prefix(b"!<arch>\n".as_slice()) else {
+        anyhow::bail!("not an ar archive");
+    };
+
+    let mut members = Vec::new();
+    while rest.len() >= 60 {
+        let (header, body) = rest.split_at(60);
+        let name = String::from_utf8_lossy(&header[..16])
+            .trim_end()
+            .to_string();
+        let size: usize = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse()
+            .context("corrupt member header")?;
+        if body.len() < size {
+            anyhow::bail!("truncated member '{name}'");
+        }
+        let mut member = &body[..size];
+        let mut member_name = name.clone();
+        if let Some(len) = name.strip_prefix("#1/") {
+            let len: usize = len.parse().unwrap_or(0).min(member.len());
+            member_name = String::from_utf8_lossy(&member[..len])
+                .trim_end_matches('\0')
+                .to_string();
+            member = &member[len..];
+        }
+
+        let is_symbol_table = matches!(member_name.as_str(), "/" | "//" | "/SYM64/")
+            || member_name.starts_with("__.SYMDEF");
+        if !is_symbol_table {
+            members.push(member);
+        }
+
+        // members are 2-byte aligned
+        rest = &body[(size + size % 2).min(body.len())..];
+    }
+    Ok(members)
+}
+
+/// Counts the object members of a static archive that are LLVM bitcode,
+/// returning `(bitcode_members, total_members)`.
+pub fn archive_bitcode_members(path: &Path) -> Result<(usize, usize)> {
+    const RAW_BITCODE: &[u8] = b"BC\xc0\xde";
+    const WRAPPED_BITCODE: &[u8] = b"\xde\xc0\x17\x0b";
+
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let members =
+        archive_members(&data).with_context(|| format!("Failed to parse {}", path.display()))?;
+    let bitcode = members
+        .iter()
+        .filter(|m| m.starts_with(RAW_BITCODE) || m.starts_with(WRAPPED_BITCODE))
+        .count();
+    Ok((bitcode, members.len()))
+}
+
+/// Looks `program` up in the directories of `PATH`.
+pub fn find_in_path(program: &str) -> Option<std::path::PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
 
 /// Extension methods for `tokio::process::Command` to support a verbose mode.
 pub(crate) trait CommandVerboseExt {
-    /// Executes the command and controls output based on `verbose`.
+    /// Executes the command and controls output based on `options`.
     ///
-    /// - `verbose = true`: stream output directly
-    /// - `verbose = false`: capture output and only print it on failure
-    async fn run_with_verbose(&mut self, verbose: bool) -> Result<()>;
+    /// - `verbose >= 1`: stream output directly
+    /// - `verbose = 0`: capture output and only print it on failure
+    /// - `verbose >= 2`: also log the working directory and full environment
+    /// - `dry_run = true`: log the fully-rendered command without spawning it
+    /// - `prefix_target`: tag each output line with the build target
+    async fn run_with(&mut self, options: ExecOptions) -> Result<()>;
+
+    /// Like [`CommandVerboseExt::run_with`], also writing the command's whole
+    /// output to `log_path` whether it succeeds or not.
+    async fn run_with_log(&mut self, options: ExecOptions, log_path: &Path) -> Result<()>;
 }
 
 impl CommandVerboseExt for Command {
-    async fn run_with_verbose(&mut self, verbose: bool) -> Result<()> {
-        let desc = cmd_desc(self, verbose);
-        log::info!("Executing Command: {}", desc);
+    async fn run_with(&mut self, options: ExecOptions) -> Result<()> {
+        run_command(self, options, None).await
+    }
 
-        if verbose {
-            let status = self.status().await?;
-            if !status.success() {
+    async fn run_with_log(&mut self, options: ExecOptions, log_path: &Path) -> Result<()> {
+        run_command(self, options, Some(log_path)).await
+    }
+}
+
+/// [`CommandVerboseExt::run_with`], writing the output to `log_path` if set.
+async fn run_command(
+    cmd: &mut Command,
+    options: ExecOptions,
+    log_path: Option<&Path>,
+) -> Result<()> {
+    let verbose = options.verbose > 0;
+    let prefix = options.line_prefix();
+    let tag = prefix.as_deref().unwrap_or_default();
+    if options.dry_run {
+        log::info!("{tag}[dry-run] {:?}", cmd.as_std());
+        return Ok(());
+    }
+
+    let desc = cmd_desc(cmd, verbose);
+    log::info!("{tag}Executing Command: {}", desc);
+    if options.verbose >= VERBOSE_COMMAND_ENV {
+        log_command_context(cmd, tag);
+    }
+    if options.timeout.is_some() {
+        // dropping the timed-out future must also stop the process
+        cmd.kill_on_drop(true);
+        // and its own children, like the compilers `make` starts
+        #[cfg(unix)]
+        cmd.process_group(0);
+    }
+
+    if verbose {
+        logging::append(&format!("{tag}$ {}", desc));
+        let tail_lines = options
+            .failure_tail_lines
+            .unwrap_or(DEFAULT_FAILURE_TAIL_LINES);
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let child = cmd.spawn()?;
+        let pid = child.id();
+        // shared so the output read before a timeout still reaches `log_path`
+        let captured = Arc::new(Mutex::new(CapturedOutput::new(
+            tail_lines,
+            log_path.is_some(),
+        )));
+        let status = with_timeout(
+            stream_tee(child, prefix, captured.clone()),
+            options.timeout,
+            pid,
+            &desc,
+        )
+        .await;
+        let mut captured = captured.lock().unwrap_or_else(PoisonError::into_inner);
+        if let (Some(log_path), Some(all)) = (log_path, &captured.all) {
+            write_output_log(log_path, all.as_bytes())?;
+        }
+        let status = status?;
+        let tail: Vec<String> = captured.tail.drain(..).collect();
+        if !status.success() {
+            // the real error is usually far up in the scrollback; repeat it last
+            if tail.is_empty() {
                 anyhow::bail!("Command failed with exit code: {:?}", status.code());
             }
-        } else {
-            let output = self.output().await?;
-            if !output.status.success() {
-                if !output.stdout.is_empty() {
-                    eprintln!("\nSTDOUT:\n{}", String::from_utf8_lossy(&output.stdout));
-                }
-                if !output.stderr.is_empty() {
-                    eprintln!("\nSTDERR:\n{}", String::from_utf8_lossy(&output.stderr));
+            anyhow::bail!(
+                "Command failed with exit code: {:?}: {}\nLast {} lines of output:\n{}",
+                status.code(),
+                desc,
+                tail.len(),
+                tail.join("\n")
+            );
+        }
+    } else {
+        // what `output()` does, but keeping the pid to kill on timeout
+        let child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let pid = child.id();
+        let output = with_timeout(child.wait_with_output(), options.timeout, pid, &desc).await;
+        if let Some(log_path) = log_path {
+            // a killed command's captured output is lost, so at least say why
+            let logged = match &output {
+                Ok(output) => [&output.stdout[..], &output.stderr[..]].concat(),
+                Err(err) => format!("{err:#}\n").into_bytes(),
+            };
+            write_output_log(log_path, &logged)?;
+        }
+        let output = output?;
+        let stdout = prefix_lines(&String::from_utf8_lossy(&output.stdout), prefix.as_deref());
+        let stderr = prefix_lines(&String::from_utf8_lossy(&output.stderr), prefix.as_deref());
+        if logging::log_file_active() {
+            logging::append(&format!("{tag}$ {}", desc));
+            logging::append(&stdout);
+            logging::append(&stderr);
+        }
+        if !output.status.success() {
+            if !stdout.is_empty() {
+                eprintln!("\nSTDOUT:\n{}", stdout);
+            }
+            if !stderr.is_empty() {
+                eprintln!("\nSTDERR:\n{}", stderr);
+            }
+            eprintln!("\n{tag}Command failed: {}", desc);
+            eprintln!("Exit code: {:?}\n", output.status.code());
+
+            anyhow::bail!("Command failed with exit code: {:?}", output.status.code());
+        }
+    }
+    Ok(())
+}
+
+/// Awaits `fut`, failing with a clear error if it exceeds `timeout`. The
+/// process group of `pid` is killed then, not just the command itself.
+async fn with_timeout<T, E>(
+    fut: impl Future<Output = Result<T, E>>,
+    timeout: Option<Duration>,
+    pid: Option<u32>,
+    desc: &str,
+) -> Result<T>
+where
+    E: Into<anyhow::Error>,
+{
+    match timeout {
+        Some(limit) => match tokio::time::timeout(limit, fut).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => {
+                if let Some(pid) = pid {
+                    kill_process_group(pid);
                 }
-                eprintln!("\nCommand failed: {}", desc);
-                eprintln!("Exit code: {:?}\n", output.status.code());
+                anyhow::bail!(
+                    "Command timed out after {}s and was killed: {}",
+                    limit.as_secs(),
+                    desc
+                )
+            }
+        },
+        None => fut.await.map_err(Into::into),
+    }
+}
+
+/// Kills every process in the group led by `pid`; elsewhere only the child
+/// itself is killed, when its handle is dropped.
+fn kill_process_group(pid: u32) {
+    #[cfg(unix)]
+    {
+        let _ = std::process::Command::new("kill")
+            .args(["-KILL", "--", &format!("-{pid}")])
+            .stderr(Stdio::null())
+            .status();
+    }
+    #[cfg(not(unix))]
+    let _ = pid;
+}
+
+/// Streams the child's output to the terminal (optionally prefixed per line)
+/// while duplicating it into the log file. Returns the exit status and the last
+/// `tail_lines` lines of output.
+async fn stream_tee(
+    mut child: Child,
+    prefix: Option<String>,
+    captured: Arc<Mutex<CapturedOutput>>,
+) -> Result<ExitStatus> {
+    let stdout = child.stdout.take().context("child stdout is not piped")?;
+    let stderr = child.stderr.take().context("child stderr is not piped")?;
 
-                anyhow::bail!("Command failed with exit code: {:?}", output.status.code());
+    let stdout_task = tokio::spawn(forward_lines(
+        stdout,
+        false,
+        prefix.clone(),
+        captured.clone(),
+    ));
+    let stderr_task = tokio::spawn(forward_lines(stderr, true, prefix, captured));
+    let status = child.wait().await?;
+    stdout_task.await??;
+    stderr_task.await??;
+    Ok(status)
+}
+
+/// What is kept of a streamed command's output: the last `tail_lines` lines
+/// (prefixed) and, if requested, all of it as printed by the command.
+struct CapturedOutput {
+    tail: VecDeque<String>,
+    tail_lines: usize,
+    all: Option<String>,
+}
+
+impl CapturedOutput {
+    fn new(tail_lines: usize, keep_all: bool) -> Self {
+        Self {
+            tail: VecDeque::with_capacity(tail_lines),
+            tail_lines,
+            all: keep_all.then(String::new),
+        }
+    }
+}
+
+/// Writes a command's captured output to `path`, creating its directory.
+fn write_output_log(path: &Path, output: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, output).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+async fn forward_lines<R: AsyncRead + Unpin>(
+    reader: R,
+    to_stderr: bool,
+    prefix: Option<String>,
+    captured: Arc<Mutex<CapturedOutput>>,
+) -> std::io::Result<()> {
+    let prefix = prefix.unwrap_or_default();
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf).await? == 0 {
+            return Ok(());
+        }
+        let raw = String::from_utf8_lossy(&buf);
+        let raw = raw.trim_end_matches(['\r', '\n']);
+        let line = format!("{prefix}{raw}");
+        if to_stderr {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+        logging::append(&line);
+        let mut captured = captured.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(all) = &mut captured.all {
+            all.push_str(raw);
+            all.push('\n');
+        }
+        if captured.tail_lines > 0 {
+            if captured.tail.len() == captured.tail_lines {
+                captured.tail.pop_front();
             }
+            captured.tail.push_back(line);
         }
-        Ok(())
+    }
+}
+
+/// Logs where `cmd` runs and the environment it sees: the inherited one with
+/// the command's own changes applied.
+fn log_command_context(cmd: &Command, tag: &str) {
+    let std_cmd = cmd.as_std();
+    let cwd = match std_cmd.get_current_dir() {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir().unwrap_or_default(),
+    };
+    log::info!("{tag}  cwd: {}", cwd.display());
+    let mut env: std::collections::BTreeMap<_, _> = std::env::vars_os().collect();
+    for (key, value) in std_cmd.get_envs() {
+        match value {
+            Some(value) => env.insert(key.to_os_string(), value.to_os_string()),
+            None => env.remove(key),
+        };
+    }
+    for (key, value) in env {
+        log::info!(
+            "{tag}  env: {}={}",
+            key.to_string_lossy(),
+            value.to_string_lossy()
+        );
     }
 }
 
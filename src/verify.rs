@@ -0,0 +1,62 @@
+use crate::config;
+use crate::post_build::CHECKSUMS_FILE;
+use crate::utils;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy)]
+pub struct VerifyOptions {
+    pub verbose: u8,
+    /// Fail on unknown keys in `build_config.toml` instead of warning.
+    pub strict_config: bool,
+}
+
+/// Re-check every entry of `<build_dir>/SHA256SUMS` against the build tree.
+pub async fn run(options: VerifyOptions) -> Result<()> {
+    let config_path = PathBuf::from("build_config.toml");
+    let config = config::load_or_create_config(&config_path, options.strict_config)?;
+    let checked = verify_checksums(&config.paths.build_dir, options.verbose)?;
+    println!("All {checked} files match {}", CHECKSUMS_FILE);
+    Ok(())
+}
+
+/// Checks the files listed in `<build_dir>/SHA256SUMS`, returning how many
+/// matched; missing and changed files are printed and fail the check.
+pub fn verify_checksums(build_dir: &Path, verbose: u8) -> Result<usize> {
+    let checksums_path = build_dir.join(CHECKSUMS_FILE);
+    let checksums = fs::read_to_string(&checksums_path)
+        .with_context(|| format!("Failed to read {}", checksums_path.display()))?;
+
+    let mut checked = 0;
+    let mut failures = Vec::new();
+    for line in checksums.lines().filter(|l| !l.trim().is_empty()) {
+        let Some((expected, relative)) = line.split_once("  ") else {
+            anyhow::bail!("Malformed line in {}: {}", checksums_path.display(), line);
+        };
+        let path = build_dir.join(relative);
+        checked += 1;
+        if !path.is_file() {
+            failures.push(format!("{relative}: missing"));
+            continue;
+        }
+        let actual = utils::sha256_file(&path)?;
+        if actual != expected {
+            failures.push(format!("{relative}: checksum mismatch"));
+        } else if verbose > 0 {
+            log::info!("{relative}: OK");
+        }
+    }
+
+    if !failures.is_empty() {
+        for failure in &failures {
+            eprintln!("{failure}");
+        }
+        anyhow::bail!(
+            "{} of {} files failed verification",
+            failures.len(),
+            checked
+        );
+    }
+    Ok(checked)
+}
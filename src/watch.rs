@@ -0,0 +1,196 @@
+use crate::build::{self, BuildOptions};
+use crate::config::{self, Library};
+use crate::repo;
+use crate::ui;
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WatchOptions {
+    pub build: BuildOptions,
+    /// Quiet period after the last change before rebuilding.
+    pub debounce: Duration,
+}
+
+/// What a batch of file system events touched.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub config: bool,
+    /// Libraries whose checkout changed.
+    pub libraries: BTreeSet<Library>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        !self.config && self.libraries.is_empty()
+    }
+}
+
+/// Sort changed `paths` into the config file and the checkouts in `repos`
+/// (as `(checkout, library)` pairs). Paths inside `.git` are ignored.
+pub fn classify_changes(
+    paths: &[PathBuf],
+    config_path: &Path,
+    repos: &[(PathBuf, Library)],
+) -> Changes {
+    let mut changes = Changes::default();
+    for path in paths {
+        if path.components().any(|c| c.as_os_str() == ".git") {
+            continue;
+        }
+        if path == config_path {
+            changes.config = true;
+            continue;
+        }
+        if let Some((_, library)) = repos.iter().find(|(dir, _)| path.starts_with(dir)) {
+            changes.libraries.insert(*library);
+        }
+    }
+    changes
+}
+
+/// `changed` plus every configured library depending on one of them, which
+/// links against the rebuilt library.
+pub fn stale_libraries(
+    changed: &BTreeSet<Library>,
+    configured: &[Library],
+) -> Result<Vec<Library>> {
+    let mut stale = Vec::new();
+    for library in configured {
+        if library
+            .with_dependencies()?
+            .iter()
+            .any(|lib| changed.contains(lib))
+        {
+            stale.push(*library);
+        }
+    }
+    Ok(stale)
+}
+
+/// Build once, then rebuild whenever `build_config.toml` or a checkout changes.
+/// Checkouts are built as they are (like `build --resume`) so edits survive,
+/// and only the edited libraries and their dependents are rebuilt; the rest
+/// reuse the build cache, which also picks up config changes.
+pub async fn run(options: WatchOptions) -> Result<()> {
+    let config_path = std::path::absolute("build_config.toml")?;
+    let build_options = BuildOptions {
+        resume: true,
+        trust_cache: true,
+        ..options.build
+    };
+
+    rebuild(&config_path, &BTreeSet::new(), build_options).await;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                let _ = tx.send(event);
+            }
+            Err(e) => log::warn!("File watcher error: {e}"),
+        })?;
+    // editors often save by renaming over the file, which ends a watch on the file itself
+    let config_dir = config_path.parent().context("Config path has no parent")?;
+    watcher
+        .watch(config_dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", config_dir.display()))?;
+    let mut repos = watch_repos(&mut watcher, &config_path, build_options.strict_config, &[])?;
+
+    ui::section("Watching for changes (Ctrl-C to stop)");
+    loop {
+        let mut paths = Vec::new();
+        let Some(event) = rx.recv().await else {
+            return Ok(());
+        };
+        collect_paths(event, &mut paths);
+        while let Ok(Some(event)) = tokio::time::timeout(options.debounce, rx.recv()).await {
+            collect_paths(event, &mut paths);
+        }
+
+        let changes = classify_changes(&paths, &config_path, &repos);
+        if changes.is_empty() {
+            continue;
+        }
+        rebuild(&config_path, &changes.libraries, build_options).await;
+        if changes.config {
+            repos = watch_repos(
+                &mut watcher,
+                &config_path,
+                build_options.strict_config,
+                &repos,
+            )?;
+        }
+        // builds touch the checkouts too (autogen, in-tree objects); drop those events
+        tokio::time::sleep(options.debounce).await;
+        while rx.try_recv().is_ok() {}
+        ui::section("Watching for changes (Ctrl-C to stop)");
+    }
+}
+
+fn collect_paths(event: notify::Event, paths: &mut Vec<PathBuf>) {
+    if matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        paths.extend(event.paths);
+    }
+}
+
+/// Watch the checkouts of the configured libraries instead of `previous`.
+fn watch_repos(
+    watcher: &mut impl Watcher,
+    config_path: &Path,
+    strict_config: bool,
+    previous: &[(PathBuf, Library)],
+) -> Result<Vec<(PathBuf, Library)>> {
+    for (dir, _) in previous {
+        let _ = watcher.unwatch(dir);
+    }
+    let config = config::load_or_create_config(config_path, strict_config)?;
+    let mut repos = Vec::new();
+    for repo in repo::get_repos(&config)? {
+        if !repo.local_path.exists() {
+            continue;
+        }
+        let dir = std::path::absolute(&repo.local_path)?;
+        watcher
+            .watch(&dir, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", dir.display()))?;
+        for library in &config.general.libraries {
+            if library.repo_name() == repo.name {
+                repos.push((dir.clone(), *library));
+            }
+        }
+    }
+    Ok(repos)
+}
+
+/// Rebuild after `changed` libraries were edited. Failures are logged, not
+/// returned, so watching goes on until the next fix.
+async fn rebuild(config_path: &Path, changed: &BTreeSet<Library>, options: BuildOptions) {
+    let result = async {
+        let mut config = config::load_or_create_config(config_path, options.strict_config)?;
+        // the build cache lives in the intermediates
+        config.general.keep_intermediate = true;
+        for library in stale_libraries(changed, &config.general.libraries)? {
+            log::info!("Rebuilding {library}: its source or a dependency changed");
+            build::invalidate_build_cache(&config, &library)?;
+        }
+        build::run_with_config(config, options).await
+    }
+    .await;
+    match result {
+        Ok(report) => ui::success(format!(
+            "{} artifacts: {} built, {} reused from cache",
+            report.artifacts.len(),
+            report.built_count(),
+            report.cached_count()
+        )),
+        Err(e) => log::error!("Build failed: {e:#}"),
+    }
+}
@@ -0,0 +1,14 @@
+use opus_builder::platforms::android::build::host_platform_for;
+
+#[test]
+fn ndk_prebuilt_dir_per_host() {
+    assert_eq!(host_platform_for("macos").unwrap(), "darwin-x86_64");
+    assert_eq!(host_platform_for("linux").unwrap(), "linux-x86_64");
+    assert_eq!(host_platform_for("windows").unwrap(), "windows-x86_64");
+}
+
+#[test]
+fn unsupported_host_is_an_error() {
+    let err = host_platform_for("freebsd").unwrap_err();
+    assert!(format!("{err:#}").contains("freebsd"));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::{AndroidStl, Arch, Config};
+use opus_builder::platforms::android::build::{cmake_args, copy_stl_runtime, host_platform_for};
+use std::fs;
+
+#[test]
+fn shared_runtime_is_copied_from_the_ndk_sysroot() {
+    let tmp = TempDir::new("android-stl");
+    let mut config = Config::default();
+    config.platforms.android.ndk_path = tmp.join("ndk");
+    let prebuilt = tmp
+        .join("ndk/toolchains/llvm/prebuilt")
+        .join(host_platform_for(std::env::consts::OS).unwrap());
+    // the runtime is found relative to the toolchain's bin dir
+    fs::create_dir_all(prebuilt.join("bin")).unwrap();
+    let sysroot = prebuilt.join("sysroot/usr/lib");
+    for (triple, content) in [
+        ("aarch64-linux-android", "arm64"),
+        ("arm-linux-androideabi", "armv7"),
+    ] {
+        fs::create_dir_all(sysroot.join(triple)).unwrap();
+        fs::write(sysroot.join(triple).join("libc++_shared.so"), content).unwrap();
+    }
+
+    let dest = tmp.join("out");
+    copy_stl_runtime(&config, Arch::Arm64V8a, &dest).unwrap();
+    assert!(!dest.exists(), "nothing to ship without c++_shared");
+
+    config.platforms.android.stl = AndroidStl::CxxShared;
+    copy_stl_runtime(&config, Arch::Arm64V8a, &dest.join("arm64")).unwrap();
+    copy_stl_runtime(&config, Arch::ArmeabiV7a, &dest.join("armv7")).unwrap();
+    assert_eq!(
+        fs::read_to_string(dest.join("arm64/libc++_shared.so")).unwrap(),
+        "arm64"
+    );
+    assert_eq!(
+        fs::read_to_string(dest.join("armv7/libc++_shared.so")).unwrap(),
+        "armv7"
+    );
+}
+
+#[test]
+fn stl_is_read_from_config_and_passed_to_cmake() {
+    let mut config = Config::default();
+    config.platforms.android.stl = serde_json::from_str("\"c++_static\"").unwrap();
+    assert_eq!(config.platforms.android.stl, AndroidStl::CxxStatic);
+    assert!(
+        cmake_args(Arch::Arm64V8a, &config)
+            .unwrap()
+            .contains(&"-DANDROID_STL=c++_static".to_string())
+    );
+    assert_eq!(AndroidStl::None.ldflags(), None);
+    assert_eq!(Config::default().platforms.android.stl, AndroidStl::None);
+}
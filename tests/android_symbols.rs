@@ -0,0 +1,25 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::Arch;
+use opus_builder::platforms::android::build::copy_unstripped_symbols;
+use std::fs;
+
+#[test]
+fn shared_library_is_kept_per_abi() {
+    let tmp = TempDir::new("android-symbols");
+    let build_dir = tmp.path();
+    let packaged = build_dir.join("lib/android/arm64-v8a/libopus-1.5.2/libopus.so");
+    fs::create_dir_all(packaged.parent().unwrap()).unwrap();
+    fs::write(&packaged, b"\x7fELF").unwrap();
+
+    copy_unstripped_symbols(build_dir, Arch::Arm64V8a, &packaged).unwrap();
+    assert_eq!(
+        fs::read(build_dir.join("symbols/android/arm64-v8a/libopus.so")).unwrap(),
+        b"\x7fELF"
+    );
+
+    // a library that wasn't built is skipped
+    copy_unstripped_symbols(build_dir, Arch::X86, &build_dir.join("missing/libopus.so")).unwrap();
+    assert!(!build_dir.join("symbols/android/x86").exists());
+}
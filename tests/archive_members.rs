@@ -0,0 +1,37 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::utils;
+use std::fs;
+
+fn member(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        "{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+        0,
+        0,
+        0,
+        644,
+        data.len()
+    )
+    .into_bytes();
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(b'\n');
+    }
+    out
+}
+
+#[test]
+fn counts_bitcode_members_and_skips_symbol_table() {
+    let mut archive = b"!<arch>\n".to_vec();
+    archive.extend(member("/", b"\0\0\0\0"));
+    archive.extend(member("a.o/", b"\x7fELF\x02\x01\x01"));
+    archive.extend(member("b.o/", b"BC\xc0\xde\x35\x14"));
+    // BSD long name: `#1/<len>` followed by the name inside the member data
+    archive.extend(member("#1/8", b"c.o\0\0\0\0\0\xde\xc0\x17\x0b\x00"));
+
+    let dir = TempDir::new("archive-members");
+    let path = dir.join("libmixed.a");
+    fs::write(&path, archive).unwrap();
+    assert_eq!(utils::archive_bitcode_members(&path).unwrap(), (2, 3));
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::build::{self, SourceCommits};
+use opus_builder::config::{Arch, Config, Library, Platform};
+use std::fs;
+
+fn fake_artifact(config: &Config, platform: Platform, arch: Arch, library: &Library) {
+    for lib_type in config.platforms.get_lib_types_for_platform(&platform) {
+        let path = build::expected_library_path(
+            &config.paths.build_dir,
+            platform,
+            arch,
+            library,
+            *lib_type,
+        )
+        .expect("expected library path");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"!<arch>\n").unwrap();
+    }
+}
+
+#[test]
+fn changing_configure_flag_invalidates_cache() {
+    let mut config = Config::default();
+    let tmp = TempDir::new("cache-flags");
+    config.paths.build_dir = tmp.path().to_path_buf();
+    let (platform, arch, library) = (Platform::Android, Arch::Arm64V8a, Library::Libogg);
+    let commits = SourceCommits::new();
+
+    fake_artifact(&config, platform, arch, &library);
+    assert!(!build::build_artifact_ready(&config, platform, arch, &library, &commits).unwrap());
+
+    build::write_build_cache_key(&config, platform, arch, &library, &commits).unwrap();
+    assert!(build::build_artifact_ready(&config, platform, arch, &library, &commits).unwrap());
+
+    config
+        .build
+        .configure_flags
+        .push("--disable-crc".to_string());
+    assert!(!build::build_artifact_ready(&config, platform, arch, &library, &commits).unwrap());
+}
+
+#[test]
+fn changing_library_version_invalidates_cache() {
+    let mut config = Config::default();
+    let tmp = TempDir::new("cache-version");
+    config.paths.build_dir = tmp.path().to_path_buf();
+    let (platform, arch, library) = (Platform::Android, Arch::X86_64, Library::Libopus);
+    let commits = SourceCommits::new();
+
+    fake_artifact(&config, platform, arch, &library);
+    build::write_build_cache_key(&config, platform, arch, &library, &commits).unwrap();
+    assert!(build::build_artifact_ready(&config, platform, arch, &library, &commits).unwrap());
+
+    config.libraries.get_mut(&library).unwrap().version = Some("v1.5.1".to_string());
+    assert!(!build::build_artifact_ready(&config, platform, arch, &library, &commits).unwrap());
+}
+
+#[test]
+fn cache_key_ignores_unrelated_settings() {
+    let mut config = Config::default();
+    let commits = SourceCommits::new();
+    let before = build::build_cache_key(
+        &config,
+        Platform::Android,
+        Arch::X86,
+        &Library::Libogg,
+        &commits,
+    )
+    .expect("cache key");
+    config.build.make_concurrent_jobs = 1;
+    config.platforms.ios.min_version = "15.0".into();
+    let after = build::build_cache_key(
+        &config,
+        Platform::Android,
+        Arch::X86,
+        &Library::Libogg,
+        &commits,
+    )
+    .expect("cache key");
+    assert_eq!(before, after);
+}
+
+#[test]
+fn new_commit_invalidates_cache() {
+    let config = Config::default();
+    let (platform, arch, library) = (Platform::Android, Arch::Arm64V8a, Library::Libopus);
+    let mut commits = SourceCommits::new();
+    commits.insert(library.repo_name().to_string(), "0123abc".to_string());
+    let before = build::build_cache_key(&config, platform, arch, &library, &commits).unwrap();
+
+    commits.insert(library.repo_name().to_string(), "4567def".to_string());
+    let after = build::build_cache_key(&config, platform, arch, &library, &commits).unwrap();
+    assert_ne!(before, after);
+}
+
+#[test]
+fn changed_dependency_invalidates_dependents() {
+    let mut config = Config::default();
+    let (platform, arch) = (Platform::Android, Arch::Arm64V8a);
+    let mut commits = SourceCommits::new();
+    let key = |config: &Config, commits: &SourceCommits, library| {
+        build::build_cache_key(config, platform, arch, &library, commits).unwrap()
+    };
+    let opusfile = key(&config, &commits, Library::Libopusfile);
+    let opusenc = key(&config, &commits, Library::Libopusenc);
+
+    commits.insert(
+        Library::Libogg.repo_name().to_string(),
+        "0123abc".to_string(),
+    );
+    assert_ne!(key(&config, &commits, Library::Libopusfile), opusfile);
+    assert_eq!(key(&config, &commits, Library::Libopusenc), opusenc);
+
+    config.libraries.get_mut(&Library::Libopus).unwrap().version = Some("v1.5.1".to_string());
+    assert_ne!(key(&config, &commits, Library::Libopusenc), opusenc);
+}
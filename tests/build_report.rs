@@ -0,0 +1,43 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::build::{ArtifactInfo, BuildReport};
+use opus_builder::config::{Arch, LibType, Library, Platform};
+use std::fs;
+
+fn artifact(library: Library, arch: Arch, path: std::path::PathBuf) -> ArtifactInfo {
+    ArtifactInfo {
+        library,
+        platform: Platform::Android,
+        arch,
+        lib_type: LibType::Static,
+        path,
+        cached: false,
+        test_log: None,
+        flags: None,
+    }
+}
+
+#[test]
+fn verify_lists_every_missing_artifact() {
+    let dir = TempDir::new("report");
+    let present = dir.join("libogg.a");
+    fs::write(&present, b"!<arch>\n").unwrap();
+
+    let mut report = BuildReport::default();
+    report
+        .artifacts
+        .push(artifact(Library::Libogg, Arch::Arm64V8a, present));
+    assert!(report.verify().is_ok());
+
+    report.artifacts.push(artifact(
+        Library::Libopus,
+        Arch::X86_64,
+        dir.join("libopus.a"),
+    ));
+    let err = format!("{:#}", report.verify().unwrap_err());
+    assert!(err.contains("1 expected artifact(s) missing"), "{err}");
+    assert!(err.contains("libopus.a"), "{err}");
+    assert!(!err.contains("libogg.a"), "{err}");
+    assert_eq!(report.missing_artifacts().len(), 1);
+}
@@ -1,4 +1,4 @@
-use opus_builder::config::{self, Platform};
+use opus_builder::config::{self, Library, Platform};
 use opus_builder::platforms::{android, harmony};
 use std::fs;
 use std::path::Path;
@@ -40,7 +40,8 @@ fn has_header_file(dir: &Path) -> bool {
 fn check_build_artifacts() {
     let config_path = std::path::PathBuf::from("build_config.toml");
     assert!(config_path.exists(), "build_config.toml must exist");
-    let config = config::load_or_create_config(&config_path).expect("load build_config.toml");
+    let config =
+        config::load_or_create_config(&config_path, false).expect("load build_config.toml");
 
     let build_dir = &config.paths.build_dir;
 
@@ -52,68 +53,102 @@ fn check_build_artifacts() {
             let expected = build_dir.join("lib").join("darwin").join(format!(
                 "{}-{}.xcframework",
                 lib_name,
-                version_no_v(version)
+                version_no_v(&version)
             ));
             assert_dir_exists(&expected);
+
+            if config.general.platforms.contains(&Platform::MacCatalyst) {
+                let has_catalyst_slice = fs::read_dir(&expected)
+                    .expect("read xcframework")
+                    .flatten()
+                    .any(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        name.starts_with("ios-") && name.ends_with("-maccatalyst")
+                    });
+                assert!(
+                    has_catalyst_slice,
+                    "expected an ios-*-maccatalyst slice in {}",
+                    expected.display()
+                );
+            }
         }
     }
 
     if config.general.platforms.contains(&Platform::Android) {
-        let lib_type = config
+        let lib_types = config
             .platforms
-            .get_lib_type_for_platform(&Platform::Android);
-        let ext = lib_type.linux_ext();
+            .get_lib_types_for_platform(&Platform::Android);
         let archs = config.platforms.get_archs_for_platform(&Platform::Android);
 
         for lib in &config.general.libraries {
             let lib_name = lib.name_with_lib_prefix();
             let version = config.get_library_version(lib).expect("library version");
-            let version = version_no_v(version);
+            let version = version_no_v(&version);
             for arch in archs {
                 let abi = android::build::arch_dir_name(*arch).expect("android abi");
-                let expected = build_dir
-                    .join("lib")
-                    .join("android")
-                    .join(abi)
-                    .join(format!("{lib_name}-{version}"))
-                    .join(format!("{lib_name}.{ext}"));
-                assert_file_exists(&expected);
+                for lib_type in lib_types {
+                    let expected = build_dir
+                        .join("lib")
+                        .join("android")
+                        .join(abi)
+                        .join(format!("{lib_name}-{version}"))
+                        .join(format!("{lib_name}.{}", lib_type.linux_ext()));
+                    assert_file_exists(&expected);
+                }
             }
         }
     }
 
     if config.general.platforms.contains(&Platform::Harmony) {
-        let lib_type = config
+        let lib_types = config
             .platforms
-            .get_lib_type_for_platform(&Platform::Harmony);
-        let ext = lib_type.linux_ext();
+            .get_lib_types_for_platform(&Platform::Harmony);
         let archs = config.platforms.get_archs_for_platform(&Platform::Harmony);
 
         for lib in &config.general.libraries {
             let lib_name = lib.name_with_lib_prefix();
             let version = config.get_library_version(lib).expect("library version");
-            let version = version_no_v(version);
+            let version = version_no_v(&version);
             for arch in archs {
                 let abi = harmony::build::arch_dir_name(*arch).expect("harmony abi");
-                let expected = build_dir
-                    .join("lib")
-                    .join("harmony")
-                    .join(abi)
-                    .join(format!("{lib_name}-{version}"))
-                    .join(format!("{lib_name}.{ext}"));
-                assert_file_exists(&expected);
+                for lib_type in lib_types {
+                    let expected = build_dir
+                        .join("lib")
+                        .join("harmony")
+                        .join(abi)
+                        .join(format!("{lib_name}-{version}"))
+                        .join(format!("{lib_name}.{}", lib_type.linux_ext()));
+                    assert_file_exists(&expected);
+                }
             }
         }
     }
 
-    for lib in &config.general.libraries {
-        let lib_name = lib.name_with_lib_prefix();
-        let header_dir = build_dir.join("include").join(&lib_name);
-        assert_dir_exists(&header_dir);
-        assert!(
-            has_header_file(&header_dir),
-            "expected at least one .h under {}",
-            header_dir.display()
-        );
+    let include_roots: Vec<_> = if config.general.per_platform_headers {
+        config
+            .general
+            .platforms
+            .iter()
+            .map(|p| build_dir.join("include").join(p.to_string().to_lowercase()))
+            .collect()
+    } else {
+        vec![build_dir.join("include")]
+    };
+
+    for include_root in &include_roots {
+        for lib in &config.general.libraries {
+            let subdir = lib.include_dir();
+            let header_dir = include_root.join(subdir.file_name().expect("include subdir"));
+            assert_dir_exists(&header_dir);
+            assert!(
+                has_header_file(&header_dir),
+                "expected at least one .h under {}",
+                header_dir.display()
+            );
+        }
+
+        if config.general.libraries.contains(&Library::Libopusfile) {
+            assert_file_exists(&include_root.join("opus").join("opusfile.h"));
+        }
     }
 }
@@ -0,0 +1,32 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::post_build::write_checksums;
+use opus_builder::verify::verify_checksums;
+use std::fs;
+
+#[test]
+fn checksums_detect_changed_and_missing_files() {
+    let tmp = TempDir::new("checksums");
+    let build_dir = tmp.path();
+    fs::create_dir_all(build_dir.join("lib/android")).unwrap();
+    fs::create_dir_all(build_dir.join("include/opus")).unwrap();
+    fs::write(build_dir.join("lib/android/libopus.so"), b"\x7fELF").unwrap();
+    fs::write(build_dir.join("include/opus/opus.h"), "#pragma once\n").unwrap();
+
+    let sums = fs::read_to_string(write_checksums(build_dir).unwrap()).unwrap();
+    assert!(
+        sums.lines()
+            .any(|line| line.ends_with("  lib/android/libopus.so")),
+        "{sums}"
+    );
+    assert_eq!(verify_checksums(build_dir, 0).unwrap(), 2);
+
+    fs::write(build_dir.join("include/opus/opus.h"), "#pragma twice\n").unwrap();
+    let err = verify_checksums(build_dir, 0).unwrap_err();
+    assert!(format!("{err:#}").contains("1 of 2"), "{err:#}");
+
+    fs::remove_file(build_dir.join("lib/android/libopus.so")).unwrap();
+    let err = verify_checksums(build_dir, 0).unwrap_err();
+    assert!(format!("{err:#}").contains("2 of 2"), "{err:#}");
+}
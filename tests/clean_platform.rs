@@ -0,0 +1,46 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::clean::clean_platform;
+use opus_builder::config::{Config, Platform};
+use std::fs;
+
+#[test]
+fn only_the_platform_outputs_are_removed() {
+    let dir = TempDir::new("clean");
+    for sub in [
+        "android/arm64-v8a/opus",
+        "ios/arm64/opus",
+        "lib/android/arm64-v8a",
+        "lib/darwin/libopus-1.5.2.xcframework",
+        "include/opus",
+    ] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    let mut config = Config::default();
+    config.paths.build_dir = dir.path().to_path_buf();
+
+    clean_platform(&config, Platform::Android, true).unwrap();
+    assert!(dir.join("android").exists(), "dry run keeps everything");
+
+    clean_platform(&config, Platform::Android, false).unwrap();
+    let remaining: Vec<_> = [
+        "android",
+        "lib/android",
+        "ios",
+        "lib/darwin",
+        "include/opus",
+    ]
+    .iter()
+    .map(|sub| dir.join(sub).exists())
+    .collect();
+    assert_eq!(remaining, [false, false, true, true, true]);
+}
+
+#[test]
+fn apple_platforms_keep_the_shared_xcframeworks() {
+    let mut config = Config::default();
+    config.paths.build_dir = "build".into();
+    let dirs = opus_builder::clean::platform_dirs(&config, Platform::Ios);
+    assert_eq!(dirs, [std::path::PathBuf::from("build/ios")]);
+}
@@ -0,0 +1,20 @@
+use opus_builder::ui::ColorChoice;
+
+#[test]
+fn auto_colors_only_terminals() {
+    assert!(ColorChoice::Auto.use_color(None, true));
+    assert!(!ColorChoice::Auto.use_color(None, false));
+}
+
+#[test]
+fn auto_respects_no_color() {
+    assert!(!ColorChoice::Auto.use_color(Some("1"), true));
+    // an empty NO_COLOR counts as unset
+    assert!(ColorChoice::Auto.use_color(Some(""), true));
+}
+
+#[test]
+fn explicit_choice_wins() {
+    assert!(ColorChoice::Always.use_color(Some("1"), false));
+    assert!(!ColorChoice::Never.use_color(None, true));
+}
@@ -0,0 +1,45 @@
+//! Fixtures shared by the integration tests. Not every test binary uses all of them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh, empty directory, removed with its contents when dropped so a
+/// failing assertion doesn't leak it.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` only makes leftovers of a killed test run recognizable.
+    pub fn new(name: &str) -> Self {
+        Self::new_in(&std::env::temp_dir(), name)
+    }
+
+    /// Like [`TempDir::new`] but inside `parent`, e.g. a relative `repos` for
+    /// code that treats checkouts there as its own.
+    pub fn new_in(parent: &Path, name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = parent.join(format!(
+            "opus-builder-{name}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,94 @@
+use opus_builder::config::{self, Config, Library, Platform};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn lookup_from(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    move |key| vars.get(key).cloned()
+}
+
+#[test]
+fn env_overrides_take_precedence_over_file() {
+    let mut config = Config::default();
+    config::apply_overrides_from(
+        &mut config,
+        lookup_from(&[
+            (config::ENV_JOBS, "3"),
+            (config::ENV_PLATFORMS, "android, ios-sim"),
+            (config::ENV_LIBRARIES, "libogg"),
+            (config::ENV_ANDROID_NDK, "/opt/ndk"),
+            (config::ENV_HARMONY_NDK, "/opt/ohos"),
+        ]),
+    )
+    .expect("apply overrides");
+
+    assert_eq!(config.build.make_concurrent_jobs, 3);
+    assert_eq!(
+        config.general.platforms,
+        vec![Platform::Android, Platform::IosSim]
+    );
+    assert_eq!(config.general.libraries, vec![Library::Libogg]);
+    assert_eq!(config.platforms.android.ndk_path, PathBuf::from("/opt/ndk"));
+    assert_eq!(
+        config.platforms.harmony.ndk_path,
+        PathBuf::from("/opt/ohos")
+    );
+}
+
+#[test]
+fn unset_or_empty_env_keeps_file_values() {
+    let mut config = Config::default();
+    config::apply_overrides_from(&mut config, lookup_from(&[(config::ENV_JOBS, "")]))
+        .expect("apply overrides");
+
+    let default = Config::default();
+    assert_eq!(
+        config.build.make_concurrent_jobs,
+        default.build.make_concurrent_jobs
+    );
+    assert_eq!(config.general.platforms, default.general.platforms);
+}
+
+#[test]
+fn invalid_env_value_is_rejected() {
+    let mut config = Config::default();
+    let err = config::apply_overrides_from(
+        &mut config,
+        lookup_from(&[(config::ENV_PLATFORMS, "ios,windows")]),
+    )
+    .unwrap_err();
+    assert!(format!("{err:#}").contains("windows"));
+}
+
+#[test]
+fn standard_ndk_env_is_a_fallback_for_missing_ndk_path() {
+    let mut config = Config::default();
+    config.platforms.android.ndk_path = PathBuf::from("/nonexistent/android-ndk");
+    config::apply_overrides_from(
+        &mut config,
+        lookup_from(&[
+            ("ANDROID_NDK_HOME", "/opt/ndk-home"),
+            ("ANDROID_NDK_ROOT", "/opt/ndk-root"),
+        ]),
+    )
+    .expect("apply overrides");
+    assert_eq!(
+        config.platforms.android.ndk_path,
+        PathBuf::from("/opt/ndk-home")
+    );
+
+    let mut config = Config::default();
+    config.platforms.android.ndk_path = PathBuf::from("/nonexistent/android-ndk");
+    config::apply_overrides_from(
+        &mut config,
+        lookup_from(&[
+            (config::ENV_ANDROID_NDK, "/opt/ndk"),
+            ("ANDROID_NDK_HOME", "/opt/ndk-home"),
+        ]),
+    )
+    .expect("apply overrides");
+    assert_eq!(config.platforms.android.ndk_path, PathBuf::from("/opt/ndk"));
+}
@@ -0,0 +1,125 @@
+use opus_builder::config::{CONFIG_VERSION, LibType, LibraryBuildOptions, LibrarySource};
+use opus_builder::migrate;
+
+const OLD_CONFIG: &str = r#"# tuned for release builds
+[general]
+libraries = ["libopus"]
+
+[build]
+cflags = "-O3 -g -DNDEBUG -ffast-math"
+
+[platforms.ios]
+min_version = { default = "11.0", arm64 = "13.0" }
+archs = ["arm64"]
+lib_type = "static" # device only
+"#;
+
+#[test]
+fn old_config_is_upgraded_in_place() {
+    let mut document: toml_edit::DocumentMut = OLD_CONFIG.parse().unwrap();
+    let changes = migrate::migrate_document(&mut document);
+    assert_eq!(changes.len(), 4, "{changes:?}");
+
+    let migrated = document.to_string();
+    assert!(migrated.starts_with("# tuned for release builds\n"));
+    assert!(!migrated.contains("lib_type ="));
+
+    let value: toml::Value = toml::from_str(&migrated).unwrap();
+    assert_eq!(migrate::file_config_version(&value), CONFIG_VERSION);
+    assert_eq!(value["build"]["cflags"].as_str(), Some("-O3 -g -DNDEBUG"));
+    assert_eq!(value["build"]["fast_math"].as_bool(), Some(true));
+    // the default ldflags used to link with -flto
+    assert_eq!(value["build"]["lto"].as_str(), Some("full"));
+    let lib_types: Vec<LibType> = value["platforms"]["ios"]["lib_types"]
+        .clone()
+        .try_into()
+        .unwrap();
+    assert_eq!(lib_types, vec![LibType::Static]);
+
+    // migrating again is a no-op
+    assert!(migrate::migrate_document(&mut document).is_empty());
+}
+
+#[test]
+fn flto_moves_from_ldflags_to_lto() {
+    let mut document: toml_edit::DocumentMut =
+        "[build]\nldflags = \"-flto=thin -Wl,--gc-sections\"\n"
+            .parse()
+            .unwrap();
+    migrate::migrate_document(&mut document);
+    let value: toml::Value = toml::from_str(&document.to_string()).unwrap();
+    assert_eq!(
+        value["build"]["ldflags"].as_str(),
+        Some("-Wl,--gc-sections")
+    );
+    assert_eq!(value["build"]["lto"].as_str(), Some("thin"));
+
+    // ldflags without -flto were set on purpose and keep building without LTO
+    let mut document: toml_edit::DocumentMut =
+        "[general]\nconfig_version = 1\n[build]\nldflags = \"-fPIE\"\n"
+            .parse()
+            .unwrap();
+    migrate::migrate_document(&mut document);
+    let value: toml::Value = toml::from_str(&document.to_string()).unwrap();
+    assert_eq!(value["build"]["lto"].as_str(), Some("off"));
+}
+
+#[test]
+fn unknown_keys_are_reported_with_their_path() {
+    let value: toml::Value = toml::from_str(&format!(
+        "{OLD_CONFIG}\n[libraries.libopus]\nversion = \"v1.5.2\"\ncflags = \"-O2\"\nconfigure_flag = []\n"
+    ))
+    .unwrap();
+    assert!(
+        migrate::unknown_keys(&value)
+            .unwrap()
+            .contains(&"libraries.libopus.configure_flag".to_string())
+    );
+
+    let value: toml::Value =
+        toml::from_str("[build]\nmake_concurent_jobs = 4\n[darwin]\nbundle_name = \"Opus\"\n")
+            .unwrap();
+    assert_eq!(
+        migrate::unknown_keys(&value).unwrap(),
+        vec!["build.make_concurent_jobs".to_string()]
+    );
+}
+
+#[test]
+fn deprecated_and_optional_keys_are_known() {
+    let value: toml::Value = toml::from_str(OLD_CONFIG).unwrap();
+    assert!(migrate::unknown_keys(&value).unwrap().is_empty());
+    assert_eq!(migrate::file_config_version(&value), 0);
+}
+
+#[test]
+fn every_library_option_is_a_known_key() {
+    // unlike toml, serde_json keeps `None` fields (as null), so this lists every field
+    let options = LibraryBuildOptions {
+        source: Some(LibrarySource::default()),
+        ..Default::default()
+    };
+    let fields = serde_json::to_value(&options).unwrap();
+    let mut library = toml::Table::new();
+    for (key, value) in fields.as_object().unwrap() {
+        let item = match value.as_object() {
+            Some(nested) => toml::Value::Table(
+                nested
+                    .keys()
+                    .map(|k| (k.clone(), toml::Value::from("x")))
+                    .collect(),
+            ),
+            None => toml::Value::from("x"),
+        };
+        library.insert(key.clone(), item);
+    }
+    let mut libraries = toml::Table::new();
+    libraries.insert("libopus".to_string(), toml::Value::Table(library));
+    let mut config = toml::Table::new();
+    config.insert("libraries".to_string(), toml::Value::Table(libraries));
+
+    assert_eq!(
+        migrate::unknown_keys(&toml::Value::Table(config)).unwrap(),
+        Vec::<String>::new()
+    );
+}
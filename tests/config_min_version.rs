@@ -0,0 +1,31 @@
+use opus_builder::config::{Arch, DarwinConfig, MinVersion};
+
+#[test]
+fn plain_min_version_applies_to_every_arch() {
+    let ios: DarwinConfig = toml::from_str(
+        r#"
+        min_version = "12.0"
+        archs = ["arm64"]
+        lib_type = "static"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(ios.min_version, MinVersion::from("12.0"));
+    assert_eq!(ios.min_version.for_arch(Arch::Arm64), "12.0");
+}
+
+#[test]
+fn per_arch_min_version_falls_back_to_default() {
+    let ios_sim: DarwinConfig = toml::from_str(
+        r#"
+        min_version = { default = "11.0", arm64 = "14.0" }
+        archs = ["arm64", "x86_64"]
+        lib_types = ["static"]
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(ios_sim.min_version.for_arch(Arch::Arm64), "14.0");
+    assert_eq!(ios_sim.min_version.for_arch(Arch::X86_64), "11.0");
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config;
+use std::fs;
+
+#[test]
+fn unknown_keys_warn_unless_strict() {
+    let dir = TempDir::new("strict");
+    let path = dir.join("build_config.toml");
+    fs::write(
+        &path,
+        "[general]\nconfig_version = 1\n\n[build]\nmake_concurent_jobs = 4\n",
+    )
+    .unwrap();
+
+    let config = config::load_or_create_config(&path, false).unwrap();
+    assert_eq!(
+        config.build.make_concurrent_jobs,
+        config::Config::default().build.make_concurrent_jobs
+    );
+
+    let err = config::load_or_create_config(&path, true).unwrap_err();
+    assert!(format!("{err:#}").contains("build.make_concurent_jobs"));
+}
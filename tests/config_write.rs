@@ -0,0 +1,70 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::{self, Config, Library};
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_config(dir: &TempDir, contents: &str) -> PathBuf {
+    let path = dir.join("build_config.toml");
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn version_write_back_keeps_comments_and_order() {
+    let original = "# pinned for the 2.x app release\n\
+                    [libraries.libopus]\n\
+                    configure_flags = [\"--disable-doc\"] # keep docs out\n\
+                    version = \"v1.5.2\"\n\
+                    \n\
+                    [general]\n\
+                    libraries = [\"libopus\"]\n";
+    let dir = TempDir::new("write-versions");
+    let path = temp_config(&dir, original);
+
+    config::write_library_versions(
+        &path,
+        &[
+            (Library::Libopus, "v1.6".to_string()),
+            (Library::Libogg, "v1.3.6".to_string()),
+        ],
+    )
+    .unwrap();
+
+    let written = fs::read_to_string(&path).unwrap();
+    assert!(written.starts_with("# pinned for the 2.x app release\n[libraries.libopus]\n"));
+    assert!(
+        written.contains(
+            "configure_flags = [\"--disable-doc\"] # keep docs out\nversion = \"v1.6\"\n"
+        )
+    );
+    assert!(written.contains("[general]"));
+    assert!(written.contains("[libraries.libogg]\nversion = \"v1.3.6\"\n"));
+
+    let config: Config = toml::from_str(&written).unwrap();
+    assert_eq!(
+        config.get_library_version(&Library::Libopus).unwrap(),
+        "v1.6"
+    );
+    assert_eq!(
+        config.get_library_version(&Library::Libogg).unwrap(),
+        "v1.3.6"
+    );
+}
+
+#[test]
+fn edit_producing_an_invalid_config_is_not_written() {
+    let original = "[general]\nlibraries = [\"libopus\"]\n";
+    let dir = TempDir::new("write-invalid");
+    let path = temp_config(&dir, original);
+
+    let result = config::edit_config_file(&path, |document| {
+        document["general"]["libraries"] = toml_edit::value("libopus");
+        Ok(())
+    });
+
+    let written = fs::read_to_string(&path).unwrap();
+    assert!(result.is_err());
+    assert_eq!(written, original);
+}
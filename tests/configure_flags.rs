@@ -0,0 +1,65 @@
+use opus_builder::builder::configure_flags;
+use opus_builder::config::{Config, Library};
+
+#[test]
+fn global_configure_flags_come_before_library_flags() {
+    let mut config = Config::default();
+    config.build.configure_flags = vec!["--with-pic".to_string()];
+    config
+        .libraries
+        .get_mut(&Library::Libopus)
+        .unwrap()
+        .configure_flags = Some(vec!["--disable-doc".to_string()]);
+
+    assert_eq!(
+        configure_flags(&config, &Library::Libopus),
+        vec!["--with-pic", "--disable-doc"]
+    );
+    // libraries without their own flags still get the global ones
+    config
+        .libraries
+        .get_mut(&Library::Libogg)
+        .unwrap()
+        .configure_flags = None;
+    assert_eq!(
+        configure_flags(&config, &Library::Libogg),
+        vec!["--with-pic"]
+    );
+}
+
+#[test]
+fn enable_http_replaces_disable_http() {
+    let mut config = Config::default();
+    config.build.configure_flags = vec!["--with-pic".to_string()];
+    let opusfile = config.libraries.get_mut(&Library::Libopusfile).unwrap();
+    opusfile.configure_flags = Some(vec![
+        "--disable-http".to_string(),
+        "--disable-examples".to_string(),
+    ]);
+    opusfile.enable_http = true;
+
+    assert_eq!(
+        configure_flags(&config, &Library::Libopusfile),
+        vec!["--with-pic", "--disable-examples", "--enable-http"]
+    );
+}
+
+#[test]
+fn enable_http_is_only_written_when_set() {
+    assert!(
+        !opus_builder::config::default_config_toml()
+            .unwrap()
+            .contains("enable_http")
+    );
+
+    let mut config = Config::default();
+    config
+        .libraries
+        .get_mut(&Library::Libopusfile)
+        .unwrap()
+        .enable_http = true;
+    let written = toml::to_string(&config).unwrap();
+    assert_eq!(written.matches("enable_http = true").count(), 1);
+    let read: Config = toml::from_str(&written).unwrap();
+    assert!(read.libraries[&Library::Libopusfile].enable_http);
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::{Config, Library, Platform};
+use opus_builder::platforms::harmony;
+use opus_builder::post_build;
+use std::fs;
+
+#[test]
+fn harmony_only_build_copies_headers() {
+    let mut config = Config::default();
+    let tmp = TempDir::new("headers-harmony");
+    config.paths.build_dir = tmp.path().to_path_buf();
+    config.general.platforms = vec![Platform::Harmony];
+    config.general.libraries = vec![Library::Libogg, Library::Libopusfile];
+
+    let arch = config.platforms.get_archs_for_platform(&Platform::Harmony)[0];
+    let arch_dir = harmony::build::arch_dir_name(arch).unwrap();
+    for library in &config.general.libraries {
+        let include = config
+            .paths
+            .build_dir
+            .join("harmony")
+            .join(arch_dir)
+            .join(library.repo_name())
+            .join(library.include_dir());
+        fs::create_dir_all(&include).unwrap();
+        let header = match library {
+            Library::Libogg => "ogg.h",
+            _ => "opusfile.h",
+        };
+        fs::write(include.join(header), b"").unwrap();
+    }
+
+    let copied = post_build::copy_headers_from_build_artifacts(&config).unwrap();
+    assert_eq!(copied, 2);
+
+    let include_root = config.paths.build_dir.join("include");
+    assert!(include_root.join("ogg").join("ogg.h").is_file());
+    assert!(include_root.join("opus").join("opusfile.h").is_file());
+}
@@ -0,0 +1,146 @@
+use opus_builder::builder::{AutotoolsToolchain, assemble_flags};
+use opus_builder::config::{Arch, Config, LibType, Library, LibraryBuildOptions, Platform};
+use opus_builder::platforms::darwin::build::toolchain_for_sdk;
+
+fn toolchain() -> AutotoolsToolchain {
+    AutotoolsToolchain {
+        platform_dir: "android".to_string(),
+        arch_dir: "arm64-v8a".to_string(),
+        host: "aarch64-linux-android".to_string(),
+        cc: "clang --target=aarch64-linux-android21".to_string(),
+        cxx: None,
+        extra_env: Vec::new(),
+        base_cflags: "-O2 --sysroot=/ndk/sysroot".to_string(),
+        base_ldflags: "--sysroot=/ndk/sysroot".to_string(),
+    }
+}
+
+#[test]
+fn opusfile_gets_every_dependency_path() {
+    let mut config = Config::default();
+    config.paths.build_dir = "/nonexistent/build".into();
+    let flags = assemble_flags(
+        &config,
+        &Library::Libopusfile,
+        &toolchain(),
+        &[LibType::Static],
+        true,
+    )
+    .unwrap();
+
+    assert!(flags.cflags.starts_with("-O2 --sysroot=/ndk/sysroot"));
+    assert!(flags.cflags.contains("-fPIC"));
+    for dep in ["ogg", "opus"] {
+        let prefix = format!("/nonexistent/build/android/arm64-v8a/{dep}");
+        assert!(flags.cppflags.contains(&format!("-I{prefix}/include")));
+        assert!(flags.ldflags.contains(&format!("-L{prefix}/lib")));
+        assert!(
+            flags
+                .pkg_config_path
+                .contains(&format!("{prefix}/lib/pkgconfig"))
+        );
+    }
+}
+
+#[test]
+fn missing_dependencies_fail_unless_allowed() {
+    let mut config = Config::default();
+    config.paths.build_dir = "/nonexistent/build".into();
+    let result = assemble_flags(
+        &config,
+        &Library::Libopusfile,
+        &toolchain(),
+        &[LibType::Static],
+        false,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn libogg_has_no_dependency_paths() {
+    let flags = assemble_flags(
+        &Config::default(),
+        &Library::Libogg,
+        &toolchain(),
+        &[LibType::Shared],
+        false,
+    )
+    .unwrap();
+    assert_eq!(flags.cppflags, "");
+    assert_eq!(flags.pkg_config_path, "");
+    assert!(flags.ldflags.starts_with("--sysroot=/ndk/sysroot"));
+}
+
+#[test]
+fn opusfile_on_ios_arm64() {
+    let mut config = Config::default();
+    config.paths.build_dir = "/nonexistent/build".into();
+    config.platforms.ios.min_version = "13.0".into();
+    let toolchain = toolchain_for_sdk(
+        Platform::Ios,
+        Arch::Arm64,
+        &config,
+        "/sdk/iPhoneOS.sdk",
+        "clang".to_string(),
+    )
+    .unwrap();
+    let flags = assemble_flags(
+        &config,
+        &Library::Libopusfile,
+        &toolchain,
+        &[LibType::Static],
+        true,
+    )
+    .unwrap();
+
+    assert!(flags.cflags.contains("-isysroot /sdk/iPhoneOS.sdk"));
+    assert!(flags.cflags.contains("-miphoneos-version-min=13.0"));
+    assert!(flags.ldflags.contains("-miphoneos-version-min=13.0"));
+    for dep in ["ogg", "opus"] {
+        let prefix = format!("/nonexistent/build/ios/arm64/{dep}");
+        assert!(flags.cppflags.contains(&format!("-I{prefix}/include")));
+        assert!(flags.ldflags.contains(&format!("-L{prefix}/lib")));
+    }
+}
+
+#[test]
+fn library_overrides_and_env_are_included() {
+    let mut config = Config::default();
+    config.libraries.insert(
+        Library::Libogg,
+        LibraryBuildOptions {
+            cflags: Some("-DOGG_EXTRA".to_string()),
+            env: [("AR".to_string(), "llvm-ar".to_string())].into(),
+            ..Default::default()
+        },
+    );
+    let flags = assemble_flags(
+        &config,
+        &Library::Libogg,
+        &toolchain(),
+        &[LibType::Static],
+        false,
+    )
+    .unwrap();
+    assert!(flags.cflags.contains("-DOGG_EXTRA"));
+    assert_eq!(flags.env, [("AR".to_string(), "llvm-ar".to_string())]);
+}
+
+#[test]
+fn shipped_config_builds_with_lto() {
+    let config = opus_builder::config::load_or_create_config(
+        std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/build_config.toml")),
+        true,
+    )
+    .unwrap();
+    let flags = assemble_flags(
+        &config,
+        &Library::Libogg,
+        &toolchain(),
+        &[LibType::Static],
+        true,
+    )
+    .unwrap();
+    assert!(flags.cflags.contains("-flto"), "{}", flags.cflags);
+    assert!(flags.ldflags.contains("-flto"), "{}", flags.ldflags);
+}
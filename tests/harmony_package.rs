@@ -0,0 +1,102 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::{Arch, Config, LibType, Library, Platform};
+use opus_builder::platforms::harmony;
+use std::fs;
+
+#[test]
+fn installed_harmony_library_is_moved_to_lib_dir() {
+    let tmp = TempDir::new("harmony-package");
+    let build_dir = tmp.path();
+    let installed = build_dir.join("harmony/arm64-v8a/opus/lib/libopus.a");
+    fs::create_dir_all(installed.parent().unwrap()).unwrap();
+    fs::write(&installed, b"!<arch>\n").unwrap();
+
+    let packaged = harmony::build::move_harmony_package(
+        build_dir,
+        &Library::Libopus,
+        "v1.5.2",
+        Arch::Arm64V8a,
+        LibType::Static,
+        false,
+    )
+    .unwrap()
+    .expect("the installed library is packaged");
+
+    assert_eq!(
+        packaged,
+        build_dir.join("lib/harmony/arm64-v8a/libopus-1.5.2/libopus.a")
+    );
+    assert_eq!(fs::read(&packaged).unwrap(), b"!<arch>\n");
+}
+
+#[test]
+fn missing_harmony_library_fails_only_when_strict() {
+    let tmp = TempDir::new("harmony-missing");
+    let build_dir = tmp.path();
+    let package = |strict| {
+        harmony::build::move_harmony_package(
+            build_dir,
+            &Library::Libopus,
+            "v1.5.2",
+            Arch::Arm64V8a,
+            LibType::Static,
+            strict,
+        )
+    };
+
+    // nothing to report, and no empty package directory either
+    assert_eq!(package(false).unwrap(), None);
+    assert!(!build_dir.join("lib/harmony").exists());
+    let err = format!("{:#}", package(true).unwrap_err());
+    assert!(
+        err.contains("harmony/arm64-v8a/opus/lib/libopus.a"),
+        "{err}"
+    );
+}
+
+#[test]
+fn package_index_maps_archs_to_packaged_files() {
+    let mut config = Config::default();
+    let tmp = TempDir::new("harmony-index");
+    config.paths.build_dir = tmp.path().to_path_buf();
+    config.general.platforms = vec![Platform::Harmony];
+    config.general.libraries = vec![Library::Libogg];
+    config.platforms.harmony.archs = vec![Arch::Arm64V8a, Arch::X86_64];
+    config.platforms.harmony.lib_types = vec![LibType::Static];
+
+    let version = config.get_library_version(&Library::Libogg).unwrap();
+    let packaged = config.paths.build_dir.join(format!(
+        "lib/harmony/arm64-v8a/libogg-{}/libogg.a",
+        version.trim_start_matches('v')
+    ));
+    fs::create_dir_all(packaged.parent().unwrap()).unwrap();
+    fs::write(&packaged, b"!<arch>\n").unwrap();
+
+    let index_path = harmony::build::write_package_index(&config).unwrap();
+    let index: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&index_path).unwrap()).unwrap();
+
+    let libogg = &index["libraries"]["libogg"];
+    assert_eq!(libogg["version"], version.trim_start_matches('v'));
+    assert_eq!(
+        libogg["archs"]["arm64-v8a"]["static"],
+        format!(
+            "arm64-v8a/libogg-{}/libogg.a",
+            version.trim_start_matches('v')
+        )
+    );
+    // nothing was packaged for x86_64
+    assert!(libogg["archs"].get("x86_64").is_none());
+}
+
+#[test]
+fn index_keys_use_the_lib_types_spelling() {
+    for lib_type in [LibType::Static, LibType::Shared] {
+        assert_eq!(
+            format!("\"{}\"", lib_type.as_str()),
+            serde_json::to_string(&lib_type).unwrap()
+        );
+    }
+}
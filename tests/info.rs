@@ -0,0 +1,42 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::{BuildSystem, Config, Library};
+use opus_builder::info;
+use std::fs;
+
+#[test]
+fn built_commit_is_read_from_manifest() {
+    let mut config = Config::default();
+    let tmp = TempDir::new("info");
+    config.paths.build_dir = tmp.path().to_path_buf();
+    assert_eq!(info::manifest_commit(&config, &Library::Libopus), None);
+
+    fs::write(
+        config.paths.build_dir.join("manifest.json"),
+        r#"{"libraries": [
+            {"name": "libogg", "version": "v1.3.5", "commit": "0123abc", "source_url": "", "platforms": [], "files": []},
+            {"name": "libopus", "version": "v1.5.2", "commit": null, "source_url": "", "platforms": [], "files": []}
+        ]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        info::manifest_commit(&config, &Library::Libogg).as_deref(),
+        Some("0123abc")
+    );
+    assert_eq!(info::manifest_commit(&config, &Library::Libopus), None);
+}
+
+#[test]
+fn build_system_is_printed_as_configured() {
+    for build_system in [
+        BuildSystem::Autotools,
+        BuildSystem::Cmake,
+        BuildSystem::Meson,
+    ] {
+        assert_eq!(
+            format!("\"{build_system}\""),
+            serde_json::to_string(&build_system).unwrap()
+        );
+    }
+}
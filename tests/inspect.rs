@@ -0,0 +1,64 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::Arch;
+use opus_builder::inspect::{expected_arch, library_archs};
+use std::fs;
+use std::path::Path;
+
+/// A bare little-endian ELF64 header for `machine`, enough for `readelf -h`.
+fn elf_header(machine: u16) -> Vec<u8> {
+    let mut header = vec![0u8; 64];
+    header[..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+    header[16..18].copy_from_slice(&3u16.to_le_bytes()); // ET_DYN
+    header[18..20].copy_from_slice(&machine.to_le_bytes());
+    header[20..24].copy_from_slice(&1u32.to_le_bytes());
+    header[52..54].copy_from_slice(&64u16.to_le_bytes());
+    header
+}
+
+#[test]
+fn expected_arch_is_the_innermost_arch_component() {
+    assert_eq!(
+        expected_arch(Path::new(
+            "build/lib/android/arm64-v8a/libopus-1.5.2/libopus.so"
+        )),
+        Some(Arch::Arm64V8a)
+    );
+    assert_eq!(
+        expected_arch(Path::new("build/lib/ios/x86_64/libopus.a")),
+        Some(Arch::X86_64)
+    );
+    assert_eq!(expected_arch(Path::new("build/include/opus/opus.h")), None);
+}
+
+#[tokio::test]
+async fn rejects_files_that_are_not_libraries() {
+    let tmp = TempDir::new("inspect-text");
+    let path = tmp.join("opus.h");
+    fs::write(&path, "#pragma once\n").unwrap();
+    let err = library_archs(&path).await.unwrap_err();
+    assert!(
+        err.to_string().contains("neither a Mach-O nor an ELF"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn elf_machine_is_named_like_the_abi() {
+    if std::process::Command::new("readelf")
+        .arg("-v")
+        .output()
+        .is_err()
+    {
+        eprintln!("readelf not found, skipping");
+        return;
+    }
+    let tmp = TempDir::new("inspect-elf");
+    let arm64 = tmp.join("libarm64.so");
+    let x86_64 = tmp.join("libx86_64.so");
+    fs::write(&arm64, elf_header(183)).unwrap();
+    fs::write(&x86_64, elf_header(62)).unwrap();
+    assert_eq!(library_archs(&arm64).await.unwrap(), ["arm64-v8a"]);
+    assert_eq!(library_archs(&x86_64).await.unwrap(), ["x86_64"]);
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::builder::process_la_files;
+use opus_builder::config::LaFiles;
+use std::fs;
+
+fn install(prefix: &std::path::Path) {
+    let p = prefix.display();
+    fs::create_dir_all(prefix.join("lib/pkgconfig")).unwrap();
+    fs::write(
+        prefix.join("lib/libopusfile.la"),
+        format!(
+            "dlname=''\nlibrary_names=''\nold_library='libopusfile.a'\ndependency_libs=' -L/build/ogg/lib /build/ogg/lib/libogg.la -lm'\ninstalled=yes\nlibdir='{p}/lib'\n"
+        ),
+    )
+    .unwrap();
+    fs::write(
+        prefix.join("lib/pkgconfig/opusfile.pc"),
+        format!(
+            "prefix={p}\nexec_prefix=${{prefix}}\nlibdir={p}/lib\nincludedir={p}/include\n\nName: opusfile\nLibs: -L${{libdir}} -lopusfile\nCflags: -I${{includedir}}/opus\n"
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn relocate_rewrites_absolute_paths() {
+    let tmp = TempDir::new("la-relocate");
+    install(tmp.path());
+
+    process_la_files(tmp.path(), LaFiles::Relocate).unwrap();
+
+    let la = fs::read_to_string(tmp.join("lib/libopusfile.la")).unwrap();
+    assert!(la.contains("dependency_libs='-logg -lm'"), "{la}");
+    assert!(la.contains("libdir='lib'"), "{la}");
+    let pc = fs::read_to_string(tmp.join("lib/pkgconfig/opusfile.pc")).unwrap();
+    assert!(pc.contains("prefix=${pcfiledir}/../..\n"), "{pc}");
+    assert!(pc.contains("libdir=${prefix}/lib\n"), "{pc}");
+    assert!(pc.contains("includedir=${prefix}/include\n"), "{pc}");
+    assert!(!pc.contains(&tmp.path().display().to_string()), "{pc}");
+}
+
+#[test]
+fn delete_removes_la_files_and_keep_leaves_them() {
+    let tmp = TempDir::new("la-delete");
+    install(tmp.path());
+    let la = fs::read_to_string(tmp.join("lib/libopusfile.la")).unwrap();
+
+    process_la_files(tmp.path(), LaFiles::Keep).unwrap();
+    assert_eq!(
+        fs::read_to_string(tmp.join("lib/libopusfile.la")).unwrap(),
+        la
+    );
+
+    process_la_files(tmp.path(), LaFiles::Delete).unwrap();
+    assert!(!tmp.join("lib/libopusfile.la").exists());
+    assert!(tmp.join("lib/pkgconfig/opusfile.pc").exists());
+}
@@ -0,0 +1,53 @@
+use opus_builder::build::FailedTargets;
+use opus_builder::config::{self, Arch, Library, Platform};
+
+#[test]
+fn dependencies_are_built_first() {
+    let sorted = config::sort_by_dependencies(&[
+        Library::Libopusfile,
+        Library::Libopusenc,
+        Library::Libopus,
+        Library::Libogg,
+    ])
+    .unwrap();
+
+    let pos = |lib| sorted.iter().position(|l| *l == lib).unwrap();
+    assert_eq!(sorted.len(), 4);
+    for library in &sorted {
+        for dep in library.deps() {
+            assert!(pos(*dep) < pos(*library), "{dep} must precede {library}");
+        }
+    }
+}
+
+#[test]
+fn unconfigured_dependencies_are_skipped() {
+    let sorted = config::sort_by_dependencies(&[Library::Libopusenc]).unwrap();
+    assert_eq!(sorted, vec![Library::Libopusenc]);
+}
+
+#[test]
+fn keep_going_skips_dependents_of_failed_targets() {
+    let mut failed = FailedTargets::default();
+    failed.insert(Platform::Android, Arch::Arm64V8a, Library::Libogg);
+
+    let dependency =
+        |platform, arch, library| failed.failed_dependency(platform, arch, &library).unwrap();
+    assert_eq!(
+        dependency(Platform::Android, Arch::Arm64V8a, Library::Libopusfile),
+        Some(Library::Libogg)
+    );
+    assert_eq!(
+        dependency(Platform::Android, Arch::Arm64V8a, Library::Libopus),
+        None
+    );
+    assert_eq!(
+        dependency(Platform::Android, Arch::X86_64, Library::Libopusfile),
+        None
+    );
+    // the failed library itself is not its own dependency
+    assert_eq!(
+        dependency(Platform::Android, Arch::Arm64V8a, Library::Libogg),
+        None
+    );
+}
@@ -0,0 +1,16 @@
+use opus_builder::config::Config;
+
+#[test]
+fn make_jobs_default_override_and_zero() {
+    let mut config = Config::default();
+    assert_eq!(config.build.resolved_make_jobs(), 8);
+
+    config.build.make_concurrent_jobs = 3;
+    assert_eq!(config.build.resolved_make_jobs(), 3);
+
+    // 0 means one job per available CPU
+    config.build.make_concurrent_jobs = 0;
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get() as u32);
+    assert_eq!(config.build.resolved_make_jobs(), cpus);
+    assert!(config.build.resolved_make_jobs() >= 1);
+}
@@ -0,0 +1,38 @@
+use opus_builder::config::{BuildSystem, Config, Library, LibraryBuildOptions};
+use opus_builder::repo;
+
+#[test]
+fn make_targets_default_to_install() {
+    assert_eq!(LibraryBuildOptions::default().make_targets(), ["install"]);
+    let config = Config::default();
+    let opus = config.libraries.get(&Library::Libopus).unwrap();
+    assert_eq!(opus.make_targets(), ["install"]);
+    assert!(opus.make_args.is_empty());
+}
+
+#[test]
+fn make_targets_and_args_are_read_from_the_config() {
+    let opts: LibraryBuildOptions = toml::from_str(
+        r#"
+        version = "v1.5.2"
+        make_targets = ["check", "install-strip"]
+        make_args = ["V=1"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(opts.make_targets(), ["check", "install-strip"]);
+    assert_eq!(opts.make_args, ["V=1"]);
+}
+
+#[test]
+fn make_options_are_rejected_for_other_build_systems() {
+    let mut config = Config::default();
+    config.general.libraries = vec![Library::Libopus];
+    let opus = config.libraries.get_mut(&Library::Libopus).unwrap();
+    opus.build_system = BuildSystem::Cmake;
+    opus.make_args = vec!["V=1".to_string()];
+
+    let err = format!("{:#}", repo::get_repos(&config).unwrap_err());
+    assert!(err.contains("libraries.libopus"), "{err}");
+    assert!(err.contains("only apply to autotools"), "{err}");
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::{Arch, Config, LibType, Library, Platform};
+use opus_builder::post_build::{output_dirs, write_manifest};
+use opus_builder::utils::sha256_file;
+use std::fs;
+
+#[tokio::test]
+async fn manifest_lists_shipped_files() {
+    let tmp = TempDir::new("manifest");
+    let mut config = Config::default();
+    config.paths.build_dir = tmp.path().to_path_buf();
+    config.general.platforms = vec![Platform::Android];
+    config.general.libraries = vec![Library::Libogg];
+    config.platforms.android.archs = vec![Arch::Arm64V8a];
+
+    let dir = output_dirs(&config, &Library::Libogg).unwrap().remove(0);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("libogg.so"), b"\x7fELF").unwrap();
+
+    let path = write_manifest(&config, &[]).await.unwrap();
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let libogg = &manifest["libraries"][0];
+    assert_eq!(libogg["name"], "libogg");
+    assert_eq!(libogg["version"], "v1.3.5");
+    assert_eq!(libogg["commit"], serde_json::Value::Null);
+    assert_eq!(libogg["platforms"][0]["platform"], "android");
+    assert_eq!(
+        libogg["platforms"][0]["archs"][0],
+        Arch::Arm64V8a.to_string()
+    );
+    assert_eq!(libogg["platforms"][0]["lib_types"][0], "shared");
+    assert_eq!(
+        libogg["files"][0]["path"],
+        "lib/android/arm64-v8a/libogg-1.3.5/libogg.so"
+    );
+    assert_eq!(libogg["files"][0]["size"], 4);
+    assert_eq!(
+        libogg["files"][0]["sha256"],
+        sha256_file(&dir.join("libogg.so")).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn manifest_lists_the_shared_xcframework() {
+    let tmp = TempDir::new("manifest-xcframework");
+    let mut config = Config::default();
+    config.paths.build_dir = tmp.path().to_path_buf();
+    config.general.platforms = vec![Platform::Ios];
+    config.general.libraries = vec![Library::Libogg];
+    config.platforms.ios.lib_types = vec![LibType::Static, LibType::Shared];
+
+    let darwin = tmp.join("lib/darwin");
+    for name in [
+        "libogg-1.3.5.xcframework",
+        "libogg-1.3.5-shared.xcframework",
+    ] {
+        fs::create_dir_all(darwin.join(name)).unwrap();
+        fs::write(darwin.join(name).join("Info.plist"), name).unwrap();
+    }
+
+    let path = write_manifest(&config, &[]).await.unwrap();
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let mut paths: Vec<&str> = manifest["libraries"][0]["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["path"].as_str().unwrap())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [
+            "lib/darwin/libogg-1.3.5-shared.xcframework/Info.plist",
+            "lib/darwin/libogg-1.3.5.xcframework/Info.plist"
+        ]
+    );
+}
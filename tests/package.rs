@@ -0,0 +1,90 @@
+mod common;
+
+use common::TempDir;
+use flate2::read::GzDecoder;
+use opus_builder::config::{Config, Platform};
+use opus_builder::package::{ArchiveFormat, PackageOptions, package};
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+fn packaged(tmp: &TempDir, format: ArchiveFormat) -> PathBuf {
+    let mut config = Config::default();
+    config.paths.build_dir = tmp.join("build");
+    config.general.platforms = vec![Platform::Android];
+    fs::create_dir_all(tmp.join("build/include/opus")).unwrap();
+    fs::create_dir_all(tmp.join("build/lib/android")).unwrap();
+    fs::write(tmp.join("build/include/opus/opus.h"), "#pragma once\n").unwrap();
+    fs::write(tmp.join("build/lib/android/libopus.so"), b"\x7fELF").unwrap();
+
+    let options = PackageOptions {
+        output_dir: tmp.join("dist"),
+        format,
+        strict_config: false,
+    };
+    package(&config, &options).unwrap()
+}
+
+#[test]
+fn tar_gz_is_named_after_the_version_and_platforms() {
+    let tmp = TempDir::new("package-tar");
+    let path = packaged(&tmp, ArchiveFormat::TarGz);
+    assert_eq!(path, tmp.join("dist/opus-1.5.2-android.tar.gz"));
+
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(&path).unwrap()));
+    let names: Vec<String> = archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            entry
+                .unwrap()
+                .path()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    assert!(
+        names.iter().all(|name| name.starts_with("opus-1.5.2/")),
+        "{names:?}"
+    );
+    assert!(
+        names.contains(&"opus-1.5.2/include/opus/opus.h".to_string()),
+        "{names:?}"
+    );
+    assert!(
+        names.contains(&"opus-1.5.2/lib/android/libopus.so".to_string()),
+        "{names:?}"
+    );
+}
+
+#[test]
+fn zip_has_the_same_layout() {
+    let tmp = TempDir::new("package-zip");
+    let path = packaged(&tmp, ArchiveFormat::Zip);
+    assert_eq!(path, tmp.join("dist/opus-1.5.2-android.zip"));
+
+    let archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let mut names: Vec<&str> = archive.file_names().collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "opus-1.5.2/include/opus/opus.h",
+            "opus-1.5.2/lib/android/libopus.so"
+        ]
+    );
+}
+
+#[test]
+fn nothing_to_package_without_a_build() {
+    let tmp = TempDir::new("package-empty");
+    let mut config = Config::default();
+    config.paths.build_dir = tmp.join("build");
+    let options = PackageOptions {
+        output_dir: tmp.join("dist"),
+        format: ArchiveFormat::TarGz,
+        strict_config: false,
+    };
+    let err = package(&config, &options).unwrap_err();
+    assert!(err.to_string().contains("Nothing to package"), "{err}");
+}
@@ -0,0 +1,36 @@
+use opus_builder::builder::set_position_independence;
+use opus_builder::config::LibType;
+
+#[test]
+fn shared_builds_replace_pie_with_pic() {
+    let mut cflags = "-O3 -fPIE".to_string();
+    let mut ldflags = "-fPIE -pie -Wl,-z,max-page-size=16384".to_string();
+    set_position_independence(
+        &mut cflags,
+        &mut ldflags,
+        &[LibType::Static, LibType::Shared],
+    );
+    assert_eq!(cflags, "-O3 -fPIC");
+    assert_eq!(ldflags, "-Wl,-z,max-page-size=16384");
+}
+
+#[test]
+fn pic_is_added_once() {
+    let mut cflags = "-O3 -fPIC".to_string();
+    let mut ldflags = String::new();
+    set_position_independence(&mut cflags, &mut ldflags, &[LibType::Shared]);
+    assert_eq!(cflags, "-O3 -fPIC");
+
+    let mut cflags = String::new();
+    set_position_independence(&mut cflags, &mut ldflags, &[LibType::Static]);
+    assert_eq!(cflags, "-fPIC");
+}
+
+#[test]
+fn static_only_builds_keep_explicit_pie() {
+    let mut cflags = "-O3".to_string();
+    let mut ldflags = "-fPIE".to_string();
+    set_position_independence(&mut cflags, &mut ldflags, &[LibType::Static]);
+    assert_eq!(cflags, "-O3");
+    assert_eq!(ldflags, "-fPIE");
+}
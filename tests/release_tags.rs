@@ -0,0 +1,19 @@
+use opus_builder::repo::{latest_release, parse_release_version};
+
+#[test]
+fn release_versions_are_parsed_without_prereleases() {
+    assert_eq!(parse_release_version("v1.5.2"), Some(vec![1, 5, 2]));
+    assert_eq!(parse_release_version("0.12"), Some(vec![0, 12]));
+    assert_eq!(parse_release_version("v1.5-rc1"), None);
+    assert_eq!(parse_release_version("branch:main"), None);
+}
+
+#[test]
+fn latest_release_compares_numerically() {
+    let tags = ["v1.3.5", "v1.10", "v1.9.9", "v2.0-beta", "v1.4"];
+    assert_eq!(latest_release(tags), Some("v1.10"));
+    // the configured spelling of an equal release is kept
+    assert_eq!(latest_release(["v1.5", "v1.5.0"]), Some("v1.5"));
+    assert_eq!(latest_release(["v1.5.0", "v1.5"]), Some("v1.5.0"));
+    assert_eq!(latest_release(["nightly"]), None);
+}
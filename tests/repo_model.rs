@@ -0,0 +1,44 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::ExecOptions;
+use opus_builder::repo::{ModelSource, REPO_CACHE_DIR, Repo};
+use std::fs;
+use std::path::Path;
+
+#[tokio::test]
+async fn failed_model_download_leaves_no_cached_file() {
+    let root = TempDir::new("model");
+    let models = root.join("models");
+    fs::create_dir_all(&models).unwrap();
+    let local_path = root.join("checkout");
+    fs::create_dir_all(&local_path).unwrap();
+
+    // the cache lives in `repo-cache/<repo name>`, so name the repo after a temp dir there
+    let cache = TempDir::new_in(Path::new(REPO_CACHE_DIR), "model-cache");
+    let name = cache.path().file_name().unwrap().to_string_lossy();
+    let repo = Repo {
+        name: name.to_string(),
+        url: String::new(),
+        local_path: local_path.clone(),
+        version: "v1.5.2".to_string(),
+        cache_globs: Vec::new(),
+        model: Some(ModelSource {
+            base_url: format!("file://{}", models.display()),
+            version: Some("abc123".to_string()),
+        }),
+        in_monorepo: false,
+        tarball: None,
+        tarball_sha256: None,
+    };
+
+    assert!(repo.ensure_model(ExecOptions::default()).await.is_err());
+    assert!(!cache.join("opus_data-abc123.tar.gz").exists());
+    assert!(!cache.join("opus_data-abc123.tar.gz.part").exists());
+
+    fs::write(models.join("opus_data-abc123.tar.gz"), "model").unwrap();
+    repo.ensure_model(ExecOptions::default()).await.unwrap();
+    assert!(cache.join("opus_data-abc123.tar.gz").is_file());
+    assert!(!cache.join("opus_data-abc123.tar.gz.part").exists());
+    assert!(local_path.join("opus_data-abc123.tar.gz").is_file());
+}
@@ -0,0 +1,72 @@
+use opus_builder::repo::{Repo, prepare_parallel};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+fn repo(name: &str, in_monorepo: bool) -> Repo {
+    Repo {
+        name: name.to_string(),
+        url: String::new(),
+        local_path: PathBuf::from("repos").join(name),
+        version: "v1".to_string(),
+        cache_globs: Vec::new(),
+        model: None,
+        in_monorepo,
+        tarball: None,
+        tarball_sha256: None,
+    }
+}
+
+/// Runs `repos` through `prepare_parallel`, returning the most repos that were
+/// prepared at the same time and the results.
+async fn max_concurrency(repos: &[Repo], max_parallel: usize) -> (usize, Vec<anyhow::Result<()>>) {
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let results = prepare_parallel(repos, max_parallel, |repo| {
+        let running = running.clone();
+        let peak = peak.clone();
+        async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            match repo.name.as_str() {
+                "bad" => anyhow::bail!("{} failed", repo.name),
+                _ => Ok(()),
+            }
+        }
+    })
+    .await;
+    (peak.load(Ordering::SeqCst), results)
+}
+
+#[tokio::test]
+async fn runs_at_most_max_parallel_repos_at_once() {
+    let repos: Vec<_> = ["ogg", "opus", "opusenc", "opusfile"]
+        .iter()
+        .map(|name| repo(name, false))
+        .collect();
+    let (peak, results) = max_concurrency(&repos, 2).await;
+    assert_eq!(peak, 2);
+    assert!(results.iter().all(Result::is_ok));
+
+    let (peak, _) = max_concurrency(&repos, 1).await;
+    assert_eq!(peak, 1);
+}
+
+#[tokio::test]
+async fn results_keep_the_repo_order_and_every_repo_runs() {
+    let repos = [repo("ogg", false), repo("bad", false), repo("opus", false)];
+    let (_, results) = max_concurrency(&repos, 4).await;
+    assert!(results[0].is_ok());
+    assert_eq!(results[1].as_ref().unwrap_err().to_string(), "bad failed");
+    assert!(results[2].is_ok());
+}
+
+#[tokio::test]
+async fn monorepo_libraries_take_turns() {
+    let repos = [repo("ogg", true), repo("opus", true), repo("opusenc", true)];
+    let (peak, _) = max_concurrency(&repos, 4).await;
+    assert_eq!(peak, 1);
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::ExecOptions;
+use opus_builder::clean::{CleanOptions, run_with_config};
+use opus_builder::config::{Config, Library};
+use opus_builder::repo::{DEFAULT_CLONE_DIR, Repo};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("run git");
+    assert!(status.success(), "git {args:?} failed");
+}
+
+/// A one-commit upstream repo tagged `v1.0.0`.
+fn origin(root: &TempDir) -> PathBuf {
+    let origin = root.join("origin");
+    fs::create_dir_all(&origin).unwrap();
+    git(&origin, &["init", "-q"]);
+    fs::write(origin.join("README"), "libogg").unwrap();
+    git(&origin, &["add", "README"]);
+    git(&origin, &["commit", "-q", "-m", "initial"]);
+    git(&origin, &["tag", "v1.0.0"]);
+    origin
+}
+
+#[tokio::test]
+async fn directory_without_git_is_recloned() {
+    let root = TempDir::new("reclone");
+    let origin = origin(&root);
+
+    // what an interrupted clone leaves behind; only `repos/` is ours to delete
+    let clones = TempDir::new_in(Path::new(DEFAULT_CLONE_DIR), "reclone");
+    let local_path = clones.join("ogg");
+    fs::create_dir_all(&local_path).unwrap();
+    fs::write(local_path.join("partial"), "").unwrap();
+
+    let repo = Repo {
+        name: "ogg".to_string(),
+        url: origin.display().to_string(),
+        local_path: local_path.clone(),
+        version: "v1.0.0".to_string(),
+        cache_globs: Vec::new(),
+        model: None,
+        in_monorepo: false,
+        tarball: None,
+        tarball_sha256: None,
+    };
+    repo.ensure(ExecOptions::default())
+        .await
+        .expect("ensure re-clones the partial checkout");
+
+    assert!(local_path.join(".git").exists() && local_path.join("README").exists());
+    assert!(!local_path.join("partial").exists());
+}
+
+#[tokio::test]
+async fn dot_slash_repos_is_still_ours() {
+    let root = TempDir::new("reclone-dot");
+    let origin = origin(&root);
+
+    // `repo_path = ["./repos"]` spells the clone dir differently
+    let clones = TempDir::new_in(Path::new(DEFAULT_CLONE_DIR), "reclone-dot");
+    let local_path = Path::new(".").join(clones.path()).join("ogg");
+    fs::create_dir_all(&local_path).unwrap();
+    fs::write(local_path.join("partial"), "").unwrap();
+
+    let repo = Repo {
+        name: "ogg".to_string(),
+        url: origin.display().to_string(),
+        local_path: local_path.clone(),
+        version: "v1.0.0".to_string(),
+        cache_globs: Vec::new(),
+        model: None,
+        in_monorepo: false,
+        tarball: None,
+        tarball_sha256: None,
+    };
+    repo.ensure(ExecOptions::default())
+        .await
+        .expect("ensure re-clones the partial checkout under ./repos");
+    assert!(local_path.join("README").exists());
+    assert!(!local_path.join("partial").exists());
+}
+
+#[tokio::test]
+async fn user_directory_without_git_is_kept() {
+    let root = TempDir::new("reclone-user");
+    // found via paths.repo_path: someone's source tree, not a clone of ours
+    let local_path = root.join("src").join("ogg");
+    fs::create_dir_all(&local_path).unwrap();
+    fs::write(local_path.join("work.c"), "int main(void) { return 0; }").unwrap();
+
+    let repo = Repo {
+        name: "ogg".to_string(),
+        url: root.join("origin").display().to_string(),
+        local_path: local_path.clone(),
+        version: "v1.0.0".to_string(),
+        cache_globs: Vec::new(),
+        model: None,
+        in_monorepo: false,
+        tarball: None,
+        tarball_sha256: None,
+    };
+    let err = format!(
+        "{:#}",
+        repo.ensure(ExecOptions::default()).await.unwrap_err()
+    );
+
+    assert!(err.contains("paths.repo_path"), "{err}");
+    assert!(local_path.join("work.c").exists());
+}
+
+#[tokio::test]
+async fn clean_reclone_keeps_user_directories_unless_allowed() {
+    let root = TempDir::new("clean-reclone");
+    let local_path = root.join("src").join("ogg");
+    fs::create_dir_all(&local_path).unwrap();
+    fs::write(local_path.join("work.c"), "int main(void) { return 0; }").unwrap();
+
+    let mut config = Config::default();
+    config.paths.repo_path = vec![root.join("src")];
+    config.general.libraries = vec![Library::Libogg];
+    let mut options = CleanOptions {
+        verbose: 0,
+        dry_run: false,
+        clean_build_dir: false,
+        clean_repos: true,
+        reclone: true,
+        reclone_user_repos: false,
+        clean_cache: false,
+        strict_config: false,
+    };
+    let err = format!("{:#}", run_with_config(&config, options).await.unwrap_err());
+    assert!(err.contains("--reclone-user-repos"), "{err}");
+    assert!(local_path.join("work.c").exists());
+
+    options.reclone_user_repos = true;
+    run_with_config(&config, options).await.unwrap();
+    assert!(!local_path.exists());
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::ExecOptions;
+use opus_builder::config::{Config, Library, LibrarySource};
+use opus_builder::repo::Repo;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("run git");
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+#[tokio::test]
+async fn ref_newer_than_the_clone_is_fetched() {
+    let root = TempDir::new("ref");
+    let origin = root.join("origin");
+    fs::create_dir_all(&origin).unwrap();
+    git(&origin, &["init", "-q"]);
+    fs::write(origin.join("README"), "v1").unwrap();
+    git(&origin, &["add", "README"]);
+    git(&origin, &["commit", "-q", "-m", "initial"]);
+    git(&origin, &["tag", "v1.0.0"]);
+
+    let local_path = root.join("repos").join("ogg");
+    let mut repo = Repo {
+        name: "ogg".to_string(),
+        url: origin.display().to_string(),
+        local_path: local_path.clone(),
+        version: "v1.0.0".to_string(),
+        cache_globs: Vec::new(),
+        model: None,
+        in_monorepo: false,
+        tarball: None,
+        tarball_sha256: None,
+    };
+    repo.ensure(ExecOptions::default()).await.unwrap();
+
+    // upstream moves on after the clone
+    fs::write(origin.join("README"), "v2").unwrap();
+    git(&origin, &["commit", "-q", "-am", "second"]);
+    let second = git(&origin, &["rev-parse", "HEAD"]);
+
+    repo.version = second[..12].to_string();
+    repo.ensure(ExecOptions::default())
+        .await
+        .expect("ensure fetches the unknown commit");
+    assert_eq!(git(&local_path, &["rev-parse", "HEAD"]), second);
+}
+
+#[test]
+fn override_version_replaces_the_configured_one() {
+    let mut config = Config::default();
+    config
+        .override_version(&Library::Libopus, "abc1234")
+        .unwrap();
+    assert_eq!(
+        config.get_library_version(&Library::Libopus).unwrap(),
+        "abc1234"
+    );
+
+    config.libraries.get_mut(&Library::Libogg).unwrap().source = Some(LibrarySource {
+        tarball: Some("ogg.tar.gz".to_string()),
+        ..Default::default()
+    });
+    assert!(config.override_version(&Library::Libogg, "v1.3.5").is_err());
+}
+
+#[test]
+fn branch_versions_are_one_path_component() {
+    let mut config = Config::default();
+    config
+        .override_version(&Library::Libopus, "branch:release/1.5")
+        .unwrap();
+    assert!(config.tracks_branch(&Library::Libopus));
+    assert_eq!(
+        config.get_library_version(&Library::Libopus).unwrap(),
+        "release-1.5"
+    );
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::ExecOptions;
+use opus_builder::repo::{self, Repo};
+use std::fs;
+use std::process::Command;
+
+#[tokio::test]
+async fn tarball_source_is_extracted_and_restored_by_clean() {
+    let root = TempDir::new("tarball");
+    let release = root.join("libogg-1.3.5");
+    fs::create_dir_all(&release).unwrap();
+    fs::write(release.join("configure"), "#!/bin/sh\n").unwrap();
+    let tarball = root.join("libogg-1.3.5.tar.gz");
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(&tarball)
+        .arg("-C")
+        .arg(root.path())
+        .arg("libogg-1.3.5")
+        .status()
+        .expect("run tar");
+    assert!(status.success());
+
+    let local_path = root.join("repos").join("ogg");
+    let repo = Repo {
+        name: "ogg".to_string(),
+        url: String::new(),
+        local_path: local_path.clone(),
+        version: "v1.3.5".to_string(),
+        cache_globs: Vec::new(),
+        model: None,
+        in_monorepo: false,
+        tarball: Some(tarball.display().to_string()),
+        tarball_sha256: None,
+    };
+
+    repo.ensure(ExecOptions::default())
+        .await
+        .expect("ensure extracts the tarball");
+    assert!(local_path.join("configure").exists());
+
+    fs::write(local_path.join("config.status"), "").unwrap();
+    repo.clean(ExecOptions::default())
+        .await
+        .expect("clean extracts the tarball again");
+    assert!(local_path.join("configure").exists());
+    assert!(!local_path.join("config.status").exists());
+    assert!(repo.head_commit().await.is_err());
+}
+
+#[test]
+fn sha256_is_checked_against_known_hashes() {
+    let dir = TempDir::new("sha256");
+    let path = dir.join("abc.txt");
+    fs::write(&path, "abc").unwrap();
+    let good = repo::verify_sha256(
+        &path,
+        "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD",
+    );
+    let bad = repo::verify_sha256(
+        &path,
+        "0000000000000000000000000000000000000000000000000000000000000000",
+    );
+
+    good.expect("matching hash");
+    let err = format!("{:#}", bad.unwrap_err());
+    assert!(err.contains("SHA256 mismatch"), "{err}");
+    assert!(
+        err.contains("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn tarball_with_wrong_sha256_is_not_extracted() {
+    let root = TempDir::new("badsum");
+    let tarball = root.join("libogg-1.3.5.tar.gz");
+    fs::write(&tarball, "not really a tarball").unwrap();
+
+    let local_path = root.join("repos").join("ogg");
+    let repo = Repo {
+        name: "ogg".to_string(),
+        url: String::new(),
+        local_path: local_path.clone(),
+        version: "v1.3.5".to_string(),
+        cache_globs: Vec::new(),
+        model: None,
+        in_monorepo: false,
+        tarball: Some(tarball.display().to_string()),
+        tarball_sha256: Some("0".repeat(64)),
+    };
+    let result = repo.ensure(ExecOptions::default()).await;
+
+    let err = format!("{:#}", result.unwrap_err());
+    assert!(err.contains("SHA256 mismatch"), "{err}");
+    assert!(!local_path.exists());
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::{Arch, Config, LibType, Library, Platform};
+use opus_builder::{BuildOptions, build, utils};
+
+/// Builds libogg for Android twice from a clean tree and compares the archives.
+#[tokio::test]
+#[ignore = "needs the Android NDK configured in build_config.toml and network access"]
+async fn consecutive_clean_builds_are_identical() {
+    let dir = TempDir::new("repro");
+    let (platform, arch, library) = (Platform::Android, Arch::Arm64V8a, Library::Libogg);
+
+    let mut hashes = Vec::new();
+    for _ in 0..2 {
+        let mut config = opus_builder::load_or_create_config("build_config.toml".as_ref(), false)
+            .unwrap_or_else(|_| Config::default());
+        config.paths.build_dir = dir.join("build");
+        config.platforms.android.lib_types = vec![LibType::Static];
+        config.build.reproducible = true;
+
+        let _ = std::fs::remove_dir_all(&config.paths.build_dir);
+        let options = BuildOptions {
+            verbose: 0,
+            dry_run: false,
+            offline: false,
+            jobs: None,
+            force: true,
+            log_file: false,
+            keep_going: false,
+            reclone: false,
+            reclone_user_repos: false,
+            resume: false,
+            trust_cache: false,
+            strict: false,
+            run_tests: false,
+            smoke_test: false,
+            strict_config: false,
+        };
+        build::run_single(config, library, platform, arch, options)
+            .await
+            .expect("build libogg");
+
+        let lib = build::expected_library_path(
+            &dir.join("build"),
+            platform,
+            arch,
+            &library,
+            LibType::Static,
+        )
+        .unwrap();
+        hashes.push(utils::sha256_file(&lib).unwrap());
+    }
+    assert_eq!(hashes[0], hashes[1]);
+}
@@ -0,0 +1,33 @@
+use opus_builder::build;
+use opus_builder::config::{Arch, Library, Platform};
+use std::path::Path;
+
+#[test]
+fn cross_compiled_targets_do_not_run_on_host() {
+    assert!(!build::runs_on_host(Platform::Android, Arch::Arm64V8a));
+    assert!(!build::runs_on_host(Platform::Ios, Arch::Arm64));
+    assert!(!build::runs_on_host(Platform::Harmony, Arch::X86_64));
+    let native = if cfg!(target_arch = "aarch64") {
+        Arch::Arm64
+    } else {
+        Arch::X86_64
+    };
+    assert_eq!(
+        build::runs_on_host(Platform::Macos, native),
+        cfg!(target_os = "macos")
+    );
+}
+
+#[test]
+fn test_logs_are_kept_outside_intermediate_dirs() {
+    assert_eq!(
+        build::test_log_path(
+            Path::new("build"),
+            Platform::Macos,
+            Arch::Arm64,
+            &Library::Libopus
+        )
+        .unwrap(),
+        Path::new("build/logs/tests/libopus-macos-arm64.log")
+    );
+}
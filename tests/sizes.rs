@@ -0,0 +1,75 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::sizes::{SizeEntry, SizeSummary, format_size};
+use std::fs;
+
+fn entry(path: &str, bytes: u64) -> SizeEntry {
+    SizeEntry {
+        path: path.to_string(),
+        bytes,
+    }
+}
+
+#[test]
+fn groups_by_platform_and_library() {
+    assert_eq!(
+        entry("lib/android/arm64-v8a/libopus-1.5.2/libopus.a", 1).group(),
+        "android/libopus-1.5.2"
+    );
+    assert_eq!(
+        entry("lib/darwin/libogg-1.3.5.xcframework/Info.plist", 1).group(),
+        "darwin/libogg-1.3.5.xcframework"
+    );
+    assert_eq!(entry("lib/harmony/index.json", 1).group(), "harmony");
+    assert_eq!(entry("include/opus/opus.h", 1).group(), "include/opus");
+}
+
+#[test]
+fn flags_changes_over_ten_percent() {
+    let previous = SizeSummary {
+        entries: vec![
+            entry("lib/a.a", 1000),
+            entry("lib/b.a", 1000),
+            entry("lib/c.a", 1000),
+        ],
+    };
+    let current = SizeSummary {
+        entries: vec![
+            entry("lib/a.a", 2000),
+            entry("lib/b.a", 1050),
+            entry("lib/c.a", 800),
+            entry("lib/new.a", 5000),
+        ],
+    };
+    let changes = current.changes_since(&previous);
+    let paths: Vec<_> = changes.iter().map(|c| c.path.as_str()).collect();
+    assert_eq!(paths, ["lib/a.a", "lib/c.a"]);
+    assert_eq!(changes[0].ratio(), 1.0);
+}
+
+#[test]
+fn collects_lib_and_include() {
+    let dir = TempDir::new("sizes");
+    fs::create_dir_all(dir.join("lib/android/x86/libogg-1.3.5")).unwrap();
+    fs::create_dir_all(dir.join("include/ogg")).unwrap();
+    fs::create_dir_all(dir.join("android")).unwrap();
+    fs::write(dir.join("lib/android/x86/libogg-1.3.5/libogg.a"), [0; 100]).unwrap();
+    fs::write(dir.join("include/ogg/ogg.h"), [0; 10]).unwrap();
+    fs::write(dir.join("android/intermediate.o"), [0; 1000]).unwrap();
+
+    let summary = SizeSummary::collect(dir.path()).unwrap();
+    assert_eq!(summary.total_bytes(), 110);
+
+    let saved = dir.join("last_sizes.json");
+    summary.write_json(&saved).unwrap();
+    assert_eq!(SizeSummary::load(&saved).unwrap(), Some(summary));
+    assert_eq!(SizeSummary::load(&dir.join("missing.json")).unwrap(), None);
+}
+
+#[test]
+fn formats_sizes() {
+    assert_eq!(format_size(512), "512 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+}
@@ -0,0 +1,28 @@
+use opus_builder::config::Library;
+use opus_builder::smoke;
+
+#[test]
+fn dependents_are_linked_before_their_dependencies() {
+    assert_eq!(
+        smoke::link_order(&Library::Libopusfile).unwrap(),
+        vec![Library::Libopusfile, Library::Libopus, Library::Libogg]
+    );
+    assert_eq!(
+        smoke::link_order(&Library::Libogg).unwrap(),
+        vec![Library::Libogg]
+    );
+}
+
+#[test]
+fn programs_include_the_installed_headers() {
+    for (library, header) in [
+        (Library::Libogg, "<ogg/ogg.h>"),
+        (Library::Libopus, "<opus/opus.h>"),
+        (Library::Libopusenc, "<opus/opusenc.h>"),
+        (Library::Libopusfile, "<opus/opusfile.h>"),
+    ] {
+        let source = smoke::program_source(&library);
+        assert!(source.contains(header), "{library}: {source}");
+        assert!(source.contains("int main(void)"), "{library}");
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::TempDir;
+use opus_builder::config::{Arch, LibType, Library, Platform};
+use opus_builder::platforms::darwin::build::create_universal_binary;
+use opus_builder::utils::ExecOptions;
+use std::fs;
+use std::path::Path;
+
+/// Installs libogg for `arch` on macOS, as `make install` would.
+fn install(build_dir: &Path, arch: &str) {
+    let prefix = build_dir.join("macos").join(arch).join("ogg");
+    fs::create_dir_all(prefix.join("lib")).unwrap();
+    fs::write(prefix.join("lib/libogg.a"), b"!<arch>\n").unwrap();
+    fs::create_dir_all(prefix.join("include/ogg")).unwrap();
+    fs::write(prefix.join("include/ogg/ogg.h"), arch).unwrap();
+}
+
+const DRY_RUN: ExecOptions = ExecOptions {
+    verbose: 0,
+    dry_run: true,
+    offline: false,
+    prefix_target: None,
+    timeout: None,
+    failure_tail_lines: None,
+};
+
+#[tokio::test]
+async fn missing_arch_fails_when_strict() {
+    let tmp = TempDir::new("universal-strict");
+    install(tmp.path(), "arm64");
+
+    let err = create_universal_binary(
+        tmp.path(),
+        Platform::Macos,
+        &Library::Libogg,
+        LibType::Static,
+        &[Arch::Arm64, Arch::X86_64],
+        true,
+        DRY_RUN,
+    )
+    .await
+    .unwrap_err();
+    assert!(format!("{err:#}").contains("x86_64"), "{err:#}");
+}
+
+#[tokio::test]
+async fn headers_come_from_a_built_arch() {
+    let tmp = TempDir::new("universal-headers");
+    // x86_64 sorts first but wasn't built
+    install(tmp.path(), "arm64");
+
+    create_universal_binary(
+        tmp.path(),
+        Platform::Macos,
+        &Library::Libogg,
+        LibType::Static,
+        &[Arch::Arm64, Arch::X86_64],
+        false,
+        DRY_RUN,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(tmp.join("macos/universal/ogg/include/ogg/ogg.h")).unwrap(),
+        "arm64"
+    );
+}
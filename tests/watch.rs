@@ -0,0 +1,48 @@
+#![cfg(feature = "watch")]
+
+use opus_builder::config::Library;
+use opus_builder::watch::{classify_changes, stale_libraries};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+fn repos() -> Vec<(PathBuf, Library)> {
+    vec![
+        (PathBuf::from("/w/repos/ogg"), Library::Libogg),
+        (PathBuf::from("/w/repos/opus"), Library::Libopus),
+    ]
+}
+
+#[test]
+fn sorts_changes_into_config_and_libraries() {
+    let config = Path::new("/w/build_config.toml");
+    let changes = classify_changes(
+        &[
+            PathBuf::from("/w/repos/ogg/src/framing.c"),
+            PathBuf::from("/w/repos/opus/.git/index"),
+            PathBuf::from("/w/build/last_sizes.json"),
+        ],
+        config,
+        &repos(),
+    );
+    assert!(!changes.config);
+    assert_eq!(changes.libraries, BTreeSet::from([Library::Libogg]));
+
+    let changes = classify_changes(&[config.to_path_buf()], config, &repos());
+    assert!(changes.config);
+    assert!(changes.libraries.is_empty());
+}
+
+#[test]
+fn dependents_of_changed_libraries_are_stale() {
+    let configured = [
+        Library::Libogg,
+        Library::Libopus,
+        Library::Libopusenc,
+        Library::Libopusfile,
+    ];
+    let stale = stale_libraries(&BTreeSet::from([Library::Libogg]), &configured).unwrap();
+    assert_eq!(stale, [Library::Libogg, Library::Libopusfile]);
+
+    let stale = stale_libraries(&BTreeSet::from([Library::Libopusenc]), &configured).unwrap();
+    assert_eq!(stale, [Library::Libopusenc]);
+}